@@ -0,0 +1,96 @@
+use nalgebra_glm::{Vec3, min2, max2};
+
+// Caja alineada a los ejes usada por la jerarquía de volúmenes envolventes
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        // Aceptamos las esquinas en cualquier orden, igual que Cube
+        Aabb {
+            min: min2(&min, &max),
+            max: max2(&min, &max),
+        }
+    }
+
+    // Caja vacía: la unión con cualquier otra caja devuelve la otra caja
+    pub fn empty() -> Self {
+        Aabb {
+            min: Vec3::repeat(f32::INFINITY),
+            max: Vec3::repeat(f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: min2(&self.min, &other.min),
+            max: max2(&self.max, &other.max),
+        }
+    }
+
+    pub fn grow(&self, point: &Vec3) -> Aabb {
+        Aabb {
+            min: min2(&self.min, point),
+            max: max2(&self.max, point),
+        }
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let e = self.extent();
+        if e.x < 0.0 || e.y < 0.0 || e.z < 0.0 {
+            return 0.0;
+        }
+        2.0 * (e.x * e.y + e.y * e.z + e.z * e.x)
+    }
+
+    // Eje más largo de la caja (0 = x, 1 = y, 2 = z)
+    pub fn longest_axis(&self) -> usize {
+        let e = self.extent();
+        if e.x > e.y && e.x > e.z {
+            0
+        } else if e.y > e.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    // Prueba de las placas: devuelve la distancia de entrada si el rayo toca la caja
+    // antes de `t_max`. Si el origen está dentro de la caja devuelve 0.
+    pub fn hit(&self, ray_origin: &Vec3, inv_dir: &Vec3, t_max: f32) -> Option<f32> {
+        let t0 = (self.min - ray_origin).component_mul(inv_dir);
+        let t1 = (self.max - ray_origin).component_mul(inv_dir);
+
+        let t_small = min2(&t0, &t1);
+        let t_big = max2(&t0, &t1);
+
+        let t_near = t_small.x.max(t_small.y).max(t_small.z).max(0.0);
+        let t_far = t_big.x.min(t_big.y).min(t_big.z).min(t_max);
+
+        if t_near <= t_far {
+            Some(t_near)
+        } else {
+            None
+        }
+    }
+}
+
+// Inverso de la dirección del rayo, con infinito en los ejes paralelos
+pub fn inverse_direction(ray_direction: &Vec3) -> Vec3 {
+    Vec3::new(
+        if ray_direction.x != 0.0 { 1.0 / ray_direction.x } else { f32::INFINITY },
+        if ray_direction.y != 0.0 { 1.0 / ray_direction.y } else { f32::INFINITY },
+        if ray_direction.z != 0.0 { 1.0 / ray_direction.z } else { f32::INFINITY },
+    )
+}
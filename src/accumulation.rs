@@ -0,0 +1,72 @@
+use crate::radiance::Radiance;
+
+// Buffer de acumulación en punto flotante, junto al Framebuffer. Cada pasada de
+// render suma una muestra por píxel y el promedio se refina con el tiempo.
+pub struct AccumulationBuffer {
+    pub width: usize,
+    pub height: usize,
+    sums: Vec<Radiance>,
+    samples: u32,
+}
+
+impl AccumulationBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        AccumulationBuffer {
+            width,
+            height,
+            sums: vec![Radiance::black(); width * height],
+            samples: 0,
+        }
+    }
+
+    // Descarta lo acumulado, por ejemplo cuando la cámara se mueve
+    pub fn reset(&mut self) {
+        self.sums.fill(Radiance::black());
+        self.samples = 0;
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    // Sumas por píxel para que una pasada de render agregue su muestra en paralelo;
+    // la pasada debe llamar a `finish_pass` al terminar
+    pub fn sums_mut(&mut self) -> &mut [Radiance] {
+        &mut self.sums
+    }
+
+    pub fn finish_pass(&mut self) {
+        self.samples += 1;
+    }
+
+    // Radiancia promedio de un píxel
+    pub fn average(&self, index: usize) -> Radiance {
+        if self.samples == 0 {
+            Radiance::black()
+        } else {
+            self.sums[index] / self.samples as f32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_average_and_reset() {
+        let mut buffer = AccumulationBuffer::new(2, 1);
+        for value in [1.0, 2.0, 6.0] {
+            for sum in buffer.sums_mut() {
+                *sum += Radiance::new(value, 0.0, value * 2.0);
+            }
+            buffer.finish_pass();
+        }
+        assert_eq!(buffer.samples(), 3);
+        assert_eq!(buffer.average(1), Radiance::new(3.0, 0.0, 6.0));
+
+        buffer.reset();
+        assert_eq!(buffer.samples(), 0);
+        assert_eq!(buffer.average(0), Radiance::black());
+    }
+}
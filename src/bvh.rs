@@ -0,0 +1,532 @@
+use nalgebra_glm::Vec3;
+#[cfg(feature = "bvh-stats")]
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::aabb::{Aabb, inverse_direction};
+use crate::light::Light;
+use crate::ray_intersect::{RayIntersect, Intersect};
+
+// Número de cubetas usadas para evaluar la heurística de área de superficie
+const SAH_BINS: usize = 12;
+// Costo relativo de recorrer un nodo frente a probar una primitiva
+const TRAVERSAL_COST: f32 = 1.0;
+const INTERSECTION_COST: f32 = 1.0;
+const MAX_LEAF_SIZE: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+struct BvhNode {
+    bounds: Aabb,
+    // En una hoja es el índice de la primera primitiva; en un nodo interno es el hijo izquierdo
+    first: usize,
+    // Número de primitivas de la hoja (0 en nodos internos)
+    count: usize,
+    right: usize,
+}
+
+impl BvhNode {
+    fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+// Contadores de recorrido para comparar la BVH con el recorrido lineal
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TraversalCounts {
+    pub rays: u64,
+    pub nodes_visited: u64,
+    pub box_tests: u64,
+    pub primitive_tests: u64,
+}
+
+impl TraversalCounts {
+    // Pruebas de primitiva que habría hecho el recorrido lineal con los mismos rayos
+    pub fn linear_primitive_tests(&self, primitive_count: usize) -> u64 {
+        self.rays * primitive_count as u64
+    }
+}
+
+// Contadores compartidos por todos los hilos. Solo existen con la característica
+// `bvh-stats`: sin ella `record` no hace nada y el recorrido no toca ningún atómico
+#[cfg(feature = "bvh-stats")]
+#[derive(Debug, Default)]
+struct BvhStats {
+    rays: AtomicU64,
+    nodes_visited: AtomicU64,
+    box_tests: AtomicU64,
+    primitive_tests: AtomicU64,
+}
+
+#[cfg(feature = "bvh-stats")]
+impl BvhStats {
+    // Se suma una sola vez por rayo para no pelear por los atómicos entre hilos
+    fn record(&self, counts: &TraversalCounts) {
+        self.rays.fetch_add(counts.rays, Ordering::Relaxed);
+        self.nodes_visited.fetch_add(counts.nodes_visited, Ordering::Relaxed);
+        self.box_tests.fetch_add(counts.box_tests, Ordering::Relaxed);
+        self.primitive_tests.fetch_add(counts.primitive_tests, Ordering::Relaxed);
+    }
+
+    fn counts(&self) -> TraversalCounts {
+        TraversalCounts {
+            rays: self.rays.load(Ordering::Relaxed),
+            nodes_visited: self.nodes_visited.load(Ordering::Relaxed),
+            box_tests: self.box_tests.load(Ordering::Relaxed),
+            primitive_tests: self.primitive_tests.load(Ordering::Relaxed),
+        }
+    }
+
+    fn reset(&self) {
+        self.rays.store(0, Ordering::Relaxed);
+        self.nodes_visited.store(0, Ordering::Relaxed);
+        self.box_tests.store(0, Ordering::Relaxed);
+        self.primitive_tests.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(not(feature = "bvh-stats"))]
+#[derive(Debug, Default)]
+struct BvhStats;
+
+#[cfg(not(feature = "bvh-stats"))]
+impl BvhStats {
+    fn record(&self, _counts: &TraversalCounts) {}
+
+    fn counts(&self) -> TraversalCounts {
+        TraversalCounts::default()
+    }
+
+    fn reset(&self) {}
+}
+
+// Jerarquía de volúmenes envolventes sobre cualquier primitiva con una caja. Las que
+// implementan RayIntersect se recorren con `closest_hit` y `any_hit`; las demás dan
+// su propia prueba de intersección a `closest_by` y `any_by`
+pub struct Bvh<T> {
+    primitives: Vec<T>,
+    nodes: Vec<BvhNode>,
+    stats: BvhStats,
+}
+
+struct BuildItem {
+    bounds: Aabb,
+    centroid: Vec3,
+    index: usize,
+}
+
+impl<T: RayIntersect> Bvh<T> {
+    pub fn new(primitives: Vec<T>) -> Self {
+        Self::with_bounds(primitives, |primitive| primitive.bounding_box())
+    }
+
+    // Intersección más cercana a lo largo del rayo
+    pub fn closest_hit(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
+        self.closest_by(ray_origin, ray_direction, |primitive| {
+            let hit = primitive.ray_intersect(ray_origin, ray_direction);
+            hit.is_intersecting.then_some((hit.distance, hit))
+        })
+        .unwrap_or_else(Intersect::empty)
+    }
+
+    // Devuelve true en cuanto encuentra cualquier intersección antes de `max_distance`
+    pub fn any_hit(&self, ray_origin: &Vec3, ray_direction: &Vec3, max_distance: f32) -> bool {
+        self.any_by(ray_origin, ray_direction, max_distance, |primitive| {
+            let hit = primitive.ray_intersect(ray_origin, ray_direction);
+            hit.is_intersecting.then_some(hit.distance)
+        })
+    }
+}
+
+impl<T> Bvh<T> {
+    // Construye la jerarquía con la caja que da `bounds` para cada primitiva
+    pub fn with_bounds(primitives: Vec<T>, bounds: impl Fn(&T) -> Aabb) -> Self {
+        let mut items: Vec<BuildItem> = primitives
+            .iter()
+            .enumerate()
+            .map(|(index, primitive)| {
+                let bounds = bounds(primitive);
+                BuildItem { bounds, centroid: bounds.centroid(), index }
+            })
+            .collect();
+
+        let mut nodes = Vec::with_capacity(2 * items.len().max(1));
+        if !items.is_empty() {
+            let len = items.len();
+            Self::build_node(&mut nodes, &mut items, 0, len);
+        }
+
+        // Reordenamos las primitivas según el orden de las hojas
+        let mut slots: Vec<Option<T>> = primitives.into_iter().map(Some).collect();
+        let primitives = items
+            .iter()
+            .map(|item| slots[item.index].take().unwrap())
+            .collect();
+
+        Bvh {
+            primitives,
+            nodes,
+            stats: Default::default(),
+        }
+    }
+
+    fn build_node(nodes: &mut Vec<BvhNode>, items: &mut [BuildItem], start: usize, end: usize) -> usize {
+        let node_index = nodes.len();
+        let bounds = items[start..end]
+            .iter()
+            .fold(Aabb::empty(), |acc, item| acc.union(&item.bounds));
+        nodes.push(BvhNode { bounds, first: start, count: end - start, right: 0 });
+
+        let count = end - start;
+        if count <= 1 {
+            return node_index;
+        }
+
+        let split = match Self::find_sah_split(&items[start..end], &bounds) {
+            Some((axis, position)) => {
+                let slice = &mut items[start..end];
+                let mut left = 0;
+                for i in 0..slice.len() {
+                    if slice[i].centroid[axis] < position {
+                        slice.swap(i, left);
+                        left += 1;
+                    }
+                }
+                start + left
+            }
+            None => return node_index,
+        };
+
+        // Si la partición quedó degenerada partimos por la mitad
+        let split = if split == start || split == end {
+            if count <= MAX_LEAF_SIZE {
+                return node_index;
+            }
+            let axis = bounds.longest_axis();
+            items[start..end].sort_by(|a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+            start + count / 2
+        } else {
+            split
+        };
+
+        Self::build_node(nodes, items, start, split);
+        let right = Self::build_node(nodes, items, split, end);
+        nodes[node_index].count = 0;
+        nodes[node_index].first = node_index + 1;
+        nodes[node_index].right = right;
+        node_index
+    }
+
+    // Busca el mejor plano de corte con cubetas; None si conviene dejar una hoja
+    fn find_sah_split(items: &[BuildItem], bounds: &Aabb) -> Option<(usize, f32)> {
+        let centroid_bounds = items
+            .iter()
+            .fold(Aabb::empty(), |acc, item| acc.grow(&item.centroid));
+        let leaf_cost = INTERSECTION_COST * items.len() as f32;
+        let parent_area = bounds.surface_area().max(f32::EPSILON);
+
+        let mut best: Option<(usize, f32, f32)> = None;
+
+        for axis in 0..3 {
+            let axis_min = centroid_bounds.min[axis];
+            let axis_max = centroid_bounds.max[axis];
+            if axis_max - axis_min <= f32::EPSILON {
+                continue;
+            }
+
+            let scale = SAH_BINS as f32 / (axis_max - axis_min);
+            let mut bin_bounds = [Aabb::empty(); SAH_BINS];
+            let mut bin_counts = [0usize; SAH_BINS];
+            for item in items {
+                let bin = (((item.centroid[axis] - axis_min) * scale) as usize).min(SAH_BINS - 1);
+                bin_bounds[bin] = bin_bounds[bin].union(&item.bounds);
+                bin_counts[bin] += 1;
+            }
+
+            // Barrido de izquierda a derecha y de derecha a izquierda
+            let mut left_area = [0.0; SAH_BINS - 1];
+            let mut left_count = [0usize; SAH_BINS - 1];
+            let mut acc = Aabb::empty();
+            let mut acc_count = 0;
+            for i in 0..SAH_BINS - 1 {
+                acc = acc.union(&bin_bounds[i]);
+                acc_count += bin_counts[i];
+                left_area[i] = acc.surface_area();
+                left_count[i] = acc_count;
+            }
+
+            let mut acc = Aabb::empty();
+            let mut acc_count = 0;
+            for i in (1..SAH_BINS).rev() {
+                acc = acc.union(&bin_bounds[i]);
+                acc_count += bin_counts[i];
+                let cost = TRAVERSAL_COST
+                    + INTERSECTION_COST
+                        * (left_area[i - 1] * left_count[i - 1] as f32 + acc.surface_area() * acc_count as f32)
+                        / parent_area;
+                if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                    let position = axis_min + i as f32 / scale;
+                    best = Some((axis, position, cost));
+                }
+            }
+        }
+
+        match best {
+            Some((axis, position, cost)) if cost < leaf_cost || items.len() > MAX_LEAF_SIZE => {
+                Some((axis, position))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.primitives.len()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn primitives(&self) -> &[T] {
+        &self.primitives
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |root| root.bounds)
+    }
+
+    // Golpe más cercano a lo largo del rayo. `test` da la distancia y el resultado de
+    // golpear una primitiva, o None si el rayo no la toca
+    pub fn closest_by<'s, H>(
+        &'s self,
+        ray_origin: &Vec3,
+        ray_direction: &Vec3,
+        mut test: impl FnMut(&'s T) -> Option<(f32, H)>,
+    ) -> Option<H> {
+        let mut counts = TraversalCounts { rays: 1, ..Default::default() };
+        let mut closest = None;
+        let mut zbuffer = f32::INFINITY;
+
+        if self.nodes.is_empty() {
+            self.stats.record(&counts);
+            return closest;
+        }
+
+        let inv_dir = inverse_direction(ray_direction);
+        let mut stack = Vec::with_capacity(64);
+        counts.box_tests += 1;
+        if self.nodes[0].bounds.hit(ray_origin, &inv_dir, zbuffer).is_some() {
+            stack.push(0);
+        }
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            counts.nodes_visited += 1;
+
+            if node.is_leaf() {
+                for primitive in &self.primitives[node.first..node.first + node.count] {
+                    counts.primitive_tests += 1;
+                    if let Some((distance, hit)) = test(primitive) {
+                        if distance < zbuffer {
+                            zbuffer = distance;
+                            closest = Some(hit);
+                        }
+                    }
+                }
+                continue;
+            }
+
+            let left = node.first;
+            let right = node.right;
+            counts.box_tests += 2;
+            let hit_left = self.nodes[left].bounds.hit(ray_origin, &inv_dir, zbuffer);
+            let hit_right = self.nodes[right].bounds.hit(ray_origin, &inv_dir, zbuffer);
+
+            // Apilamos primero el hijo lejano para visitar antes el cercano
+            match (hit_left, hit_right) {
+                (Some(t_left), Some(t_right)) => {
+                    if t_left <= t_right {
+                        stack.push(right);
+                        stack.push(left);
+                    } else {
+                        stack.push(left);
+                        stack.push(right);
+                    }
+                }
+                (Some(_), None) => stack.push(left),
+                (None, Some(_)) => stack.push(right),
+                (None, None) => {}
+            }
+        }
+
+        self.stats.record(&counts);
+        closest
+    }
+
+    // Devuelve true en cuanto `test` da un golpe antes de `max_distance`
+    pub fn any_by(&self, ray_origin: &Vec3, ray_direction: &Vec3, max_distance: f32, mut test: impl FnMut(&T) -> Option<f32>) -> bool {
+        let mut counts = TraversalCounts { rays: 1, ..Default::default() };
+        let mut found = false;
+
+        if !self.nodes.is_empty() {
+            let inv_dir = inverse_direction(ray_direction);
+            let mut stack = Vec::with_capacity(64);
+            counts.box_tests += 1;
+            if self.nodes[0].bounds.hit(ray_origin, &inv_dir, max_distance).is_some() {
+                stack.push(0);
+            }
+
+            'traversal: while let Some(node_index) = stack.pop() {
+                let node = &self.nodes[node_index];
+                counts.nodes_visited += 1;
+
+                if node.is_leaf() {
+                    for primitive in &self.primitives[node.first..node.first + node.count] {
+                        counts.primitive_tests += 1;
+                        if test(primitive).is_some_and(|distance| distance < max_distance) {
+                            found = true;
+                            break 'traversal;
+                        }
+                    }
+                    continue;
+                }
+
+                for child in [node.first, node.right] {
+                    counts.box_tests += 1;
+                    if self.nodes[child].bounds.hit(ray_origin, &inv_dir, max_distance).is_some() {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+
+        self.stats.record(&counts);
+        found
+    }
+
+    // Contadores acumulados desde el último reinicio; siempre cero si se compiló sin
+    // la característica `bvh-stats`
+    pub fn counts(&self) -> TraversalCounts {
+        self.stats.counts()
+    }
+
+    pub fn reset_counts(&self) {
+        self.stats.reset();
+    }
+}
+
+// Una BVH también es una primitiva, así que se pueden anidar
+impl<T: RayIntersect> RayIntersect for Bvh<T> {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
+        self.closest_hit(ray_origin, ray_direction)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds()
+    }
+
+    fn emitters(&self) -> Vec<Light> {
+        self.primitives.iter().flat_map(|primitive| primitive.emitters()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Cube;
+    use crate::materials::Material;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    fn random_cubes(rng: &mut StdRng, count: usize) -> Vec<Cube> {
+        (0..count)
+            .map(|_| {
+                let min = Vec3::new(
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                );
+                let size = Vec3::new(
+                    rng.gen_range(0.1..2.0),
+                    rng.gen_range(0.1..2.0),
+                    rng.gen_range(0.1..2.0),
+                );
+                Cube::new(min, min + size, Material::black())
+            })
+            .collect()
+    }
+
+    fn random_ray(rng: &mut StdRng) -> (Vec3, Vec3) {
+        let origin = Vec3::new(
+            rng.gen_range(-15.0..15.0),
+            rng.gen_range(-15.0..15.0),
+            rng.gen_range(-15.0..15.0),
+        );
+        let target = Vec3::new(
+            rng.gen_range(-5.0..5.0),
+            rng.gen_range(-5.0..5.0),
+            rng.gen_range(-5.0..5.0),
+        );
+        (origin, (target - origin).normalize())
+    }
+
+    #[test]
+    fn test_closest_hit_matches_linear_scan() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let cubes = random_cubes(&mut rng, 200);
+        let reference = random_cubes(&mut StdRng::seed_from_u64(7), 200);
+        let bvh = Bvh::new(cubes);
+
+        for _ in 0..500 {
+            let (origin, direction) = random_ray(&mut rng);
+            let mut expected = f32::INFINITY;
+            for cube in &reference {
+                let tmp = cube.ray_intersect(&origin, &direction);
+                if tmp.is_intersecting && tmp.distance < expected {
+                    expected = tmp.distance;
+                }
+            }
+
+            let hit = bvh.closest_hit(&origin, &direction);
+            if expected.is_finite() {
+                assert!(hit.is_intersecting);
+                assert!((hit.distance - expected).abs() < 1e-4);
+            } else {
+                assert!(!hit.is_intersecting);
+            }
+        }
+    }
+
+    #[test]
+    fn test_any_hit_matches_closest_hit() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let bvh = Bvh::new(random_cubes(&mut rng, 100));
+
+        for _ in 0..500 {
+            let (origin, direction) = random_ray(&mut rng);
+            let closest = bvh.closest_hit(&origin, &direction);
+            assert_eq!(bvh.any_hit(&origin, &direction, f32::INFINITY), closest.is_intersecting);
+            if closest.is_intersecting {
+                assert!(!bvh.any_hit(&origin, &direction, closest.distance * 0.999));
+            }
+        }
+    }
+
+    #[cfg(feature = "bvh-stats")]
+    #[test]
+    fn test_counts_fewer_primitive_tests_than_linear() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let bvh = Bvh::new(random_cubes(&mut rng, 500));
+        bvh.reset_counts();
+
+        for _ in 0..200 {
+            let (origin, direction) = random_ray(&mut rng);
+            bvh.closest_hit(&origin, &direction);
+        }
+
+        let counts = bvh.counts();
+        assert_eq!(counts.rays, 200);
+        assert!(counts.box_tests > 0);
+        assert!(counts.primitive_tests < counts.linear_primitive_tests(bvh.len()));
+
+        bvh.reset_counts();
+        assert_eq!(bvh.counts().rays, 0);
+    }
+}
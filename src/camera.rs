@@ -24,12 +24,9 @@ impl Camera {
         let right = forward.cross(&self.up).normalize();
         let up = right.cross(&forward); // Ya está normalizado
     
-        let rotated =
-            vector.x * right +
-            vector.y * up -
-            vector.z * forward;
-    
-        rotated
+        vector.x * right +
+        vector.y * up -
+        vector.z * forward
     }    
 
     pub fn orbit(&mut self, delta_yaw: f32, delta_pitch: f32) {
@@ -0,0 +1,246 @@
+use nalgebra_glm::Vec3;
+use crate::tonemap::ToneMapOperator;
+use crate::integrator::RenderMode;
+
+pub const DEFAULT_SCENE: &str = "scenes/forest.toml";
+
+pub const USAGE: &str = "\
+Uso: Raytracing_Rust_Project [ESCENA] [OPCIONES]
+
+  ESCENA                  Archivo de escena TOML (por defecto scenes/forest.toml)
+
+Opciones:
+  --width N               Ancho de la imagen en píxeles (800)
+  --height N              Alto de la imagen en píxeles (600)
+  --headless              Renderizar sin ventana y guardar imágenes PNG
+  --output RUTA           Archivo de salida en modo headless (render.png). Con varios
+                          cuadros, los '#' se reemplazan por el número de cuadro
+  --time T                Hora del día como fracción del ciclo (0 = medianoche)
+  --frames INICIO..FIN    Rango de cuadros a renderizar, FIN excluido (0..1)
+  --time-step T           Avance de la hora del día por cuadro (0.01)
+  --mode MODO             Integrador: whitted, path, ao, normals, uv, depth o
+                          material (whitted)
+  --samples N             Muestras por píxel en modo headless (1)
+  --exposure EV           Exposición inicial en pasos (0)
+  --tonemap OPERADOR      clamp, reinhard, reinhard-extended o aces (clamp)
+  --eye X,Y,Z             Posición de la cámara (reemplaza la de la escena)
+  --center X,Y,Z          Punto al que mira la cámara (reemplaza el de la escena)
+  --help                  Mostrar esta ayuda
+";
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub scene_path: String,
+    pub width: usize,
+    pub height: usize,
+    pub headless: bool,
+    pub output: String,
+    pub time: Option<f32>,
+    pub frames: (u32, u32),
+    pub time_step: f32,
+    pub eye: Option<Vec3>,
+    pub center: Option<Vec3>,
+    pub mode: RenderMode,
+    pub samples: u32,
+    pub exposure_ev: f32,
+    pub tone_map: ToneMapOperator,
+    pub help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            scene_path: DEFAULT_SCENE.to_string(),
+            width: 800,
+            height: 600,
+            headless: false,
+            output: "render.png".to_string(),
+            time: None,
+            frames: (0, 1),
+            time_step: 0.01,
+            eye: None,
+            center: None,
+            mode: RenderMode::Whitted,
+            samples: 1,
+            exposure_ev: 0.0,
+            tone_map: ToneMapOperator::Clamp,
+            help: false,
+        }
+    }
+}
+
+impl Options {
+    pub fn frame_count(&self) -> u32 {
+        self.frames.1 - self.frames.0
+    }
+
+    // Ruta de salida de un cuadro: los '#' se reemplazan por el número con ceros a la
+    // izquierda; si hay varios cuadros y no hay '#', se agrega el número antes de la extensión
+    pub fn output_path(&self, frame: u32) -> String {
+        if let Some(start) = self.output.find('#') {
+            let digits = self.output[start..].chars().take_while(|&c| c == '#').count();
+            let number = format!("{:0width$}", frame, width = digits);
+            return format!("{}{}{}", &self.output[..start], number, &self.output[start + digits..]);
+        }
+        if self.frame_count() <= 1 {
+            return self.output.clone();
+        }
+        match self.output.rfind('.') {
+            Some(dot) => format!("{}_{:04}{}", &self.output[..dot], frame, &self.output[dot..]),
+            None => format!("{}_{:04}", self.output, frame),
+        }
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("valor inválido para {}: '{}'", flag, value))
+}
+
+fn parse_vec3(flag: &str, value: &str) -> Result<Vec3, String> {
+    let parts: Vec<&str> = value.split(',').collect();
+    if parts.len() != 3 {
+        return Err(format!("{} espera tres números separados por comas, se recibió '{}'", flag, value));
+    }
+    Ok(Vec3::new(
+        parse_number(flag, parts[0].trim())?,
+        parse_number(flag, parts[1].trim())?,
+        parse_number(flag, parts[2].trim())?,
+    ))
+}
+
+fn parse_tone_map(flag: &str, value: &str) -> Result<ToneMapOperator, String> {
+    match value {
+        "clamp" => Ok(ToneMapOperator::Clamp),
+        "reinhard" => Ok(ToneMapOperator::Reinhard),
+        "reinhard-extended" => Ok(ToneMapOperator::ReinhardExtended { white_point: 4.0 }),
+        "aces" => Ok(ToneMapOperator::AcesFilmic),
+        _ => Err(format!("operador desconocido para {}: '{}'", flag, value)),
+    }
+}
+
+fn parse_mode(flag: &str, value: &str) -> Result<RenderMode, String> {
+    match value {
+        "whitted" => Ok(RenderMode::Whitted),
+        "path" => Ok(RenderMode::PathTraced),
+        "ao" => Ok(RenderMode::AmbientOcclusion),
+        "normals" => Ok(RenderMode::Normals),
+        "uv" => Ok(RenderMode::Uv),
+        "depth" => Ok(RenderMode::Depth),
+        "material" => Ok(RenderMode::MaterialIndex),
+        _ => Err(format!("modo desconocido para {}: '{}'", flag, value)),
+    }
+}
+
+fn parse_range(flag: &str, value: &str) -> Result<(u32, u32), String> {
+    let (start, end) = match value.split_once("..") {
+        Some((start, end)) => (parse_number(flag, start)?, parse_number(flag, end)?),
+        None => {
+            let frame: u32 = parse_number(flag, value)?;
+            (frame, frame + 1)
+        }
+    };
+    if end <= start {
+        return Err(format!("{} necesita un rango no vacío, se recibió '{}'", flag, value));
+    }
+    Ok((start, end))
+}
+
+// Interpreta los argumentos de la línea de comandos (sin el nombre del programa)
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut scene_given = false;
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let flag = arg.as_str();
+        let mut value = || {
+            args.next().ok_or_else(|| format!("falta el valor de {}", flag))
+        };
+        match flag {
+            "--help" | "-h" => options.help = true,
+            "--headless" => options.headless = true,
+            "--width" => options.width = parse_number(flag, &value()?)?,
+            "--height" => options.height = parse_number(flag, &value()?)?,
+            "--output" => options.output = value()?,
+            "--time" => options.time = Some(parse_number(flag, &value()?)?),
+            "--frames" => options.frames = parse_range(flag, &value()?)?,
+            "--time-step" => options.time_step = parse_number(flag, &value()?)?,
+            "--eye" => options.eye = Some(parse_vec3(flag, &value()?)?),
+            "--center" => options.center = Some(parse_vec3(flag, &value()?)?),
+            "--mode" => options.mode = parse_mode(flag, &value()?)?,
+            "--samples" => options.samples = parse_number(flag, &value()?)?,
+            "--exposure" => options.exposure_ev = parse_number(flag, &value()?)?,
+            "--tonemap" => options.tone_map = parse_tone_map(flag, &value()?)?,
+            _ if flag.starts_with("--") => return Err(format!("opción desconocida: {}", flag)),
+            _ if !scene_given => {
+                options.scene_path = arg.clone();
+                scene_given = true;
+            }
+            _ => return Err(format!("argumento inesperado: {}", flag)),
+        }
+    }
+
+    if options.width == 0 || options.height == 0 {
+        return Err("la resolución debe ser mayor que cero".to_string());
+    }
+    if options.samples == 0 {
+        return Err("--samples debe ser al menos 1".to_string());
+    }
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn test_defaults() {
+        let options = parse(&[]).unwrap();
+        assert_eq!(options.scene_path, DEFAULT_SCENE);
+        assert!(!options.headless);
+        assert_eq!((options.width, options.height), (800, 600));
+    }
+
+    #[test]
+    fn test_headless_flags() {
+        let options = parse(&[
+            "scenes/otra.toml", "--headless", "--width", "320", "--height", "240",
+            "--output", "out/frame_###.png", "--time", "0.25", "--frames", "3..6",
+            "--eye", "1,2,3",
+        ])
+        .unwrap();
+        assert_eq!(options.scene_path, "scenes/otra.toml");
+        assert!(options.headless);
+        assert_eq!((options.width, options.height), (320, 240));
+        assert_eq!(options.time, Some(0.25));
+        assert_eq!(options.frames, (3, 6));
+        assert_eq!(options.eye, Some(Vec3::new(1.0, 2.0, 3.0)));
+        assert_eq!(options.output_path(4), "out/frame_004.png");
+    }
+
+    #[test]
+    fn test_output_path_without_placeholder() {
+        let mut options = parse(&["--output", "render.png"]).unwrap();
+        assert_eq!(options.output_path(0), "render.png");
+        options.frames = (0, 10);
+        assert_eq!(options.output_path(7), "render_0007.png");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse(&["--width"]).is_err());
+        assert!(parse(&["--width", "ancho"]).is_err());
+        assert!(parse(&["--frames", "5..5"]).is_err());
+        assert!(parse(&["--eye", "1,2"]).is_err());
+        assert!(parse(&["--desconocida"]).is_err());
+        assert!(parse(&["--tonemap", "filmic"]).is_err());
+        assert!(parse(&["--mode", "bidir"]).is_err());
+        assert!(parse(&["a.toml", "b.toml"]).is_err());
+    }
+}
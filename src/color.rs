@@ -20,11 +20,12 @@ impl Color {
         }
     }
 
+    #[allow(dead_code)]
     pub fn black() -> Color {
         Color {
-            red: 0 as u8,
-            green: 0 as u8,
-            blue: 0 as u8,
+            red: 0,
+            green: 0,
+            blue: 0,
         }
     }
 
@@ -38,7 +39,8 @@ impl Color {
     }
 
     // Método para convertir Color a hexadecimal
-    pub fn to_hex(&self) -> u32 {
+    #[allow(dead_code)]
+    pub fn to_hex(self) -> u32 {
         ((self.red as u32) << 16) | ((self.green as u32) << 8) | (self.blue as u32)
     }
 }
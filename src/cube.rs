@@ -1,53 +1,65 @@
-use crate::Material;
+use crate::materials::Material;
 use nalgebra_glm::{Vec3, min2, max2};
 use crate::ray_intersect::{RayIntersect, Intersect};
+use crate::aabb::{Aabb, inverse_direction};
+use crate::light::{Light, LightShape};
 
 pub struct Cube {
     pub min: Vec3, // Una esquina del cubo
     pub max: Vec3, // La esquina opuesta del cubo
-    pub material: Material,
+    pub material: Material, // Material de los lados, y de todas las caras si no hay otros
+    pub top: Option<Material>,    // Material de la cara superior, como el césped de un bloque de tierra
+    pub bottom: Option<Material>, // Material de la cara inferior
 }
 
 impl Cube {
-    // Obtén las coordenadas UV para el cubo
-    fn get_uv(&self, point: &Vec3) -> (f32, f32) {
-        // Determina qué cara del cubo se está intersectando
-        let mut u = 0.0;
-        let mut v = 0.0;
-
-        if point.x == self.min.x { // Cara izquierda
-            u = (point.z - self.min.z) / (self.max.z - self.min.z);
-            v = (point.y - self.min.y) / (self.max.y - self.min.y);
-        } else if point.x == self.max.x { // Cara derecha
-            u = (point.z - self.min.z) / (self.max.z - self.min.z);
-            v = (point.y - self.min.y) / (self.max.y - self.min.y);
-        } else if point.y == self.min.y { // Cara inferior
-            u = (point.x - self.min.x) / (self.max.x - self.min.x);
-            v = (point.z - self.min.z) / (self.max.z - self.min.z);
-        } else if point.y == self.max.y { // Cara superior
-            u = (point.x - self.min.x) / (self.max.x - self.min.x);
-            v = (point.z - self.min.z) / (self.max.z - self.min.z);
-        } else if point.z == self.min.z { // Cara trasera
-            u = (point.x - self.min.x) / (self.max.x - self.min.x);
-            v = (point.y - self.min.y) / (self.max.y - self.min.y);
-        } else if point.z == self.max.z { // Cara delantera
-            u = (point.x - self.min.x) / (self.max.x - self.min.x);
-            v = (point.y - self.min.y) / (self.max.y - self.min.y);
-        }
+    // Cubo con el mismo material en todas sus caras
+    pub fn new(min: Vec3, max: Vec3, material: Material) -> Self {
+        Cube { min, max, material, top: None, bottom: None }
+    }
+
+    // Obtén las coordenadas UV para el cubo en la cara con la normal dada: de 0 a 1
+    // sobre la cara y, además, las mismas direcciones en unidades del mundo
+    fn get_uv(&self, point: &Vec3, normal: &Vec3) -> ((f32, f32), (f32, f32)) {
+        let (u_axis, v_axis) = if normal.x != 0.0 {
+            (2, 1) // Caras izquierda y derecha
+        } else if normal.y != 0.0 {
+            (0, 2) // Caras inferior y superior
+        } else {
+            (0, 1) // Caras trasera y delantera
+        };
+        let local = |axis: usize| (point[axis] - self.min[axis]) / (self.max[axis] - self.min[axis]);
+        ((local(u_axis), local(v_axis)), (point[u_axis], point[v_axis]))
+    }
 
-        (u, v)
+    // Material de la cara con la normal dada
+    fn face_material(&self, normal: &Vec3) -> &Material {
+        let face = if normal.y > 0.0 {
+            self.top.as_ref()
+        } else if normal.y < 0.0 {
+            self.bottom.as_ref()
+        } else {
+            None
+        };
+        face.unwrap_or(&self.material)
     }
 }
 
+// Ejes en que crecen u y v en una cara del cubo, según el mapeo de `get_uv`
+pub fn face_tangents(normal: &Vec3) -> (Vec3, Vec3) {
+    if normal.x != 0.0 {
+        (Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0))
+    } else if normal.y != 0.0 {
+        (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0))
+    } else {
+        (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0))
+    }
+}
 
 impl RayIntersect for Cube {
     fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
         // Inverso de la dirección del rayo
-        let inv_dir = Vec3::new(
-            if ray_direction.x != 0.0 { 1.0 / ray_direction.x } else { f32::INFINITY },
-            if ray_direction.y != 0.0 { 1.0 / ray_direction.y } else { f32::INFINITY },
-            if ray_direction.z != 0.0 { 1.0 / ray_direction.z } else { f32::INFINITY }
-        );
+        let inv_dir = inverse_direction(ray_direction);
 
         // Calcular t_min y t_max para los ejes x, y, z
         let t_min = (self.min - ray_origin).component_mul(&inv_dir);
@@ -66,8 +78,58 @@ impl RayIntersect for Cube {
             return Intersect::empty();
         }
 
+        let front = self.surface_hit(ray_origin, ray_direction, t_near);
+        if !front.material.is_cut(front.u, front.v) {
+            return front;
+        }
+        // El rayo pasa por un hueco de la cara de entrada y puede golpear la de
+        // salida, que se ve por dentro: su normal mira hacia el rayo
+        let mut back = self.surface_hit(ray_origin, ray_direction, t_far);
+        if back.material.is_cut(back.u, back.v) {
+            return Intersect::empty();
+        }
+        back.normal = -back.normal;
+        back.smooth_normal = back.normal;
+        back
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::new(self.min, self.max)
+    }
+
+    fn emitters(&self) -> Vec<Light> {
+        let center = (self.min + self.max) * 0.5;
+        let size = (self.max - self.min).abs();
+        if self.top.is_none() && self.bottom.is_none() {
+            return Light::emitter(center, LightShape::Box { size }, &self.material)
+                .into_iter()
+                .collect();
+        }
+
+        // Con caras de otro material cada cara emite por su cuenta, como un rectángulo
+        let (x, y, z) = (Vec3::x() * size.x, Vec3::y() * size.y, Vec3::z() * size.z);
+        let faces = [
+            (Vec3::x(), z, y),
+            (Vec3::y(), x, z),
+            (Vec3::z(), x, y),
+        ];
+        let mut emitters = Vec::new();
+        for (axis, u, v) in faces {
+            for side in [-1.0, 1.0] {
+                let normal = axis * side;
+                let position = center + normal.component_mul(&size) * 0.5;
+                emitters.extend(Light::emitter(position, LightShape::Rectangle { u, v }, self.face_material(&normal)));
+            }
+        }
+        emitters
+    }
+}
+
+impl Cube {
+    // Intersección con la superficie del cubo a distancia `t` sobre el rayo
+    fn surface_hit(&self, ray_origin: &Vec3, ray_direction: &Vec3, t: f32) -> Intersect {
         // Calcular el punto de intersección y normal
-        let intersection_point = ray_origin + ray_direction * t_near;
+        let intersection_point = ray_origin + ray_direction * t;
 
         // Para encontrar la normal de la cara intersectada
         let normal = if (intersection_point.x - self.min.x).abs() < 1e-4 {
@@ -85,10 +147,40 @@ impl RayIntersect for Cube {
         };
 
         let normal = normal.normalize();
+        let material = self.face_material(&normal).clone();
         // Calcular las coordenadas UV en la cara donde se dio la intersección
-        let (u, v) = self.get_uv(&intersection_point);
+        let ((u, v), (world_u, world_v)) = self.get_uv(&intersection_point, &normal);
+        let (u, v) = material.texture_uv(u, v, world_u, world_v);
+        let (tangent, bitangent) = face_tangents(&normal);
+
+        // Sin tamaño de baldosa cada cara estira la textura sobre sus dos lados
+        let size = self.max - self.min;
+        let axis = normal.iamax();
+        let uv_extent = material.texture_uv_extent((size[(axis + 1) % 3] * size[(axis + 2) % 3]).sqrt());
 
         // Retornar la intersección con toda la información
-        Intersect::new(intersection_point, normal, t_near, self.material, u, v)
+        Intersect::new(intersection_point, normal, t, material, u, v)
+            .with_tangents(tangent, bitangent)
+            .with_uv_extent(uv_extent)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::radiance::Radiance;
+
+    #[test]
+    fn test_emissive_top_face() {
+        // Bloque apagado con la cara superior emisiva: solo esa cara es una luz
+        let mut glow = Material::black();
+        glow.emissive_color = Some(Radiance::new(1.0, 0.5, 0.0));
+        glow.emissive_intensity = 2.0;
+        let cube = Cube { top: Some(glow), ..Cube::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(2.0, 1.0, 2.0), Material::black()) };
+
+        let emitters = cube.emitters();
+        assert_eq!(emitters.len(), 1);
+        assert!((emitters[0].position - Vec3::new(1.0, 1.0, 1.0)).norm() < 1e-6);
+        assert_eq!(emitters[0].shape, LightShape::Rectangle { u: Vec3::new(2.0, 0.0, 0.0), v: Vec3::new(0.0, 0.0, 2.0) });
+        assert_eq!(emitters[0].intensity, 2.0);
+    }
+}
@@ -0,0 +1,185 @@
+use nalgebra_glm::Vec3;
+use rand::RngCore;
+use crate::integrator::{Integrator, RenderContext};
+use crate::radiance::Radiance;
+use crate::sampling::cosine_sample_hemisphere;
+
+// Integradores de depuración: muestran una propiedad del primer impacto en lugar
+// de la iluminación. Los rayos que no golpean nada quedan en negro.
+
+const EPSILON: f32 = 1e-3;
+
+// Oclusión ambiental: una superficie blanca iluminada solo por el cielo, a través
+// de los rayos coseno del hemisferio que no chocan con nada antes de `radius`
+pub struct AmbientOcclusionIntegrator {
+    radius: f32,
+    samples: u32,
+}
+
+impl AmbientOcclusionIntegrator {
+    pub fn new() -> Self {
+        AmbientOcclusionIntegrator { radius: 2.0, samples: 4 }
+    }
+}
+
+impl Integrator for AmbientOcclusionIntegrator {
+    fn radiance(&self, origin: &Vec3, direction: &Vec3, context: &RenderContext, rng: &mut dyn RngCore) -> Radiance {
+        let intersect = context.objects.closest_hit(origin, direction);
+        if !intersect.is_intersecting {
+            return Radiance::black();
+        }
+        let normal = if intersect.normal.dot(direction) < 0.0 { intersect.normal } else { -intersect.normal };
+        let ao_origin = intersect.point + normal * EPSILON;
+
+        let mut sky_light = Radiance::black();
+        for _ in 0..self.samples {
+            let ao_direction = cosine_sample_hemisphere(&normal, rng);
+            if !context.objects.any_hit(&ao_origin, &ao_direction, self.radius) {
+                sky_light += context.sky_light(&ao_direction);
+            }
+        }
+        sky_light / self.samples as f32
+    }
+}
+
+// Normal de sombreado (con el mapa de normales) en espacio de mundo, llevada de
+// [-1, 1] a [0, 1] por componente
+pub struct NormalIntegrator;
+
+impl Integrator for NormalIntegrator {
+    fn radiance(&self, origin: &Vec3, direction: &Vec3, context: &RenderContext, _rng: &mut dyn RngCore) -> Radiance {
+        let intersect = context.objects.closest_hit(origin, direction);
+        if !intersect.is_intersecting {
+            return Radiance::black();
+        }
+        let normal = intersect.shading_normal(context.texture_manager) * 0.5 + Vec3::new(0.5, 0.5, 0.5);
+        Radiance::new(normal.x, normal.y, normal.z)
+    }
+}
+
+// Coordenadas de textura: u en rojo, v en verde
+pub struct UvIntegrator;
+
+impl Integrator for UvIntegrator {
+    fn radiance(&self, origin: &Vec3, direction: &Vec3, context: &RenderContext, _rng: &mut dyn RngCore) -> Radiance {
+        let intersect = context.objects.closest_hit(origin, direction);
+        if !intersect.is_intersecting {
+            return Radiance::black();
+        }
+        Radiance::new(intersect.u, intersect.v, 0.0)
+    }
+}
+
+// Distancia al primer impacto: blanco junto a la cámara, negro a `max_distance` o más
+pub struct DepthIntegrator {
+    max_distance: f32,
+}
+
+impl DepthIntegrator {
+    pub fn new() -> Self {
+        DepthIntegrator { max_distance: 40.0 }
+    }
+}
+
+impl Integrator for DepthIntegrator {
+    fn radiance(&self, origin: &Vec3, direction: &Vec3, context: &RenderContext, _rng: &mut dyn RngCore) -> Radiance {
+        let intersect = context.objects.closest_hit(origin, direction);
+        if !intersect.is_intersecting {
+            return Radiance::black();
+        }
+        let nearness = 1.0 - (intersect.distance / self.max_distance).clamp(0.0, 1.0);
+        Radiance::new(nearness, nearness, nearness)
+    }
+}
+
+// Un color fijo por material, para ver qué material tiene cada superficie
+pub struct MaterialIndexIntegrator;
+
+impl Integrator for MaterialIndexIntegrator {
+    fn radiance(&self, origin: &Vec3, direction: &Vec3, context: &RenderContext, _rng: &mut dyn RngCore) -> Radiance {
+        let intersect = context.objects.closest_hit(origin, direction);
+        if !intersect.is_intersecting {
+            return Radiance::black();
+        }
+        index_color(intersect.material.id)
+    }
+}
+
+// Tonos separados por la razón áurea para que índices vecinos no se parezcan
+fn index_color(index: usize) -> Radiance {
+    let hue = (index as f32 * 0.618_034).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    match hue as u32 {
+        0 => Radiance::new(1.0, x, 0.0),
+        1 => Radiance::new(x, 1.0, 0.0),
+        2 => Radiance::new(0.0, 1.0, x),
+        3 => Radiance::new(0.0, x, 1.0),
+        4 => Radiance::new(x, 0.0, 1.0),
+        _ => Radiance::new(1.0, 0.0, x),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::Bvh;
+    use crate::cube::Cube;
+    use crate::light::SceneLights;
+    use crate::materials::{Material, TextureManager};
+    use crate::ray_intersect::Object;
+    use crate::sky::Sky;
+
+    fn floor() -> Bvh<Object> {
+        let mut material = Material::new(Radiance::new(0.5, 0.5, 0.5), 10.0, [0.9, 0.1], 0.0, 0.0, 1.0);
+        material.id = 3;
+        let cube = Cube::new(Vec3::new(-5.0, -1.0, -5.0), Vec3::new(5.0, 0.0, 5.0), material);
+        Bvh::new(vec![Box::new(cube) as Object])
+    }
+
+    fn trace(integrator: &dyn Integrator, objects: &Bvh<Object>, origin: Vec3, direction: Vec3) -> Radiance {
+        let texture_manager = TextureManager::new();
+        let sky = Sky::uniform(Radiance::new(1.0, 1.0, 1.0));
+        let lights = SceneLights::new(Vec::new());
+        let context = RenderContext {
+            objects,
+            lights: &lights,
+            texture_manager: &texture_manager,
+            sky: &sky,
+            environment: None,
+            ambient: Radiance::black(),
+            pixel_spread: 0.0,
+        };
+        integrator.radiance(&origin, &direction, &context, &mut rand::thread_rng())
+    }
+
+    #[test]
+    fn test_normal_and_depth_of_floor() {
+        let objects = floor();
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let normal = trace(&NormalIntegrator, &objects, Vec3::new(0.0, 4.0, 0.0), down);
+        assert_eq!(normal, Radiance::new(0.5, 1.0, 0.5));
+
+        let depth = trace(&DepthIntegrator::new(), &objects, Vec3::new(0.0, 4.0, 0.0), down);
+        assert!((depth.r - 0.9).abs() < 1e-5);
+
+        let miss = trace(&DepthIntegrator::new(), &objects, Vec3::new(0.0, 4.0, 0.0), -down);
+        assert_eq!(miss, Radiance::black());
+    }
+
+    #[test]
+    fn test_open_floor_is_unoccluded() {
+        let objects = floor();
+        let ao = trace(&AmbientOcclusionIntegrator::new(), &objects, Vec3::new(0.0, 4.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(ao, Radiance::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_material_index_colors_differ() {
+        let objects = floor();
+        let color = trace(&MaterialIndexIntegrator, &objects, Vec3::new(0.0, 4.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(color, index_color(3));
+        for index in 0..8 {
+            assert_ne!(index_color(index), index_color(index + 1));
+        }
+    }
+}
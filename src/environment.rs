@@ -0,0 +1,195 @@
+use nalgebra_glm::Vec3;
+use rand::{Rng, RngCore};
+use std::f32::consts::PI;
+use crate::radiance::Radiance;
+use crate::sampling::Distribution2D;
+use crate::textures::{direction_to_equirectangular, equirectangular_to_direction, EnvironmentMap};
+
+// Resolución máxima de la rejilla de muestreo por importancia (ancho; el alto es la mitad)
+const DISTRIBUTION_WIDTH: usize = 512;
+
+// Cielo fotografiado: un mapa de entorno que reemplaza al cielo procedural como
+// fondo y, en path tracing, ilumina la escena (iluminación basada en imagen)
+pub struct Environment {
+    map: EnvironmentMap,
+    rotation: f32,  // Giro del mapa alrededor del eje y, en radianes
+    intensity: f32, // Multiplicador de la radiancia del mapa
+    // Brillo del mapa en una rejilla equirectangular, ponderado por el área de
+    // cada celda, para elegir las direcciones más brillantes con más frecuencia
+    distribution: Distribution2D,
+    average: Radiance, // Radiancia media sobre todas las direcciones, sin la intensidad
+}
+
+// Dirección elegida por importancia, con su radiancia y su densidad por ángulo sólido
+pub struct EnvironmentSample {
+    pub direction: Vec3,
+    pub radiance: Radiance,
+    pub pdf: f32,
+}
+
+impl Environment {
+    pub fn new(map: EnvironmentMap, rotation: f32, intensity: f32) -> Self {
+        let width = match &map {
+            EnvironmentMap::Equirectangular(texture) => texture.width.clamp(2, DISTRIBUTION_WIDTH),
+            EnvironmentMap::Cubemap(faces) => (faces[0].width * 4).clamp(2, DISTRIBUTION_WIDTH),
+        };
+        let height = (width / 2).max(1);
+
+        let mut func = Vec::with_capacity(width * height);
+        let mut total = Radiance::black();
+        let mut total_weight = 0.0;
+        for row in 0..height {
+            let v = (row as f32 + 0.5) / height as f32;
+            // Las filas cerca de los polos cubren menos ángulo sólido
+            let sin_theta = (v * PI).sin();
+            for column in 0..width {
+                let u = (column as f32 + 0.5) / width as f32;
+                let radiance = map.lookup(&equirectangular_to_direction(u, v));
+                func.push(radiance.luminance() * sin_theta);
+                total += radiance * sin_theta;
+                total_weight += sin_theta;
+            }
+        }
+
+        Environment {
+            map,
+            rotation,
+            intensity,
+            distribution: Distribution2D::new(&func, width, height),
+            average: total / total_weight.max(f32::EPSILON),
+        }
+    }
+
+    // Radiancia que llega desde `direction` (hacia afuera de la escena)
+    pub fn radiance(&self, direction: &Vec3) -> Radiance {
+        let local = rotate_y(&direction.normalize(), -self.rotation);
+        self.map.lookup(&local) * self.intensity
+    }
+
+    // Luz ambiente para los integradores sin luz indirecta: lo que refleja una
+    // superficie blanca bajo un cielo uniforme con la radiancia media del mapa
+    pub fn ambient(&self) -> Radiance {
+        self.average * self.intensity
+    }
+
+    // Elige una dirección con probabilidad proporcional al brillo del mapa
+    pub fn sample(&self, rng: &mut dyn RngCore) -> EnvironmentSample {
+        let ((u, v), uv_pdf) = self.distribution.sample(rng.gen(), rng.gen());
+        let local = equirectangular_to_direction(u, v);
+        let sin_theta = (v * PI).sin();
+        let pdf = if sin_theta > 0.0 { uv_pdf / (2.0 * PI * PI * sin_theta) } else { 0.0 };
+        EnvironmentSample {
+            direction: rotate_y(&local, self.rotation),
+            radiance: self.map.lookup(&local) * self.intensity,
+            pdf,
+        }
+    }
+
+    // Densidad con la que `sample` habría elegido `direction`
+    pub fn pdf(&self, direction: &Vec3) -> f32 {
+        let local = rotate_y(&direction.normalize(), -self.rotation);
+        let (u, v) = direction_to_equirectangular(&local);
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u.rem_euclid(1.0), v) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+// Gira una dirección `angle` radianes alrededor del eje y
+fn rotate_y(direction: &Vec3, angle: f32) -> Vec3 {
+    let (sin, cos) = angle.sin_cos();
+    Vec3::new(cos * direction.x + sin * direction.z, direction.y, -sin * direction.x + cos * direction.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::textures::Texture;
+    use image::{DynamicImage, ImageBuffer, Rgb};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use std::sync::Arc;
+
+    // Mapa equirectangular gris con un punto muy brillante en la columna y fila dadas
+    fn spot_map(width: u32, height: u32, spot: (u32, u32)) -> EnvironmentMap {
+        let image = ImageBuffer::from_fn(width, height, |x, y| {
+            if (x, y) == spot { Rgb([1000.0f32, 1000.0, 1000.0]) } else { Rgb([0.1f32, 0.1, 0.1]) }
+        });
+        EnvironmentMap::Equirectangular(Arc::new(Texture::from_image(DynamicImage::ImageRgb32F(image))))
+    }
+
+    #[test]
+    fn test_equirectangular_round_trip() {
+        for direction in [Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.6, 0.3, 0.2).normalize(), Vec3::new(-0.5, -0.7, 0.4).normalize()] {
+            let (u, v) = direction_to_equirectangular(&direction);
+            assert!((equirectangular_to_direction(u, v) - direction).norm() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_importance_sampling_prefers_bright_spot() {
+        let environment = Environment::new(spot_map(64, 32, (40, 10)), 0.7, 2.0);
+        let spot = rotate_y(&equirectangular_to_direction(40.5 / 64.0, 10.5 / 32.0), 0.7);
+        let mut rng = StdRng::seed_from_u64(3);
+        let n = 10000;
+        let mut near_spot = 0;
+        let mut pdf_mismatches = 0;
+        for _ in 0..n {
+            let sample = environment.sample(&mut rng);
+            if sample.direction.dot(&spot) > 0.99 {
+                near_spot += 1;
+            }
+            // Salvo en los bordes de las celdas, pdf() coincide con la densidad de la muestra
+            if (environment.pdf(&sample.direction) - sample.pdf).abs() > sample.pdf * 1e-2 {
+                pdf_mismatches += 1;
+            }
+        }
+        assert!(near_spot > n / 2);
+        assert!(pdf_mismatches < n / 100, "{}", pdf_mismatches);
+    }
+
+    #[test]
+    fn test_importance_sampling_is_unbiased() {
+        // La media de L/pdf estima la integral de la radiancia sobre la esfera. Con
+        // un degradado suave de arriba a abajo la integral es la de 1 + cos(θ) por 2
+        let image = ImageBuffer::from_fn(64, 32, |_, y| {
+            let v = (y as f32 + 0.5) / 32.0;
+            let value = 2.0 + 2.0 * (v * PI).cos();
+            Rgb([value, value, value])
+        });
+        let map = EnvironmentMap::Equirectangular(Arc::new(Texture::from_image(DynamicImage::ImageRgb32F(image))));
+        let environment = Environment::new(map, 0.3, 1.0);
+        let mut rng = StdRng::seed_from_u64(4);
+        let n = 20000;
+        let mut estimate = 0.0;
+        for _ in 0..n {
+            let sample = environment.sample(&mut rng);
+            estimate += sample.radiance.r / sample.pdf;
+        }
+        estimate /= n as f32;
+        let exact = 8.0 * PI;
+        assert!((estimate - exact).abs() < exact * 0.02, "{} vs {}", estimate, exact);
+    }
+
+    #[test]
+    fn test_rotation_and_intensity() {
+        let map = spot_map(64, 32, (16, 16));
+        let spot = equirectangular_to_direction(16.5 / 64.0, 16.5 / 32.0);
+        let unrotated = Environment::new(map.clone(), 0.0, 1.0);
+        let rotated = Environment::new(map, PI / 2.0, 3.0);
+        assert!((unrotated.radiance(&spot).r - 1000.0).abs() < 1e-1);
+        let moved = rotate_y(&spot, PI / 2.0);
+        assert!((rotated.radiance(&moved).r - 3000.0).abs() < 1e-1);
+    }
+
+    #[test]
+    fn test_ambient_is_mean_radiance() {
+        // Mitad de arriba blanca y mitad de abajo negra: la media es la mitad
+        let image = ImageBuffer::from_fn(64, 32, |_, y| if y < 16 { Rgb([1.0f32, 1.0, 1.0]) } else { Rgb([0.0f32, 0.0, 0.0]) });
+        let map = EnvironmentMap::Equirectangular(Arc::new(Texture::from_image(DynamicImage::ImageRgb32F(image))));
+        let environment = Environment::new(map, 0.0, 2.0);
+        assert!((environment.ambient().g - 1.0).abs() < 0.02, "{:?}", environment.ambient());
+    }
+}
@@ -0,0 +1,989 @@
+use nalgebra_glm::{self as glm, Mat4, Vec3, Vec4};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use crate::camera::Camera;
+use crate::light::{Falloff, Light};
+use crate::materials::{AlphaCutout, Material, TextureManager};
+use crate::mesh::{Mesh, Triangle};
+use crate::radiance::Radiance;
+use crate::textures::{Filter, Sampler, Texture, WrapMode};
+
+// Extensiones que el importador entiende, del todo o en parte
+const SUPPORTED_EXTENSIONS: [&str; 4] = [
+    "KHR_lights_punctual",
+    "KHR_materials_emissive_strength",
+    "KHR_materials_transmission",
+    "KHR_materials_ior",
+];
+
+// Valores de un accessor sin bufferView, que son todos cero
+const MAX_EMPTY_ACCESSOR_COUNT: usize = 1 << 24;
+
+// Lo que aporta un archivo glTF a la escena: una malla por cada nodo con malla, ya
+// en coordenadas del mundo, sus luces y la primera cámara que aparezca
+pub struct GltfModel {
+    pub meshes: Vec<Mesh>,
+    pub lights: Vec<Light>,
+    pub camera: Option<Camera>,
+    pub material_count: usize,
+    pub warnings: Vec<String>, // Partes del archivo que no se pudieron usar
+}
+
+#[derive(Debug)]
+pub struct GltfError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for GltfError {}
+
+// Lee un .gltf (con sus .bin e imágenes al lado o incrustados en data URIs) o un .glb.
+// `transform` lleva el modelo a la escena; los materiales reciben ids a partir de
+// `first_id` y los primitivos sin material usan `fallback`
+pub fn load_gltf(
+    path: &str,
+    texture_manager: &mut TextureManager,
+    transform: &Mat4,
+    fallback: Material,
+    first_id: usize,
+) -> Result<GltfModel, GltfError> {
+    let error = |message: String| GltfError { path: path.to_string(), message };
+    let bytes = fs::read(path).map_err(|e| error(format!("no se pudo leer el modelo: {}", e)))?;
+    let (json, binary) = if bytes.starts_with(b"glTF") { split_glb(&bytes).map_err(error)? } else { (&bytes[..], None) };
+    let document: Document =
+        serde_json::from_slice(json).map_err(|e| error(format!("JSON inválido: {}", e)))?;
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+
+    let mut loader = Loader {
+        buffers: Vec::new(),
+        warnings: Vec::new(),
+        images: HashMap::new(),
+        document: &document,
+        directory,
+    };
+    for extension in &document.extensions_used {
+        if !SUPPORTED_EXTENSIONS.contains(&extension.as_str()) {
+            let required = document.extensions_required.contains(extension);
+            loader.warn(format!(
+                "la extensión {} no está soportada{}",
+                extension,
+                if required { " y el modelo la exige; puede verse mal" } else { " y se ignora" }
+            ));
+        }
+    }
+    loader.buffers = document
+        .buffers
+        .iter()
+        .enumerate()
+        .map(|(index, buffer)| loader.load_buffer(index, buffer, binary))
+        .collect::<Result<_, _>>()
+        .map_err(error)?;
+
+    let materials = document
+        .materials
+        .iter()
+        .enumerate()
+        .map(|(index, material)| loader.material(material, texture_manager, first_id + index))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(error)?;
+
+    let roots = match document.scene.or(if document.scenes.is_empty() { None } else { Some(0) }) {
+        Some(scene) => document.scenes.get(scene).ok_or_else(|| error(format!("la escena {} no existe", scene)))?.nodes.clone(),
+        // Sin escenas se dibujan todos los nodos que no son hijos de otro
+        None => {
+            let children: Vec<usize> = document.nodes.iter().flat_map(|node| node.children.iter().copied()).collect();
+            (0..document.nodes.len()).filter(|index| !children.contains(index)).collect()
+        }
+    };
+
+    let mut model = GltfModel { meshes: Vec::new(), lights: Vec::new(), camera: None, material_count: materials.len(), warnings: Vec::new() };
+    let mut stack: Vec<(usize, Mat4)> = roots.iter().rev().map(|&node| (node, *transform)).collect();
+    let mut visited = vec![false; document.nodes.len()];
+    while let Some((index, parent)) = stack.pop() {
+        let node = document.nodes.get(index).ok_or_else(|| error(format!("el nodo {} no existe", index)))?;
+        if std::mem::replace(&mut visited[index], true) {
+            return Err(error(format!("el nodo {} aparece dos veces en la jerarquía", index)));
+        }
+        let world = parent * node.local_transform();
+
+        if let Some(mesh) = node.mesh {
+            let (triangles, table) = loader.mesh_triangles(mesh, &world, &materials, &fallback).map_err(error)?;
+            if !triangles.is_empty() {
+                model.meshes.push(Mesh::new(triangles, table));
+            }
+        }
+        if let Some(camera) = node.camera {
+            loader.camera(camera, &world, &mut model.camera).map_err(error)?;
+        }
+        if let Some(light) = node.extensions.as_ref().and_then(|extensions| extensions.lights_punctual.as_ref()) {
+            model.lights.push(loader.light(light.light, &world).map_err(error)?);
+        }
+        stack.extend(node.children.iter().rev().map(|&child| (child, world)));
+    }
+    model.warnings = loader.warnings;
+    Ok(model)
+}
+
+// Separa un .glb en el trozo JSON y el binario opcional que hace de primer buffer
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+    let word = |offset: usize| -> Result<u32, String> {
+        bytes
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| "el .glb está cortado".to_string())
+    };
+    if word(4)? != 2 {
+        return Err(format!("versión de .glb {} no soportada (solo la 2)", word(4)?));
+    }
+    let length = (word(8)? as usize).min(bytes.len());
+    let mut json = None;
+    let mut binary = None;
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let chunk_length = word(offset)? as usize;
+        let kind = word(offset + 4)?;
+        let data = bytes.get(offset + 8..offset + 8 + chunk_length).ok_or_else(|| "el .glb está cortado".to_string())?;
+        match kind {
+            0x4E4F_534A => json = json.or(Some(data)),     // "JSON"
+            0x004E_4942 => binary = binary.or(Some(data)), // "BIN\0"
+            _ => {}
+        }
+        offset += 8 + chunk_length;
+    }
+    Ok((json.ok_or_else(|| "el .glb no tiene JSON".to_string())?, binary))
+}
+
+struct Loader<'a> {
+    document: &'a Document,
+    directory: &'a Path,
+    buffers: Vec<Vec<u8>>,
+    images: HashMap<(usize, bool), usize>, // (imagen, es lineal) -> índice en el TextureManager
+    warnings: Vec<String>,
+}
+
+impl<'a> Loader<'a> {
+    // Cada aviso se da una vez, aunque lo provoquen muchos materiales o primitivos
+    fn warn(&mut self, message: String) {
+        if !self.warnings.contains(&message) {
+            self.warnings.push(message);
+        }
+    }
+
+    fn load_buffer(&self, index: usize, buffer: &BufferDef, binary: Option<&[u8]>) -> Result<Vec<u8>, String> {
+        let data = match &buffer.uri {
+            Some(uri) => self.read_uri(uri)?,
+            None if index == 0 => binary.ok_or("el buffer 0 no tiene URI y no hay trozo binario")?.to_vec(),
+            None => return Err(format!("el buffer {} no tiene URI", index)),
+        };
+        if data.len() < buffer.byte_length {
+            return Err(format!("el buffer {} tiene {} bytes y debería tener {}", index, data.len(), buffer.byte_length));
+        }
+        Ok(data)
+    }
+
+    // Contenido de una data URI en base64 o de un archivo junto al modelo
+    fn read_uri(&self, uri: &str) -> Result<Vec<u8>, String> {
+        if let Some(data) = uri.strip_prefix("data:") {
+            let (header, payload) = data.split_once(',').ok_or("data URI sin datos")?;
+            if !header.ends_with(";base64") {
+                return Err("solo se entienden data URIs en base64".to_string());
+            }
+            return decode_base64(payload).ok_or_else(|| "data URI con base64 inválido".to_string());
+        }
+        let file = self.directory.join(percent_decode(uri));
+        fs::read(&file).map_err(|e| format!("no se pudo leer {}: {}", file.display(), e))
+    }
+
+    fn buffer_view(&self, index: usize) -> Result<(&[u8], Option<usize>), String> {
+        let view = self.document.buffer_views.get(index).ok_or_else(|| format!("el bufferView {} no existe", index))?;
+        let buffer = self.buffers.get(view.buffer).ok_or_else(|| format!("el buffer {} no existe", view.buffer))?;
+        let bytes = view
+            .byte_offset
+            .checked_add(view.byte_length)
+            .and_then(|end| buffer.get(view.byte_offset..end))
+            .ok_or_else(|| format!("el bufferView {} se sale de su buffer", index))?;
+        Ok((bytes, view.byte_stride))
+    }
+
+    // Valores de un accessor como filas de `components` números. Los enteros
+    // normalizados pasan a 0..1 (o -1..1 con signo); los demás se leen tal cual
+    fn accessor(&mut self, index: usize, components: &[&str]) -> Result<Vec<Vec<f64>>, String> {
+        let document: &'a Document = self.document;
+        let accessor = document.accessors.get(index).ok_or_else(|| format!("el accessor {} no existe", index))?;
+        let width = match accessor.kind.as_str() {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" => 4,
+            other => return Err(format!("el accessor {} es de tipo {} y no se puede usar aquí", index, other)),
+        };
+        if !components.contains(&accessor.kind.as_str()) {
+            return Err(format!("el accessor {} es {} y se esperaba {}", index, accessor.kind, components.join(" o ")));
+        }
+        if accessor.sparse.is_some() {
+            self.warn(format!("el accessor {} es disperso; se usan sus valores base", index));
+        }
+        let size = match accessor.component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            other => return Err(format!("tipo de componente {} desconocido en el accessor {}", other, index)),
+        };
+        // Sin bufferView todos los valores son cero; la cantidad no está respaldada por
+        // ningún dato, así que se limita
+        let Some(view) = accessor.buffer_view else {
+            if accessor.count > MAX_EMPTY_ACCESSOR_COUNT {
+                return Err(format!("el accessor {} no tiene bufferView y pide {} valores", index, accessor.count));
+            }
+            return Ok(vec![vec![0.0; width]; accessor.count]);
+        };
+        let (bytes, stride) = self.buffer_view(view)?;
+        let stride = stride.unwrap_or(size * width);
+        // El último elemento debe caber en el bufferView antes de reservar memoria para
+        // todos: la cantidad viene del archivo
+        let end = match accessor.count.checked_sub(1) {
+            None => Some(0),
+            Some(last) => last
+                .checked_mul(stride)
+                .and_then(|start| start.checked_add(accessor.byte_offset))
+                .and_then(|start| start.checked_add(size * width)),
+        };
+        if end.is_none_or(|end| end > bytes.len()) {
+            return Err(format!("el accessor {} se sale de su bufferView", index));
+        }
+        let mut rows = Vec::with_capacity(accessor.count);
+        for element in 0..accessor.count {
+            let start = accessor.byte_offset + element * stride;
+            let row = (0..width)
+                .map(|component| {
+                    let offset = start + component * size;
+                    let b = bytes.get(offset..offset + size).ok_or_else(|| format!("el accessor {} se sale de su bufferView", index))?;
+                    let value = match accessor.component_type {
+                        5120 => b[0] as i8 as f64,
+                        5121 => b[0] as f64,
+                        5122 => i16::from_le_bytes([b[0], b[1]]) as f64,
+                        5123 => u16::from_le_bytes([b[0], b[1]]) as f64,
+                        5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                        _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    };
+                    Ok(match (accessor.normalized, accessor.component_type) {
+                        (true, 5120) => (value / 127.0).max(-1.0),
+                        (true, 5121) => value / 255.0,
+                        (true, 5122) => (value / 32767.0).max(-1.0),
+                        (true, 5123) => value / 65535.0,
+                        _ => value,
+                    })
+                })
+                .collect::<Result<Vec<f64>, String>>()?;
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+
+    // Triángulos de todos los primitivos de una malla, llevados al mundo con `world`,
+    // y la tabla con los materiales que usan
+    fn mesh_triangles(
+        &mut self,
+        index: usize,
+        world: &Mat4,
+        materials: &[Material],
+        fallback: &Material,
+    ) -> Result<(Vec<Triangle>, Vec<Material>), String> {
+        let document: &'a Document = self.document;
+        let mesh = document.meshes.get(index).ok_or_else(|| format!("la malla {} no existe", index))?;
+        // Las normales se transforman con la inversa traspuesta para que sigan siendo
+        // perpendiculares aunque la escala no sea uniforme
+        let normal_matrix = glm::inverse_transpose(glm::mat4_to_mat3(world));
+        let mut triangles = Vec::new();
+        let mut table = Vec::new();
+        let mut slots: HashMap<Option<usize>, usize> = HashMap::new();
+        for primitive in &mesh.primitives {
+            let mode = primitive.mode.unwrap_or(4);
+            if !matches!(mode, 4..=6) {
+                self.warn(format!("la malla {} tiene primitivos de puntos o líneas, que no se dibujan", index));
+                continue;
+            }
+            let Some(&positions) = primitive.attributes.get("POSITION") else {
+                self.warn(format!("la malla {} tiene un primitivo sin POSITION", index));
+                continue;
+            };
+            let positions: Vec<Vec3> = self
+                .accessor(positions, &["VEC3"])?
+                .iter()
+                .map(|p| glm::vec4_to_vec3(&(world * Vec4::new(p[0] as f32, p[1] as f32, p[2] as f32, 1.0))))
+                .collect();
+            let normals: Option<Vec<Vec3>> = match primitive.attributes.get("NORMAL") {
+                Some(&accessor) => Some(
+                    self.accessor(accessor, &["VEC3"])?
+                        .iter()
+                        .map(|n| (normal_matrix * Vec3::new(n[0] as f32, n[1] as f32, n[2] as f32)).normalize())
+                        .collect(),
+                ),
+                None => None,
+            };
+            // glTF pone v = 0 arriba de la imagen y los materiales abajo
+            let uvs: Option<Vec<[f32; 2]>> = match primitive.attributes.get("TEXCOORD_0") {
+                Some(&accessor) => Some(self.accessor(accessor, &["VEC2"])?.iter().map(|uv| [uv[0] as f32, 1.0 - uv[1] as f32]).collect()),
+                None => None,
+            };
+            let indices: Vec<usize> = match primitive.indices {
+                Some(accessor) => self.accessor(accessor, &["SCALAR"])?.iter().map(|i| i[0] as usize).collect(),
+                None => (0..positions.len()).collect(),
+            };
+            let material = match slots.get(&primitive.material) {
+                Some(&slot) => slot,
+                None => {
+                    table.push(match primitive.material {
+                        Some(material) => materials.get(material).ok_or_else(|| format!("el material {} no existe", material))?.clone(),
+                        None => fallback.clone(),
+                    });
+                    slots.insert(primitive.material, table.len() - 1);
+                    table.len() - 1
+                }
+            };
+
+            for corners in triangle_corners(&indices, mode) {
+                if corners.iter().any(|&i| i >= positions.len()) {
+                    return Err(format!("la malla {} usa un índice de vértice fuera de rango", index));
+                }
+                let [a, b, c] = corners;
+                triangles.push(Triangle {
+                    vertices: [positions[a], positions[b], positions[c]],
+                    normals: normals.as_ref().and_then(|n| Some([*n.get(a)?, *n.get(b)?, *n.get(c)?])),
+                    uvs: uvs.as_ref().and_then(|uv| Some([*uv.get(a)?, *uv.get(b)?, *uv.get(c)?])),
+                    material,
+                });
+            }
+        }
+        Ok((triangles, table))
+    }
+
+    // Material PBR metal-rugosidad traducido al modelo de Phong de la escena: la
+    // rugosidad da el exponente especular, el metal la reflectividad y quita difuso
+    fn material(&mut self, def: &MaterialDef, texture_manager: &mut TextureManager, id: usize) -> Result<Material, String> {
+        let pbr = def.pbr_metallic_roughness.clone().unwrap_or_default();
+        let [r, g, b, alpha] = pbr.base_color_factor;
+        let metallic = pbr.metallic_factor.clamp(0.0, 1.0);
+        let roughness = pbr.roughness_factor.clamp(0.0, 1.0);
+        let name = def.name.clone().unwrap_or_else(|| format!("{}", id));
+
+        // Exponente de Blinn-Phong equivalente a GGX con alfa = rugosidad^2
+        let alpha_roughness = (roughness * roughness).max(0.01);
+        let specular = (2.0 / (alpha_roughness * alpha_roughness) - 2.0).clamp(1.0, 2000.0);
+        let reflectivity = metallic * (1.0 - roughness);
+        let albedo = [1.0 - metallic, (0.04 + 0.96 * metallic) * (1.0 - roughness)];
+
+        let extension = |key: &str, field: &str| {
+            def.extensions.get(key).and_then(|value| value.get(field)).and_then(|value| value.as_f64()).map(|value| value as f32)
+        };
+        let transmission = extension("KHR_materials_transmission", "transmissionFactor").unwrap_or(0.0);
+        let refraction_index = extension("KHR_materials_ior", "ior").unwrap_or(1.5);
+        let blend = def.alpha_mode.as_deref() == Some("BLEND");
+        let transparency = if blend { transmission.max(1.0 - alpha) } else { transmission }.clamp(0.0, 1.0);
+
+        let mut material = Material::new(Radiance::new(r, g, b), specular, albedo, reflectivity, transparency, refraction_index);
+        if let Some(texture) = &pbr.base_color_texture {
+            let (index, sampler) = self.texture(texture, texture_manager, false)?;
+            material.texture_index = Some(index);
+            material.sampler = sampler;
+            material.texture_tint = Radiance::new(r, g, b);
+            if def.alpha_mode.as_deref() == Some("MASK") {
+                material.alpha_cutout = Some(AlphaCutout {
+                    texture: Arc::clone(texture_manager.get_texture(index)),
+                    threshold: def.alpha_cutoff.unwrap_or(0.5),
+                });
+            }
+        }
+        if let Some(texture) = &def.normal_texture {
+            material.normal_map = Some(self.texture(texture, texture_manager, true)?.0);
+        }
+        if pbr.metallic_roughness_texture.is_some() || def.occlusion_texture.is_some() || def.emissive_texture.is_some() {
+            self.warn(format!("el material {} usa mapas de metal, rugosidad, oclusión o emisión; solo se usan sus factores", name));
+        }
+
+        let strength = extension("KHR_materials_emissive_strength", "emissiveStrength").unwrap_or(1.0);
+        let [er, eg, eb] = def.emissive_factor;
+        if er.max(eg).max(eb) > 0.0 && strength > 0.0 {
+            material.emissive_color = Some(Radiance::new(er, eg, eb));
+            material.emissive_intensity = strength;
+        }
+        for key in def.extensions.keys() {
+            if !SUPPORTED_EXTENSIONS.contains(&key.as_str()) {
+                self.warn(format!("el material {} usa la extensión {}, que se ignora", name, key));
+            }
+        }
+        material.id = id;
+        Ok(material)
+    }
+
+    // Índice en el TextureManager de la imagen de una textura, cargada una sola vez,
+    // y el muestreo que pide su sampler
+    fn texture(&mut self, reference: &TextureRef, texture_manager: &mut TextureManager, linear: bool) -> Result<(usize, Sampler), String> {
+        let document: &'a Document = self.document;
+        if reference.tex_coord != 0 {
+            self.warn(format!("solo se usa TEXCOORD_0; las texturas con TEXCOORD_{} se leen con él", reference.tex_coord));
+        }
+        let texture = document.textures.get(reference.index).ok_or_else(|| format!("la textura {} no existe", reference.index))?;
+        let image = texture.source.ok_or_else(|| format!("la textura {} no tiene imagen", reference.index))?;
+        let sampler = match texture.sampler {
+            Some(sampler) => document.samplers.get(sampler).ok_or_else(|| format!("el sampler {} no existe", sampler))?.to_sampler(),
+            // Sin sampler, glTF pide repetir y filtrar a gusto: los modelos suelen
+            // tener texturas fotográficas, así que se suavizan
+            None => Sampler::new(Filter::Trilinear, WrapMode::Repeat),
+        };
+        if let Some(index) = self.images.get(&(image, linear)) {
+            return Ok((*index, sampler));
+        }
+
+        let def = document.images.get(image).ok_or_else(|| format!("la imagen {} no existe", image))?;
+        let bytes = match (&def.uri, def.buffer_view) {
+            (Some(uri), _) => self.read_uri(uri)?,
+            (None, Some(view)) => self.buffer_view(view)?.0.to_vec(),
+            (None, None) => return Err(format!("la imagen {} no tiene URI ni bufferView", image)),
+        };
+        let decoded = image::load_from_memory(&bytes).map_err(|e| format!("no se pudo leer la imagen {}: {}", image, e))?;
+        let texture = if linear { Texture::from_image_linear(decoded) } else { Texture::from_image(decoded) };
+        let index = texture_manager.add_texture(texture);
+        self.images.insert((image, linear), index);
+        Ok((index, sampler))
+    }
+
+    // La cámara mira hacia -z del nodo con +y hacia arriba. La escena tiene un campo
+    // de visión fijo, así que solo se usa la posición y orientación
+    fn camera(&mut self, index: usize, world: &Mat4, camera: &mut Option<Camera>) -> Result<(), String> {
+        let document: &'a Document = self.document;
+        let def = document.cameras.get(index).ok_or_else(|| format!("la cámara {} no existe", index))?;
+        if camera.is_some() {
+            self.warn("el modelo tiene varias cámaras; se usa la primera".to_string());
+            return Ok(());
+        }
+        match &def.perspective {
+            Some(perspective) if (perspective.yfov.to_degrees() - 60.0).abs() > 0.5 => self.warn(format!(
+                "la cámara tiene un campo de visión de {:.0}° y la escena usa 60°",
+                perspective.yfov.to_degrees()
+            )),
+            None => self.warn("las cámaras ortográficas se tratan como de perspectiva".to_string()),
+            _ => {}
+        }
+        let eye = glm::vec4_to_vec3(&(world * Vec4::new(0.0, 0.0, 0.0, 1.0)));
+        let forward = glm::vec4_to_vec3(&(world * Vec4::new(0.0, 0.0, -1.0, 0.0))).normalize();
+        let up = glm::vec4_to_vec3(&(world * Vec4::new(0.0, 1.0, 0.0, 0.0))).normalize();
+        *camera = Some(Camera::new(eye, eye + forward, up));
+        Ok(())
+    }
+
+    // Luces de KHR_lights_punctual. Apuntan hacia -z del nodo y las puntuales y los
+    // focos bajan con el cuadrado de la distancia
+    fn light(&mut self, index: usize, world: &Mat4) -> Result<Light, String> {
+        let document: &'a Document = self.document;
+        let def = document
+            .extensions
+            .lights_punctual
+            .as_ref()
+            .and_then(|lights| lights.lights.get(index))
+            .ok_or_else(|| format!("la luz {} no existe", index))?;
+        let [r, g, b] = def.color;
+        let color = Radiance::new(r, g, b);
+        let position = glm::vec4_to_vec3(&(world * Vec4::new(0.0, 0.0, 0.0, 1.0)));
+        let direction = glm::vec4_to_vec3(&(world * Vec4::new(0.0, 0.0, -1.0, 0.0))).normalize();
+        if def.range.is_some() {
+            self.warn("el alcance de las luces se ignora".to_string());
+        }
+        match def.kind.as_str() {
+            "directional" => Ok(Light::directional(direction, color, def.intensity)),
+            "point" => Ok(Light { falloff: Falloff::InverseSquare, ..Light::new(position, color, def.intensity) }),
+            "spot" => {
+                let spot = def.spot.clone().unwrap_or_default();
+                Ok(Light::spot(position, direction, spot.inner_cone_angle, spot.outer_cone_angle, color, def.intensity))
+            }
+            other => Err(format!("tipo de luz desconocido '{}'", other)),
+        }
+    }
+}
+
+// Vértices de cada triángulo según el modo del primitivo: 4 lista, 5 tira, 6 abanico
+fn triangle_corners(indices: &[usize], mode: u32) -> Vec<[usize; 3]> {
+    match mode {
+        5 => (2..indices.len())
+            .map(|i| if i % 2 == 0 { [indices[i - 2], indices[i - 1], indices[i]] } else { [indices[i - 1], indices[i - 2], indices[i]] })
+            .collect(),
+        6 => (2..indices.len()).map(|i| [indices[0], indices[i - 1], indices[i]]).collect(),
+        _ => indices.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect(),
+    }
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    };
+    let digits: Vec<u8> = text.bytes().filter(|c| !c.is_ascii_whitespace() && *c != b'=').map(value).collect::<Option<_>>()?;
+    let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+    for group in digits.chunks(4) {
+        let bits = group.iter().enumerate().fold(0u32, |bits, (i, &digit)| bits | (digit as u32) << (18 - 6 * i));
+        bytes.extend_from_slice(&bits.to_be_bytes()[1..group.len()]);
+    }
+    Some(bytes)
+}
+
+// Las URIs de archivos pueden traer espacios y otros caracteres como %20
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Partes del JSON de glTF 2.0 que se usan; el resto de campos se ignora
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Document {
+    #[serde(default)]
+    extensions_used: Vec<String>,
+    #[serde(default)]
+    extensions_required: Vec<String>,
+    scene: Option<usize>,
+    #[serde(default)]
+    scenes: Vec<SceneDef>,
+    #[serde(default)]
+    nodes: Vec<Node>,
+    #[serde(default)]
+    meshes: Vec<MeshDef>,
+    #[serde(default)]
+    accessors: Vec<AccessorDef>,
+    #[serde(default)]
+    buffer_views: Vec<BufferViewDef>,
+    #[serde(default)]
+    buffers: Vec<BufferDef>,
+    #[serde(default)]
+    materials: Vec<MaterialDef>,
+    #[serde(default)]
+    textures: Vec<TextureDef>,
+    #[serde(default)]
+    images: Vec<ImageDef>,
+    #[serde(default)]
+    samplers: Vec<SamplerDef>,
+    #[serde(default)]
+    cameras: Vec<CameraDef>,
+    #[serde(default)]
+    extensions: DocumentExtensions,
+}
+
+#[derive(Deserialize, Default)]
+struct DocumentExtensions {
+    #[serde(rename = "KHR_lights_punctual")]
+    lights_punctual: Option<LightsDef>,
+}
+
+#[derive(Deserialize)]
+struct LightsDef {
+    lights: Vec<LightDef>,
+}
+
+#[derive(Deserialize)]
+struct LightDef {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default = "default_color")]
+    color: [f32; 3],
+    #[serde(default = "default_one")]
+    intensity: f32,
+    range: Option<f32>,
+    spot: Option<SpotDef>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+struct SpotDef {
+    inner_cone_angle: f32,
+    outer_cone_angle: f32,
+}
+
+impl Default for SpotDef {
+    fn default() -> Self {
+        SpotDef { inner_cone_angle: 0.0, outer_cone_angle: std::f32::consts::FRAC_PI_4 }
+    }
+}
+
+#[derive(Deserialize)]
+struct SceneDef {
+    #[serde(default)]
+    nodes: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+struct Node {
+    #[serde(default)]
+    children: Vec<usize>,
+    mesh: Option<usize>,
+    camera: Option<usize>,
+    matrix: Option<[f32; 16]>, // Por columnas
+    translation: Option<[f32; 3]>,
+    rotation: Option<[f32; 4]>, // Cuaternión x, y, z, w
+    scale: Option<[f32; 3]>,
+    extensions: Option<NodeExtensions>,
+}
+
+impl Node {
+    fn local_transform(&self) -> Mat4 {
+        if let Some(matrix) = &self.matrix {
+            return glm::make_mat4(matrix);
+        }
+        let translation = glm::translation(&Vec3::from(self.translation.unwrap_or([0.0; 3])));
+        let [x, y, z, w] = self.rotation.unwrap_or([0.0, 0.0, 0.0, 1.0]);
+        let rotation = glm::quat_to_mat4(&glm::quat(x, y, z, w));
+        let scale = glm::scaling(&Vec3::from(self.scale.unwrap_or([1.0; 3])));
+        translation * rotation * scale
+    }
+}
+
+#[derive(Deserialize)]
+struct NodeExtensions {
+    #[serde(rename = "KHR_lights_punctual")]
+    lights_punctual: Option<NodeLight>,
+}
+
+#[derive(Deserialize)]
+struct NodeLight {
+    light: usize,
+}
+
+#[derive(Deserialize)]
+struct MeshDef {
+    primitives: Vec<PrimitiveDef>,
+}
+
+#[derive(Deserialize)]
+struct PrimitiveDef {
+    attributes: HashMap<String, usize>,
+    indices: Option<usize>,
+    material: Option<usize>,
+    mode: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccessorDef {
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    #[serde(default)]
+    normalized: bool,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: String,
+    sparse: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferViewDef {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferDef {
+    uri: Option<String>,
+    byte_length: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MaterialDef {
+    name: Option<String>,
+    pbr_metallic_roughness: Option<PbrDef>,
+    normal_texture: Option<TextureRef>,
+    occlusion_texture: Option<TextureRef>,
+    emissive_texture: Option<TextureRef>,
+    #[serde(default)]
+    emissive_factor: [f32; 3],
+    alpha_mode: Option<String>,
+    alpha_cutoff: Option<f32>,
+    #[serde(default)]
+    extensions: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+struct PbrDef {
+    base_color_factor: [f32; 4],
+    base_color_texture: Option<TextureRef>,
+    metallic_factor: f32,
+    roughness_factor: f32,
+    metallic_roughness_texture: Option<TextureRef>,
+}
+
+impl Default for PbrDef {
+    fn default() -> Self {
+        PbrDef {
+            base_color_factor: [1.0; 4],
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct TextureRef {
+    index: usize,
+    #[serde(default)]
+    tex_coord: usize,
+}
+
+#[derive(Deserialize)]
+struct TextureDef {
+    source: Option<usize>,
+    sampler: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageDef {
+    uri: Option<String>,
+    buffer_view: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SamplerDef {
+    mag_filter: Option<u32>,
+    min_filter: Option<u32>,
+    wrap_s: Option<u32>,
+}
+
+impl SamplerDef {
+    // Las constantes son las de OpenGL. Solo hay un modo de repetición para u y v,
+    // así que manda el de s
+    fn to_sampler(&self) -> Sampler {
+        let filter = match (self.mag_filter, self.min_filter) {
+            (Some(9728), _) | (_, Some(9728)) => Filter::Nearest, // NEAREST
+            (_, Some(9729)) => Filter::Bilinear,                   // LINEAR, sin mipmaps
+            _ => Filter::Trilinear,
+        };
+        let wrap = match self.wrap_s {
+            Some(33071) => WrapMode::Clamp,  // CLAMP_TO_EDGE
+            Some(33648) => WrapMode::Mirror, // MIRRORED_REPEAT
+            _ => WrapMode::Repeat,
+        };
+        Sampler::new(filter, wrap)
+    }
+}
+
+#[derive(Deserialize)]
+struct CameraDef {
+    perspective: Option<PerspectiveDef>,
+}
+
+#[derive(Deserialize)]
+struct PerspectiveDef {
+    yfov: f32,
+}
+
+fn default_color() -> [f32; 3] {
+    [1.0; 3]
+}
+
+fn default_one() -> f32 {
+    1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray_intersect::RayIntersect;
+
+    // .glb con un triángulo en un nodo hijo, una cámara, un foco y un material metálico
+    fn write_glb(path: &Path) {
+        let mut binary = Vec::new();
+        for value in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0] {
+            binary.extend_from_slice(&value.to_le_bytes());
+        }
+        for index in [0u16, 1, 2, 0] {
+            binary.extend_from_slice(&index.to_le_bytes());
+        }
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "extensionsUsed": ["KHR_lights_punctual", "KHR_texture_transform"],
+            "extensions": {"KHR_lights_punctual": {"lights": [{"type": "spot", "intensity": 5, "spot": {"outerConeAngle": 0.5}}]}},
+            "scene": 0,
+            "scenes": [{"nodes": [0, 2, 3]}],
+            "nodes": [
+                {"translation": [0, 0, 5], "children": [1]},
+                {"mesh": 0, "scale": [2, 2, 2]},
+                {"camera": 0, "translation": [0, 1, 10]},
+                {"rotation": [-0.7071068, 0, 0, 0.7071068], "translation": [0, 4, 0], "extensions": {"KHR_lights_punctual": {"light": 0}}}
+            ],
+            "cameras": [{"type": "perspective", "perspective": {"yfov": 1.0471976, "znear": 0.1}}],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0, "NORMAL": 1}, "indices": 2, "material": 0}]}],
+            "materials": [{"name": "oro", "pbrMetallicRoughness": {"baseColorFactor": [1, 0.8, 0.3, 1], "metallicFactor": 1, "roughnessFactor": 0}}],
+            "buffers": [{"byteLength": 80}],
+            "bufferViews": [
+                {"buffer": 0, "byteOffset": 0, "byteLength": 72, "byteStride": 12},
+                {"buffer": 0, "byteOffset": 72, "byteLength": 6}
+            ],
+            "accessors": [
+                {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+                {"bufferView": 0, "byteOffset": 36, "componentType": 5126, "count": 3, "type": "VEC3"},
+                {"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}
+            ]
+        }"#;
+        let mut json = json.as_bytes().to_vec();
+        while json.len() & 3 != 0 {
+            json.push(b' ');
+        }
+        let mut glb = Vec::new();
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&((12 + 8 + json.len() + 8 + binary.len()) as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(binary.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&binary);
+        fs::write(path, glb).unwrap();
+    }
+
+    #[test]
+    fn test_load_glb() {
+        let dir = std::env::temp_dir().join("modelo_prueba_gltf");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("triangulo.glb");
+        write_glb(&path);
+
+        let mut texture_manager = TextureManager::new();
+        let transform = glm::translation(&Vec3::new(10.0, 0.0, 0.0));
+        let model = load_gltf(&path.display().to_string(), &mut texture_manager, &transform, Material::black(), 3).unwrap();
+        assert_eq!((model.meshes.len(), model.material_count), (1, 1));
+
+        // El triángulo va escalado por su nodo, movido por el padre y por la escena
+        let hit = model.meshes[0].ray_intersect(&Vec3::new(10.5, 0.5, 10.0), &Vec3::new(0.0, 0.0, -1.0));
+        assert!(hit.is_intersecting && (hit.distance - 5.0).abs() < 1e-4);
+        assert!((hit.smooth_normal - Vec3::new(0.0, 0.0, 1.0)).norm() < 1e-5);
+        assert_eq!((hit.material.id, hit.material.reflectivity), (3, 1.0));
+        assert!((hit.material.diffuse.g - 0.8).abs() < 1e-6 && hit.material.albedo[0] == 0.0);
+
+        // El foco gira -90° alrededor de x, así que apunta hacia abajo
+        match model.lights[0].kind {
+            crate::light::LightKind::Spot { direction, outer_angle, .. } => {
+                assert!((direction - Vec3::new(0.0, -1.0, 0.0)).norm() < 1e-5 && outer_angle == 0.5)
+            }
+            _ => panic!("se esperaba un foco"),
+        }
+        assert!((model.lights[0].position - Vec3::new(10.0, 4.0, 0.0)).norm() < 1e-5);
+        let camera = model.camera.unwrap();
+        assert!((camera.eye - Vec3::new(10.0, 1.0, 10.0)).norm() < 1e-5);
+        assert!((camera.center - Vec3::new(10.0, 1.0, 9.0)).norm() < 1e-5);
+        assert_eq!(model.warnings, vec!["la extensión KHR_texture_transform no está soportada y se ignora".to_string()]);
+    }
+
+    #[test]
+    fn test_accessor_out_of_view() {
+        let document: Document = serde_json::from_str(
+            r#"{
+                "buffers": [{"byteLength": 12}],
+                "bufferViews": [{"buffer": 0, "byteLength": 12}, {"buffer": 0, "byteOffset": 8, "byteLength": 18446744073709551615}],
+                "accessors": [
+                    {"bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC3"},
+                    {"bufferView": 0, "componentType": 5126, "count": 2, "type": "VEC3"},
+                    {"bufferView": 0, "componentType": 5126, "count": 18446744073709551615, "type": "VEC3"},
+                    {"bufferView": 0, "byteOffset": 18446744073709551615, "componentType": 5126, "count": 1, "type": "SCALAR"},
+                    {"bufferView": 1, "componentType": 5126, "count": 1, "type": "SCALAR"},
+                    {"componentType": 5126, "count": 18446744073709551615, "type": "SCALAR"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let mut loader = Loader {
+            document: &document,
+            directory: Path::new(""),
+            buffers: vec![vec![0; 12]],
+            images: HashMap::new(),
+            warnings: Vec::new(),
+        };
+        assert_eq!(loader.accessor(0, &["VEC3"]).unwrap(), vec![vec![0.0; 3]]);
+        // Las cantidades y desplazamientos enormes se rechazan sin reservar memoria ni desbordar
+        for index in 1..5 {
+            let error = loader.accessor(index, &["VEC3", "SCALAR"]).unwrap_err();
+            assert!(error.contains("se sale de su"), "{}: {}", index, error);
+        }
+        assert!(loader.accessor(5, &["SCALAR"]).unwrap_err().contains("no tiene bufferView"));
+    }
+
+    #[test]
+    fn test_base_color_factor_tints_texture() {
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255])))
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let json = format!(
+            r#"{{
+                "buffers": [{{"byteLength": {length}}}],
+                "bufferViews": [{{"buffer": 0, "byteLength": {length}}}],
+                "images": [{{"bufferView": 0, "mimeType": "image/png"}}],
+                "textures": [{{"source": 0}}],
+                "materials": [{{"pbrMetallicRoughness": {{"baseColorFactor": [1, 0.5, 0.25, 1], "baseColorTexture": {{"index": 0}}}}}}]
+            }}"#,
+            length = png.len()
+        );
+        let document: Document = serde_json::from_str(&json).unwrap();
+        let mut loader = Loader {
+            document: &document,
+            directory: Path::new(""),
+            buffers: vec![png],
+            images: HashMap::new(),
+            warnings: Vec::new(),
+        };
+        let mut texture_manager = TextureManager::new();
+        let material = loader.material(&document.materials[0], &mut texture_manager, 0).unwrap();
+        // La textura blanca toma el color del factor
+        let color = material.get_diffuse_color(0.5, 0.5, 0.0, &texture_manager);
+        assert!((color.r - 1.0).abs() < 1e-5 && (color.g - 0.5).abs() < 1e-5 && (color.b - 0.25).abs() < 1e-5, "{:?}", color);
+        assert!(loader.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_decode_base64() {
+        assert_eq!(decode_base64("aGVsbG8gZ2xURg==").unwrap(), b"hello glTF");
+        assert_eq!(decode_base64("AAEC").unwrap(), vec![0, 1, 2]);
+        assert!(decode_base64("a$b=").is_none());
+        assert_eq!(percent_decode("mi%20modelo.bin"), "mi modelo.bin");
+    }
+}
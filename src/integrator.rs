@@ -0,0 +1,133 @@
+use nalgebra_glm::Vec3;
+use rand::RngCore;
+use std::fmt;
+use crate::bvh::Bvh;
+use crate::environment::Environment;
+use crate::debug_views::{
+    AmbientOcclusionIntegrator, DepthIntegrator, MaterialIndexIntegrator, NormalIntegrator, UvIntegrator,
+};
+use crate::light::SceneLights;
+use crate::materials::TextureManager;
+use crate::path_tracer::PathTracer;
+use crate::radiance::Radiance;
+use crate::ray_intersect::Object;
+use crate::sky::Sky;
+use crate::whitted::WhittedIntegrator;
+
+// Lo que un integrador necesita de la escena para calcular la luz de un rayo
+pub struct RenderContext<'a> {
+    pub objects: &'a Bvh<Object>,
+    pub lights: &'a SceneLights,
+    pub texture_manager: &'a TextureManager,
+    pub sky: &'a Sky,      // Fondo de los rayos que no golpean nada y luz del cielo
+    pub environment: Option<&'a Environment>, // Mapa de entorno que reemplaza al cielo
+    pub ambient: Radiance, // Luz ambiente para los integradores sin luz indirecta
+    // Ángulo que abarca un píxel: el ancho del rayo crece con la distancia recorrida
+    // y elige el nivel de mipmap de las texturas
+    pub pixel_spread: f32,
+}
+
+impl RenderContext<'_> {
+    // Lo que ve un rayo que no golpea nada
+    pub fn background(&self, direction: &Vec3) -> Radiance {
+        match self.environment {
+            Some(environment) => environment.radiance(direction),
+            None => self.sky.background(direction),
+        }
+    }
+
+    // Luz del cielo que reciben las superficies, sin los discos del sol y la luna
+    pub fn sky_light(&self, direction: &Vec3) -> Radiance {
+        match self.environment {
+            Some(environment) => environment.radiance(direction),
+            None => self.sky.radiance(direction),
+        }
+    }
+}
+
+// Calcula la radiancia que llega a la cámara por un rayo. `render` lo llama una
+// vez por píxel y muestra; el generador aleatorio es el del hilo que traza el píxel
+pub trait Integrator: Send + Sync {
+    fn radiance(&self, origin: &Vec3, direction: &Vec3, context: &RenderContext, rng: &mut dyn RngCore) -> Radiance;
+}
+
+// Integradores disponibles en la ventana (teclas 1 a 7) y en la línea de comandos
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderMode {
+    Whitted,          // Trazado recursivo clásico: luz directa, reflexión y refracción
+    PathTraced,       // Trazado de caminos de Monte Carlo con luz indirecta
+    AmbientOcclusion, // Fracción del hemisferio sin obstáculos cercanos
+    Normals,          // Normal de la superficie como color
+    Uv,               // Coordenadas de textura en rojo y verde
+    Depth,            // Distancia a la cámara, más claro cuanto más cerca
+    MaterialIndex,    // Un color distinto por material de la escena
+}
+
+impl RenderMode {
+    // En el orden de las teclas numéricas
+    pub const ALL: [RenderMode; 7] = [
+        RenderMode::Whitted,
+        RenderMode::PathTraced,
+        RenderMode::AmbientOcclusion,
+        RenderMode::Normals,
+        RenderMode::Uv,
+        RenderMode::Depth,
+        RenderMode::MaterialIndex,
+    ];
+
+    pub fn integrator(self) -> Box<dyn Integrator> {
+        match self {
+            RenderMode::Whitted => Box::new(WhittedIntegrator::new()),
+            RenderMode::PathTraced => Box::new(PathTracer::new()),
+            RenderMode::AmbientOcclusion => Box::new(AmbientOcclusionIntegrator::new()),
+            RenderMode::Normals => Box::new(NormalIntegrator),
+            RenderMode::Uv => Box::new(UvIntegrator),
+            RenderMode::Depth => Box::new(DepthIntegrator::new()),
+            RenderMode::MaterialIndex => Box::new(MaterialIndexIntegrator),
+        }
+    }
+}
+
+impl fmt::Display for RenderMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderMode::Whitted => write!(f, "Whitted"),
+            RenderMode::PathTraced => write!(f, "Path tracing"),
+            RenderMode::AmbientOcclusion => write!(f, "Oclusión ambiental"),
+            RenderMode::Normals => write!(f, "Normales"),
+            RenderMode::Uv => write!(f, "UV"),
+            RenderMode::Depth => write!(f, "Profundidad"),
+            RenderMode::MaterialIndex => write!(f, "Índice de material"),
+        }
+    }
+}
+
+pub fn reflect(incident: &Vec3, normal: &Vec3) -> Vec3 {
+    incident - 2.0 * incident.dot(normal) * normal
+}
+
+pub fn refract(incident: &Vec3, normal: &Vec3, eta_t: f32) -> Vec3 {
+    let cosi = -incident.dot(normal).clamp(-1.0, 1.0);
+
+    let (n_cosi, eta, n_normal);
+
+    if cosi < 0.0 {
+        n_cosi = -cosi;
+        eta = 1.0 / eta_t;
+        n_normal = -normal;
+    } else {
+        // Ray is leaving the object
+        n_cosi = cosi;
+        eta = eta_t;
+        n_normal = *normal;
+    }
+
+    let k = 1.0 - eta * eta * (1.0 - n_cosi * n_cosi);
+
+    if k < 0.0 {
+        // Total internal reflection
+        reflect(incident, &n_normal)
+    } else {
+        eta * incident + (eta * n_cosi - k.sqrt( )) * n_normal
+    }
+}
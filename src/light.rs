@@ -1,18 +1,494 @@
 use nalgebra_glm::Vec3;
-use crate::color::Color;
+use rand::Rng;
+use std::f32::consts::PI;
+use crate::materials::Material;
+use crate::radiance::Radiance;
+use crate::sampling::{orthonormal_basis, Distribution1D};
+
+// Luces de primitivas emisivas elegidas por punto sombreado, con un rayo de sombra cada una
+const EMITTER_SAMPLES: u32 = 4;
+
+// Tipo de fuente: en qué dirección emite y desde dónde
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    // Emite en todas direcciones desde `position` (o desde su forma de área)
+    Point,
+    // Luz lejana como el sol o la luna: todos los rayos son paralelos a
+    // `direction` (hacia donde viaja la luz) y no hay distancia ni posición
+    Directional { direction: Vec3 },
+    // Foco en `position` que apunta a `direction`. Dentro del cono interior la luz
+    // es completa y se desvanece hasta el cono exterior (ángulos en radianes)
+    Spot { direction: Vec3, inner_angle: f32, outer_angle: f32 },
+}
+
+// Forma de la fuente de luz. Las de área se muestrean al azar para obtener
+// sombras suaves con penumbra
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightShape {
+    Point,
+    // Rectángulo centrado en `position` con lados `u` y `v`
+    Rectangle { u: Vec3, v: Vec3 },
+    // Esfera centrada en `position`
+    Sphere { radius: f32 },
+    // Caja alineada a los ejes centrada en `position`, como un bloque emisivo
+    Box { size: Vec3 },
+    // Triángulo de una malla emisiva, con las esquinas relativas a `position`
+    Triangle { corners: [Vec3; 3] },
+}
+
+// Cómo cambia el aporte de la luz con la posición del punto iluminado
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Falloff {
+    // La misma intensidad a cualquier distancia
+    Constant,
+    // Física: la intensidad es la de una distancia de 1 y baja con 1/d^2
+    InverseSquare,
+    // 1 / (constant + linear d + quadratic d^2), como en OpenGL clásico
+    Custom { constant: f32, linear: f32, quadratic: f32 },
+    // Superficie emisiva: el aporte depende del área visible, del ángulo con que
+    // se ve y de la distancia, como la luz de una superficie real
+    Surface,
+}
+
 #[derive(Clone, Copy)]
 pub struct Light {
     pub position: Vec3,
-    pub color: Color,
+    pub color: Radiance,
     pub intensity: f32,
+    pub kind: LightKind,
+    pub shape: LightShape,
+    pub samples: u32, // Rayos de sombra por punto sombreado (solo luces de área)
+    pub falloff: Falloff,
+}
+
+// Un punto de la luz visto desde el punto sombreado: dirección unitaria hacia la
+// luz, distancia hasta ella (infinita en las direccionales) y el peso que escala la
+// intensidad para esa muestra (1 sin atenuación)
+pub struct LightSample {
+    pub direction: Vec3,
+    pub distance: f32,
+    pub weight: f32,
 }
 
 impl Light {
-    pub fn new(position: Vec3, color: Color, intensity: f32) -> Self {
+    pub fn new(position: Vec3, color: Radiance, intensity: f32) -> Self {
         Light {
             position,
             color,
             intensity,
+            kind: LightKind::Point,
+            shape: LightShape::Point,
+            samples: 1,
+            falloff: Falloff::Constant,
         }
     }
-}
\ No newline at end of file
+
+    pub fn directional(direction: Vec3, color: Radiance, intensity: f32) -> Self {
+        Light {
+            kind: LightKind::Directional { direction: direction.normalize() },
+            ..Light::new(Vec3::zeros(), color, intensity)
+        }
+    }
+
+    pub fn spot(position: Vec3, direction: Vec3, inner_angle: f32, outer_angle: f32, color: Radiance, intensity: f32) -> Self {
+        Light {
+            kind: LightKind::Spot { direction: direction.normalize(), inner_angle, outer_angle },
+            falloff: Falloff::InverseSquare,
+            ..Light::new(position, color, intensity)
+        }
+    }
+
+    // Luz equivalente a una primitiva con material emisivo; None si no emite
+    pub fn emitter(position: Vec3, shape: LightShape, material: &Material) -> Option<Self> {
+        if !material.is_emissive() {
+            return None;
+        }
+        let color = material.emissive_color?;
+        Some(Light {
+            position,
+            color,
+            intensity: material.emissive_intensity,
+            kind: LightKind::Point,
+            shape,
+            samples: 1,
+            falloff: Falloff::Surface,
+        })
+    }
+
+    // Potencia emitida, a menos de una constante: intensidad por brillo por área
+    fn power(&self) -> f32 {
+        self.intensity * self.color.luminance() * self.surface_area()
+    }
+
+    // Cantidad de puntos a muestrear: una luz puntual o direccional no necesita más de uno
+    pub fn sample_count(&self) -> u32 {
+        match (self.kind, self.shape) {
+            (LightKind::Directional { .. }, _) | (_, LightShape::Point) => 1,
+            _ => self.samples.max(1),
+        }
+    }
+
+    // Punto al azar de la luz visto desde `from`, con el peso de su aporte: la
+    // atenuación con la distancia y, en los focos, el cono
+    pub fn sample(&self, from: &Vec3, rng: &mut (impl Rng + ?Sized)) -> LightSample {
+        if let LightKind::Directional { direction } = self.kind {
+            return LightSample { direction: -direction, distance: f32::INFINITY, weight: 1.0 };
+        }
+
+        // Una esfera vista desde fuera se muestrea en el cono que ocupa, así ningún
+        // punto cae en la cara oculta. Con la densidad uniforme en ese ángulo sólido
+        // el factor de forma vale lo mismo en todas las muestras
+        let (point, normal, solid_angle) = match self.shape {
+            LightShape::Sphere { radius } => match sample_sphere_cone(&self.position, radius, from, rng) {
+                Some((point, normal, solid_angle)) => (point, normal, Some(solid_angle)),
+                None => {
+                    let (point, normal) = self.sample_surface(rng);
+                    (point, normal, None)
+                }
+            },
+            _ => {
+                let (point, normal) = self.sample_surface(rng);
+                (point, normal, None)
+            }
+        };
+        let to_light = point - from;
+        let distance_squared = to_light.norm_squared().max(1e-8);
+        let distance = distance_squared.sqrt();
+        let direction = to_light / distance;
+
+        let attenuation = match self.falloff {
+            Falloff::Constant => 1.0,
+            Falloff::InverseSquare => 1.0 / distance_squared,
+            Falloff::Custom { constant, linear, quadratic } => {
+                1.0 / (constant + linear * distance + quadratic * distance_squared).max(1e-8)
+            }
+            // Factor de forma: A cos' / (pi d^2) con un punto uniforme del área, con la
+            // superficie emitiendo por ambas caras, o el ángulo sólido entre pi con un
+            // punto del cono. Se limita a 1 para que los puntos pegados al emisor no
+            // salgan como destellos
+            Falloff::Surface => match solid_angle {
+                Some(solid_angle) => (solid_angle / PI).min(1.0),
+                None => {
+                    let cos_light = normal.dot(&direction).abs();
+                    (self.surface_area() * cos_light / (PI * distance_squared)).min(1.0)
+                }
+            },
+        };
+        let cone = match self.kind {
+            LightKind::Spot { direction: axis, inner_angle, outer_angle } => {
+                spot_cone(axis.dot(&-direction), inner_angle, outer_angle)
+            }
+            _ => 1.0,
+        };
+        LightSample { direction, distance, weight: attenuation * cone }
+    }
+
+    fn surface_area(&self) -> f32 {
+        match self.shape {
+            LightShape::Point => 0.0,
+            LightShape::Rectangle { u, v } => u.cross(&v).norm(),
+            LightShape::Sphere { radius } => 4.0 * PI * radius * radius,
+            LightShape::Box { size } => 2.0 * (size.x * size.y + size.y * size.z + size.z * size.x),
+            LightShape::Triangle { corners: [a, b, c] } => 0.5 * (b - a).cross(&(c - a)).norm(),
+        }
+    }
+
+    // Punto uniforme sobre la superficie de la luz y la normal en ese punto
+    fn sample_surface(&self, rng: &mut (impl Rng + ?Sized)) -> (Vec3, Vec3) {
+        match self.shape {
+            LightShape::Point => (self.position, Vec3::zeros()),
+            LightShape::Rectangle { u, v } => {
+                let point = self.position + u * (rng.gen::<f32>() - 0.5) + v * (rng.gen::<f32>() - 0.5);
+                (point, u.cross(&v).normalize())
+            }
+            LightShape::Sphere { radius } => {
+                let z = 1.0 - 2.0 * rng.gen::<f32>();
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let phi = 2.0 * PI * rng.gen::<f32>();
+                let normal = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+                (self.position + normal * radius, normal)
+            }
+            LightShape::Box { size } => {
+                // Elegimos una cara con probabilidad proporcional a su área
+                let face_areas = [size.y * size.z, size.z * size.x, size.x * size.y];
+                let mut choice = rng.gen::<f32>() * (face_areas[0] + face_areas[1] + face_areas[2]);
+                let mut axis = 2;
+                for (index, area) in face_areas.iter().enumerate() {
+                    if choice < *area {
+                        axis = index;
+                        break;
+                    }
+                    choice -= area;
+                }
+                let side = if rng.gen::<bool>() { 0.5 } else { -0.5 };
+                let mut offset = Vec3::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5);
+                offset[axis] = side;
+                let mut normal = Vec3::zeros();
+                normal[axis] = side * 2.0;
+                (self.position + offset.component_mul(&size), normal)
+            }
+            LightShape::Triangle { corners: [a, b, c] } => {
+                // La raíz reparte los puntos de forma uniforme en vez de juntarlos en `a`
+                let s = rng.gen::<f32>().sqrt();
+                let t = rng.gen::<f32>();
+                let point = a * (1.0 - s) + b * (s * (1.0 - t)) + c * (s * t);
+                (self.position + point, (b - a).cross(&(c - a)).normalize())
+            }
+        }
+    }
+}
+
+// Luces que reciben los integradores. Las declaradas (sol, luna, luces del archivo)
+// se muestrean todas en cada punto; de las primitivas emisivas, que pueden ser miles
+// como los bloques de un lago de lava, se eligen unas pocas al azar con probabilidad
+// proporcional a su potencia, así el costo no crece con la cantidad de emisores
+pub struct SceneLights {
+    lights: Vec<Light>,
+    emitters: Vec<Light>,
+    selection: Option<Distribution1D>,
+}
+
+impl SceneLights {
+    pub fn new(lights: Vec<Light>) -> Self {
+        let (emitters, lights): (Vec<Light>, Vec<Light>) =
+            lights.into_iter().partition(|light| light.falloff == Falloff::Surface);
+        let selection = (!emitters.is_empty()).then(|| Distribution1D::new(emitters.iter().map(Light::power).collect()));
+        SceneLights { lights, emitters, selection }
+    }
+
+    // Luces que se muestrean todas, cada una con `sample_count` rayos de sombra
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    // Emisores a elegir por punto sombreado
+    pub fn emitter_picks(&self) -> u32 {
+        if self.emitters.is_empty() { 0 } else { EMITTER_SAMPLES }
+    }
+
+    // Emisor elegido con el número uniforme `u` y la probabilidad de haberlo elegido
+    pub fn pick_emitter(&self, u: f32) -> Option<(&Light, f32)> {
+        let (_, pdf, index) = self.selection.as_ref()?.sample(u);
+        let probability = pdf / self.emitters.len() as f32;
+        (probability > 0.0).then(|| (&self.emitters[index], probability))
+    }
+}
+
+// Punto de una esfera elegido con densidad uniforme en el cono de direcciones con
+// que se ve desde `from`, con la normal en ese punto y el ángulo sólido del cono.
+// None si `from` está dentro de la esfera
+fn sample_sphere_cone(center: &Vec3, radius: f32, from: &Vec3, rng: &mut (impl Rng + ?Sized)) -> Option<(Vec3, Vec3, f32)> {
+    let to_center = center - from;
+    let distance_squared = to_center.norm_squared();
+    if distance_squared <= radius * radius {
+        return None;
+    }
+    let distance = distance_squared.sqrt();
+    let axis = to_center / distance;
+    let cos_max = (1.0 - radius * radius / distance_squared).max(0.0).sqrt();
+
+    let cos_theta = 1.0 - rng.gen::<f32>() * (1.0 - cos_max);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.gen::<f32>();
+    let (tangent, bitangent) = orthonormal_basis(&axis);
+    let direction = tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + axis * cos_theta;
+
+    // Primer corte del rayo con la esfera; en el borde del cono la raíz se anula
+    let along = distance * cos_theta;
+    let t = along - (radius * radius - distance_squared * sin_theta * sin_theta).max(0.0).sqrt();
+    let point = from + direction * t;
+    let normal = (point - center).normalize();
+    Some((point, normal, 2.0 * PI * (1.0 - cos_max)))
+}
+
+// Factor del cono de un foco según el coseno entre su eje y el rayo de luz: 1 dentro
+// del cono interior, 0 fuera del exterior y una transición suave entre ambos
+fn spot_cone(cos_angle: f32, inner_angle: f32, outer_angle: f32) -> f32 {
+    let cos_inner = inner_angle.cos();
+    let cos_outer = outer_angle.cos();
+    if cos_inner - cos_outer <= 1e-6 {
+        return if cos_angle >= cos_outer { 1.0 } else { 0.0 };
+    }
+    let t = ((cos_angle - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Cube;
+    use crate::ray_intersect::RayIntersect;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    fn sample_point(light: &Light, from: &Vec3, rng: &mut StdRng) -> Vec3 {
+        let sample = light.sample(from, rng);
+        from + sample.direction * sample.distance
+    }
+
+    #[test]
+    fn test_samples_lie_on_the_light() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let white = Radiance::new(1.0, 1.0, 1.0);
+        let center = Vec3::new(1.0, 4.0, -2.0);
+        let from = Vec3::zeros();
+
+        let rectangle = Light {
+            shape: LightShape::Rectangle { u: Vec3::new(2.0, 0.0, 0.0), v: Vec3::new(0.0, 0.0, 1.0) },
+            samples: 8,
+            ..Light::new(center, white, 1.0)
+        };
+        let sphere = Light {
+            shape: LightShape::Sphere { radius: 0.5 },
+            samples: 8,
+            ..Light::new(center, white, 1.0)
+        };
+        for _ in 0..100 {
+            let p = sample_point(&rectangle, &from, &mut rng) - center;
+            assert!(p.x.abs() <= 1.0 + 1e-5 && p.y.abs() < 1e-5 && p.z.abs() <= 0.5 + 1e-5);
+            let q = sample_point(&sphere, &from, &mut rng) - center;
+            assert!((q.norm() - 0.5).abs() < 1e-4);
+        }
+        let point = Light::new(center, white, 1.0);
+        assert!((sample_point(&point, &from, &mut rng) - center).norm() < 1e-5);
+        assert_eq!(point.sample(&from, &mut rng).weight, 1.0);
+    }
+
+    #[test]
+    fn test_sphere_soft_shadow() {
+        // Esfera de radio 1 a 10 de altura y una losa de 2x2 a mitad de camino. Bajo
+        // el centro de la losa la luz queda tapada, a 2 del centro se ve una parte
+        // (penumbra) y a 5 se ve entera
+        let mut rng = StdRng::seed_from_u64(5);
+        let center = Vec3::new(0.0, 10.0, 0.0);
+        let sphere = Light {
+            shape: LightShape::Sphere { radius: 1.0 },
+            samples: 8,
+            ..Light::new(center, Radiance::new(1.0, 1.0, 1.0), 1.0)
+        };
+        let slab = Cube::new(Vec3::new(-1.0, 4.9, -1.0), Vec3::new(1.0, 5.1, 1.0), Material::black());
+        let visible = |x: f32, rng: &mut StdRng| {
+            let from = Vec3::new(x, 0.0, 0.0);
+            let lit = (0..1000)
+                .filter(|_| {
+                    let sample = sphere.sample(&from, rng);
+                    // Todas las muestras están en la cara que mira al punto
+                    let point = from + sample.direction * sample.distance;
+                    assert!((point - center).dot(&(from - center)) >= -1e-3);
+                    let hit = slab.ray_intersect(&from, &sample.direction);
+                    !(hit.is_intersecting && hit.distance < sample.distance)
+                })
+                .count();
+            lit as f32 / 1000.0
+        };
+        assert_eq!(visible(0.0, &mut rng), 0.0);
+        let penumbra = visible(2.0, &mut rng);
+        assert!(penumbra > 0.2 && penumbra < 0.8, "penumbra {}", penumbra);
+        assert_eq!(visible(5.0, &mut rng), 1.0);
+
+        // Como emisor, todas las muestras pesan el ángulo sólido de la esfera entre pi
+        let mut lamp = Material::black();
+        lamp.emissive_color = Some(Radiance::new(1.0, 1.0, 1.0));
+        lamp.emissive_intensity = 1.0;
+        let emitter = Light::emitter(center, LightShape::Sphere { radius: 1.0 }, &lamp).unwrap();
+        let expected = 2.0 * (1.0 - (1.0f32 - 1.0 / 100.0).sqrt());
+        for _ in 0..10 {
+            assert!((emitter.sample(&Vec3::zeros(), &mut rng).weight - expected).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_emitter_weight_falls_off_with_distance() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut lava = Material::black();
+        lava.emissive_color = Some(Radiance::new(1.0, 0.3, 0.0));
+        lava.emissive_intensity = 2.0;
+        let block = Light::emitter(Vec3::zeros(), LightShape::Box { size: Vec3::new(1.0, 1.0, 1.0) }, &lava).unwrap();
+        assert!(Light::emitter(Vec3::zeros(), LightShape::Point, &Material::black()).is_none());
+
+        let average_weight = |from: Vec3, rng: &mut StdRng| {
+            (0..2000).map(|_| block.sample(&from, rng).weight).sum::<f32>() / 2000.0
+        };
+        let near = average_weight(Vec3::new(0.0, 2.0, 0.0), &mut rng);
+        let far = average_weight(Vec3::new(0.0, 8.0, 0.0), &mut rng);
+        assert!(near > far * 4.0, "cerca {} lejos {}", near, far);
+
+        for _ in 0..100 {
+            let p = sample_point(&block, &Vec3::new(0.0, 2.0, 0.0), &mut rng);
+            assert!((p.abs().max() - 0.5).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_emitters_picked_by_power() {
+        let mut rng = StdRng::seed_from_u64(9);
+        let block = |x: f32, intensity: f32| {
+            let mut lava = Material::black();
+            lava.emissive_color = Some(Radiance::new(1.0, 1.0, 1.0));
+            lava.emissive_intensity = intensity;
+            Light::emitter(Vec3::new(x, 0.0, 0.0), LightShape::Box { size: Vec3::repeat(1.0) }, &lava).unwrap()
+        };
+        let bulb = Light::new(Vec3::new(0.0, 5.0, 0.0), Radiance::new(1.0, 1.0, 1.0), 1.0);
+        let lights = SceneLights::new(vec![block(0.0, 1.0), bulb, block(1.0, 3.0), block(2.0, 4.0)]);
+        assert_eq!(lights.lights().len(), 1);
+        assert_eq!(lights.emitter_picks(), EMITTER_SAMPLES);
+
+        let mut picked = [0usize; 3];
+        for _ in 0..8000 {
+            let (light, probability) = lights.pick_emitter(rng.gen()).unwrap();
+            assert!((probability - light.intensity / 8.0).abs() < 1e-5);
+            picked[light.position.x as usize] += 1;
+        }
+        for (count, intensity) in picked.iter().zip([1.0, 3.0, 4.0]) {
+            assert!((*count as f32 / 8000.0 - intensity / 8.0).abs() < 0.02, "{:?}", picked);
+        }
+
+        let none = SceneLights::new(vec![bulb]);
+        assert_eq!(none.emitter_picks(), 0);
+        assert!(none.pick_emitter(0.5).is_none());
+    }
+
+    #[test]
+    fn test_directional_light_has_no_distance() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let sun = Light::directional(Vec3::new(0.0, -2.0, 0.0), Radiance::new(1.0, 1.0, 1.0), 1.0);
+        for from in [Vec3::zeros(), Vec3::new(100.0, -50.0, 3.0)] {
+            let sample = sun.sample(&from, &mut rng);
+            assert_eq!(sample.direction, Vec3::new(0.0, 1.0, 0.0));
+            assert_eq!(sample.distance, f32::INFINITY);
+            assert_eq!(sample.weight, 1.0);
+        }
+    }
+
+    #[test]
+    fn test_point_falloff() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut bulb = Light::new(Vec3::zeros(), Radiance::new(1.0, 1.0, 1.0), 1.0);
+        bulb.falloff = Falloff::InverseSquare;
+        assert!((bulb.sample(&Vec3::new(2.0, 0.0, 0.0), &mut rng).weight - 0.25).abs() < 1e-6);
+        bulb.falloff = Falloff::Custom { constant: 1.0, linear: 0.5, quadratic: 0.0 };
+        assert!((bulb.sample(&Vec3::new(0.0, 0.0, 2.0), &mut rng).weight - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_spot_cone() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut spot = Light::spot(
+            Vec3::new(0.0, 4.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            20f32.to_radians(),
+            30f32.to_radians(),
+            Radiance::new(1.0, 1.0, 1.0),
+            1.0,
+        );
+        spot.falloff = Falloff::Constant;
+        let weight_at = |x: f32, rng: &mut StdRng| spot.sample(&Vec3::new(x, 0.0, 0.0), rng).weight;
+        assert_eq!(weight_at(0.0, &mut rng), 1.0);
+        // tan(25°) * 4 queda entre los dos conos
+        let penumbra = weight_at(25f32.to_radians().tan() * 4.0, &mut rng);
+        assert!(penumbra > 0.0 && penumbra < 1.0);
+        assert_eq!(weight_at(4.0, &mut rng), 0.0);
+        // Detrás del foco tampoco llega luz
+        assert_eq!(spot.sample(&Vec3::new(0.0, 8.0, 0.0), &mut rng).weight, 0.0);
+    }
+}
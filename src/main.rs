@@ -1,9 +1,14 @@
 use rayon::prelude::*;
-use minifb::{Key, Window, WindowOptions};
+use rand::Rng;
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
 use nalgebra_glm::{Vec3, normalize};
-use std::f32::INFINITY;
 use std::f32::consts::PI;
 use std::time::Instant;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use image::RgbImage;
 
 mod framebuffer;
 mod ray_intersect;
@@ -15,418 +20,242 @@ mod cube;
 mod materials;
 mod scene;
 mod textures;
+mod aabb;
+mod bvh;
+mod voxel;
+mod radiance;
+mod tonemap;
+mod scene_file;
+mod cli;
+mod accumulation;
+mod sampling;
+mod integrator;
+mod whitted;
+mod path_tracer;
+mod debug_views;
+mod sky;
+mod environment;
+mod mesh;
+mod obj;
+mod gltf;
+mod vox;
+mod nbt;
+mod schematic;
+mod transformed;
 
 // Now import from these modules
 use crate::framebuffer::Framebuffer;
-use crate::ray_intersect::{RayIntersect, Intersect};
 use crate::color::Color;
-use crate::materials::{TextureManager, Material};
 use crate::camera::Camera;
-use crate::light::Light;
-use crate::cube::Cube;
-use crate::scene::Scene;
-fn reflect(incident: &Vec3, normal: &Vec3) -> Vec3 {
-    incident - 2.0 * incident.dot(normal) * normal
+use crate::light::{Light, SceneLights};
+use crate::environment::Environment;
+use crate::radiance::Radiance;
+use crate::scene::DayNightCycle;
+use crate::tonemap::ToneMapper;
+use crate::scene_file::{load_scene, LoadedScene};
+use crate::cli::{parse_args, Options, USAGE};
+use crate::accumulation::AccumulationBuffer;
+use crate::integrator::{Integrator, RenderContext, RenderMode};
+
+// Muestras por píxel a partir de las cuales se deja de refinar la imagen
+const MAX_PROGRESSIVE_SAMPLES: u32 = 256;
+
+// Campo de visión vertical de la cámara
+const FOV: f32 = PI / 3.0;
+
+// Teclas numéricas de cada integrador, en el orden de `RenderMode::ALL`
+const MODE_KEYS: [Key; 7] = [Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5, Key::Key6, Key::Key7];
+
+// Ángulo aproximado que abarca cada píxel de una imagen de `height` filas
+fn pixel_spread(height: usize) -> f32 {
+    2.0 * (FOV / 2.0).tan() / height as f32
 }
 
-fn cast_shadow(
-    intersect: &Intersect,
-    light: &Light,
-    objects: &[Cube],
-) -> f32 {
-    let light_dir = (light.position - intersect.point).normalize();
-
-    // Ajusta el origen del rayo de sombra para evitar la autointersección
-    let offset = intersect.normal * 1e-4; // Pequeño valor para evitar estar dentro del cubo
-    let shadow_ray_origin = intersect.point + offset;
-
-    let mut shadow_intensity = 0.0;
-
-    for object in objects.iter() {
-        let shadow_intersect = object.ray_intersect(&shadow_ray_origin, &light_dir);
-        if shadow_intersect.is_intersecting {
-            shadow_intensity = 0.7;
-            break;
-        }
-    }
-
-    shadow_intensity
-}
-
-fn refract(incident: &Vec3, normal: &Vec3, eta_t: f32) -> Vec3 {
-    let cosi = -incident.dot(normal).max(-1.0).min(1.0);
-    
-    let (n_cosi, eta, n_normal);
-    
-    if cosi < 0.0 {
-        n_cosi = -cosi;
-        eta = 1.0 / eta_t;
-        n_normal = -normal;
-    } else {
-        // Ray is leaving the object
-        n_cosi = cosi;
-        eta = eta_t;
-        n_normal = *normal;
-    }
-    
-    let k = 1.0 - eta * eta * (1.0 - n_cosi * n_cosi);
-    
-    if k < 0.0 {
-        // Total internal reflection
-        reflect(incident, &n_normal)
-    } else {
-        eta * incident + (eta * n_cosi - k.sqrt( )) * n_normal
-    }
-}
-
-fn cast_ray(
-    ray_origin: &Vec3, 
-    ray_direction: &Vec3, 
-    objects: &[Cube], 
-    lights: &[Light], 
-    depth: u32,
-    texture_manager: &TextureManager
-) -> Color {
-    
-    if depth > 3 {
-        return Color::new(130, 189, 188); // Color de fondo si excedemos la profundidad máxima
-    }
-
-    let mut intersect = Intersect::empty();
-    let mut zbuffer = INFINITY; // El objeto más cercano golpeado por el rayo
-    
-    // Verificamos la intersección del rayo con los cubos
-    for object in objects {
-        let tmp = object.ray_intersect(ray_origin, ray_direction);
-        if tmp.is_intersecting && tmp.distance < zbuffer {
-            zbuffer = tmp.distance;
-            intersect = tmp;
-        }
-    }
-
-    if !intersect.is_intersecting {
-        return Color::new(130, 189, 188); // Color de fondo
-    }
-
-    let material = intersect.material;
-
-    // Si el material es emisivo, sumamos su emisión
-    let mut final_color = if material.is_emissive() {
-        material.get_emission() // Obtener la emisión del material
-    } else {
-        Color::black()
-    };
-
-    let view_dir = (ray_origin - intersect.point).normalize();
-
-    // Iteramos sobre todas las luces
-    for light in lights {
-        let light_dir = (light.position - intersect.point).normalize();
-        let reflect_dir = reflect(&-light_dir, &intersect.normal);
-
-        let shadow_intensity = cast_shadow(&intersect, light, objects);
-        let light_intensity = light.intensity * (1.0 - shadow_intensity);
-
-        // Componente difusa
-        let diffuse_intensity = intersect.normal.dot(&light_dir).max(0.0).min(1.0);
-        let diffuse_color = intersect.material.get_diffuse_color(intersect.u, intersect.v, texture_manager);
-        let diffuse = diffuse_color * intersect.material.albedo[0] * diffuse_intensity * light_intensity;
-
-        // Componente especular
-        let specular_intensity = view_dir.dot(&reflect_dir).max(0.0).powf(intersect.material.specular);
-        let specular = light.color * intersect.material.albedo[1] * specular_intensity * light_intensity;
-
-        // Sumar luz difusa y especular de esta luz al color final
-        final_color = final_color + diffuse + specular;
-    }
-
-    // Cálculo de reflexión
-    let mut reflect_color = Color::black();
-    let reflectivity = intersect.material.reflectivity;
-    let epsilon = 1e-3; // Pequeño desplazamiento para evitar "acné"
-
-    if reflectivity > 0.0 {
-        let reflect_dir = reflect(&-ray_direction, &intersect.normal).normalize();
-        let reflect_origin = intersect.point + intersect.normal * epsilon;
-        reflect_color = cast_ray(&reflect_origin, &reflect_dir, objects, lights, depth + 1, texture_manager);
-    }
-
-    // Cálculo de refracción
-    let mut refract_color = Color::black();
-    let transparency = intersect.material.transparency;
-
-    if transparency > 0.0 {
-        let refract_dir = refract(&ray_direction, &intersect.normal, intersect.material.refraction_index);
-        let refract_origin = intersect.point - intersect.normal * epsilon;
-        refract_color = cast_ray(&refract_origin, &refract_dir, objects, lights, depth + 1, texture_manager);
-    }
-
-    // Combinar resultados: color difuso + especular + reflexión + refracción
-    (final_color * (1.0 - reflectivity - transparency)) + (reflect_color * reflectivity) + (refract_color * transparency)
-}
-
-fn render(framebuffer: &mut Framebuffer, objects: &[Cube], camera: &Camera, 
-    texture_manager: &TextureManager, lights: &[Light], scene: &mut Scene, delta_time: f32) {
-    let width = framebuffer.width as f32;
-    let height = framebuffer.height as f32;
+// Traza un rayo por píxel con el integrador elegido y suma su radiancia HDR al
+// buffer de acumulación, sin tone mapping. La primera muestra pasa por la esquina del píxel como siempre; las
+// siguientes se desplazan al azar dentro del píxel para suavizar los bordes
+fn render(accumulation: &mut AccumulationBuffer, camera: &Camera, integrator: &dyn Integrator, context: &RenderContext) {
+    let row_length = accumulation.width;
+    let width = accumulation.width as f32;
+    let height = accumulation.height as f32;
+    let jitter = accumulation.samples() > 0;
     let aspect_ratio = width / height;
-    let fov = PI / 3.0;
-    let perspective_scale = (fov / 2.0).tan();
-    
-    update_lighting(scene,delta_time);
-    // Combinar la luz de la escena con las luces adicionales
-    let mut all_lights = Vec::with_capacity(lights.len() + 1);
-    all_lights.push(scene.light);       // Agregar la luz principal
-    all_lights.extend_from_slice(lights); // Agregar las luces adicionales
-    
-    let pixels: Vec<(usize, usize, Color)> = (0..framebuffer.height)
-        .into_par_iter() // Iteramos en paralelo sobre las filas
-        .flat_map(|y| {
-            let all_lights = all_lights.clone();
-            (0..framebuffer.width)
-                .into_par_iter() // Iteramos en paralelo sobre las columnas
-                .map(move |x| {
-                    let screen_x = (2.0 * x as f32) / width - 1.0;
-                    let screen_y = -(2.0 * y as f32) / height + 1.0;
+    let perspective_scale = (FOV / 2.0).tan();
+
+    accumulation
+        .sums_mut()
+        .par_chunks_mut(row_length) // Iteramos en paralelo sobre las filas
+        .enumerate()
+        .for_each(|(y, row)| {
+            row
+                .par_iter_mut() // Iteramos en paralelo sobre las columnas
+                .enumerate()
+                .for_each(|(x, pixel)| {
+                    let mut rng = rand::thread_rng();
+                    let (offset_x, offset_y) = if jitter {
+                        (rng.gen::<f32>(), rng.gen::<f32>())
+                    } else {
+                        (0.0, 0.0)
+                    };
+                    let screen_x = (2.0 * (x as f32 + offset_x)) / width - 1.0;
+                    let screen_y = -(2.0 * (y as f32 + offset_y)) / height + 1.0;
 
                     let screen_x = screen_x * aspect_ratio * perspective_scale;
                     let screen_y = screen_y * perspective_scale;
 
                     let ray_direction = normalize(&Vec3::new(screen_x, screen_y, -1.0));
                     let rotated_direction = camera.basis_change(&ray_direction);
-                    let pixel_color = cast_ray(&camera.eye, &rotated_direction, objects, &all_lights, 0, &texture_manager);
-
-                    (x, y, pixel_color)
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect();
+                    *pixel += integrator.radiance(&camera.eye, &rotated_direction, context, &mut rng);
+                });
+        });
+    accumulation.finish_pass();
+}
 
-    for (x, y, color) in pixels {
-        framebuffer.set_current_color(color);
-        framebuffer.point(x as f32, y as f32);
+// Etapa de tone mapping: lleva la radiancia HDR al framebuffer. Solo aquí se
+// cuantiza a sRGB de 8 bits, así que cambiar la exposición no requiere volver a trazar
+fn present(framebuffer: &mut Framebuffer, accumulation: &AccumulationBuffer, tone_mapper: &ToneMapper) {
+    let width = framebuffer.width;
+    for index in 0..framebuffer.width * framebuffer.height {
+        framebuffer.set_current_color(tone_mapper.apply(accumulation.average(index)).to_srgb());
+        framebuffer.point((index % width) as f32, (index / width) as f32);
     }
 }
 
 fn calculate_delta_time(last_update: Instant) -> f32 {
     let now = Instant::now();
     let duration = now.duration_since(last_update);
-    let delta_time = duration.as_secs_f32();
-    delta_time
+    duration.as_secs_f32()
 }
 
-fn update_lighting(scene: &mut Scene, delta_time: f32) {
-    // Incrementamos el tiempo en la escena
-    scene.time_of_day += delta_time;
-
-    // Normalizamos el tiempo entre 0 y 1, donde 0 es medianoche y 1 es la próxima medianoche
-    let normalized_time = (scene.time_of_day % scene.cycle_duration) / scene.cycle_duration;
-
-    // Ajustar la posición de la luz para simular el movimiento del sol
-    let angle = normalized_time * 2.0 * PI; // Ángulo del ciclo (0 a 2pi)
-    let light_radius = 100.0; // Distancia del "sol" o luz de la escena
-
-    // La posición de la luz se moverá en un arco de 180 grados
-    scene.light.position = Vec3::new(
-        light_radius * angle.cos(),
-        light_radius * angle.sin(),
-        50.0 // Altura fija de la luz
-    );
-
-    // Cambiar el color de la luz según la hora del día
-    scene.light.color = if normalized_time < 0.5 {
-        // Amanecer o mediodía: luz más brillante (blanca)
-        Color::new(255, 255, 224) // Luz cálida y brillante
-    } else {
-        // Atardecer o noche: luz más tenue y anaranjada
-        Color::new(255, 140, 0) // Luz naranja
+// Sol y luna del ciclo día/noche seguidos de las luces de la escena. Un mapa de
+// entorno ya trae la luz del cielo, así que con él el ciclo no agrega astros
+fn scene_lights(cycle: &DayNightCycle, environment: Option<&Environment>, lights: &[Light]) -> SceneLights {
+    let mut all_lights = match environment {
+        Some(_) => Vec::new(),
+        None => cycle.lights(), // Agregar el sol y la luna
     };
+    all_lights.extend_from_slice(lights); // Agregar las luces adicionales
+    SceneLights::new(all_lights)
+}
 
-    // Ajustamos la intensidad de la luz: más fuerte durante el día, más tenue en la noche
-    scene.light.intensity = if normalized_time < 0.5 {
-        1.0 // Plena luz del día
-    } else {
-        0.2 // Luz tenue al atardecer y noche
-    };
+// Luz ambiente del ciclo o, con un mapa de entorno, la que sale de él
+fn ambient_light(cycle: &DayNightCycle, environment: Option<&Environment>) -> Radiance {
+    match environment {
+        Some(environment) => environment.ambient(),
+        None => cycle.ambient_light(),
+    }
 }
 
+// Modo sin ventana: renderiza uno o varios cuadros y los guarda como PNG
+fn run_headless(options: &Options, loaded: &mut LoadedScene) -> Result<(), String> {
+    let mut framebuffer = Framebuffer::new(options.width, options.height);
+    let mut accumulation = AccumulationBuffer::new(options.width, options.height);
+    let tone_mapper = ToneMapper::new(options.tone_map, options.exposure_ev);
+    let integrator = options.mode.integrator();
+    let cycle = &mut loaded.cycle;
+    let start_time = cycle.time;
+
+    for frame in options.frames.0..options.frames.1 {
+        let time = start_time + (frame - options.frames.0) as f32 * options.time_step;
+        cycle.time = time.rem_euclid(1.0);
+
+        let lights = scene_lights(cycle, loaded.environment.as_ref(), &loaded.lights);
+        let sky = cycle.sky();
+        let context = RenderContext {
+            objects: &loaded.objects,
+            lights: &lights,
+            texture_manager: &loaded.texture_manager,
+            sky: &sky,
+            environment: loaded.environment.as_ref(),
+            ambient: ambient_light(cycle, loaded.environment.as_ref()),
+            pixel_spread: pixel_spread(options.height),
+        };
+        accumulation.reset();
+        for _ in 0..options.samples {
+            render(&mut accumulation, &loaded.camera, integrator.as_ref(), &context);
+        }
+        present(&mut framebuffer, &accumulation, &tone_mapper);
 
+        let path = options.output_path(frame);
+        if let Some(parent) = Path::new(&path).parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+            }
+        }
+        let image = RgbImage::from_raw(framebuffer.width as u32, framebuffer.height as u32, framebuffer.data.clone())
+            .ok_or_else(|| "el framebuffer no coincide con la resolución".to_string())?;
+        image.save(&path).map_err(|e| format!("{}: {}", path, e))?;
+        println!("Cuadro {} guardado en {} (hora del día {:.3}, {})", frame, path, cycle.time, cycle.clock());
+    }
+    Ok(())
+}
 
 fn main() {
-    let mut texture_manager = TextureManager::new();
-    let wood_texture_index = texture_manager.load_texture("assets/wood.png");
-    let leaf_texture_index = texture_manager.load_texture("assets/leaves.png");
-    let grass_texture_index = texture_manager.load_texture("assets/grass.jpg");
-    let stone_texture_index = texture_manager.load_texture("assets/stone.png");
-    let brick_texture_index = texture_manager.load_texture("assets/brick.jpg");
-
-    let soil_material = Material::new_with_texture(
-        grass_texture_index,
-        50.0,
-        [0.6, 0.3],
-        0.6,
-    );
-    let water_material = Material::new(
-        Color::new(115, 136, 255),
-        50.0,
-        [0.6, 0.3],
-        0.8,
-        0.7,
-        0.6
-    );
-
-
-    let wood_material = Material::new_with_texture(
-        wood_texture_index,
-        50.0,
-        [0.6, 0.3],
-        0.6,
-    );
-    let leaf_material = Material::new_with_texture(
-        leaf_texture_index,
-        50.0,
-        [0.6, 0.3],
-        0.6,
-    );
-
-    let stone_material = Material::new_with_texture(
-        stone_texture_index,
-        50.0,
-        [0.6, 0.3],
-        0.6,
-    );
-
-    let objects = [
-         // Terreno base más amplio
-         Cube {
-            min: Vec3::new(-5.0, -1.0, -5.0),
-            max: Vec3::new(5.0, -0.5, 5.0),
-            material: soil_material,
-        },
-        
-        // Charco de agua y borde
-        Cube {
-            min: Vec3::new(-2.0, -0.6, -1.0),
-            max: Vec3::new(2.5, -0.45, -4.0),
-            material: water_material,
-        },
-        // Borde del charco
-        Cube {
-            min: Vec3::new(-4.2, -0.7, -3.2),
-            max: Vec3::new(-2.3, -0.5, -1.8),
-            material: stone_material,
-        },
-        
-        // Árbol central - tronco más alto
-        Cube {
-            min: Vec3::new(-0.5, -0.5, -0.5),
-            max: Vec3::new(0.5, 2.5, 0.5),
-            material: wood_material,
-        },
-        // Copa del árbol central - más grande y alta
-        Cube {
-            min: Vec3::new(-1.5, 2.0, -1.5),
-            max: Vec3::new(1.5, 3.5, 1.5),
-            material: leaf_material,
-        },
-        Cube {
-            min: Vec3::new(-1.0, 3.5, -1.0),
-            max: Vec3::new(1.0, 4.5, 1.0),
-            material: leaf_material,
-        },
-        // Cube {
-        //     min: Vec3::new(-0.8, 4.5, -0.8),
-        //     max: Vec3::new(0.8, 5.0, 0.8),
-        //     material: leaf_material,
-        // },
-
-        // Árbol izquierdo
-        Cube {
-            min: Vec3::new(-3.5, -0.5, -2.0),
-            max: Vec3::new(-3.0, 2.0, -1.5),
-            material: wood_material,
-        },
-        Cube {
-            min: Vec3::new(-4.0, 2.0, -2.5),
-            max: Vec3::new(-2.5, 3.0, -1.0),
-            material: leaf_material,
-        },
-
-        // Árbol derecho
-        Cube {
-            min: Vec3::new(3.0, -0.5, -1.0),
-            max: Vec3::new(3.5, 2.0, -0.5),
-            material: wood_material,
-        },
-        Cube {
-            min: Vec3::new(2.5, 2.0, -1.5),
-            max: Vec3::new(4.0, 3.0, 0.0),
-            material: leaf_material,
-        },
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    if options.help {
+        print!("{}", USAGE);
+        return;
+    }
 
-        // Árbol fondo
-        Cube {
-            min: Vec3::new(-1.0, -0.5, 3.0),
-            max: Vec3::new(-0.5, 2.0, 3.5),
-            material: wood_material,
-        },
-        Cube {
-            min: Vec3::new(-1.5, 2.0, 2.5),
-            max: Vec3::new(0.0, 3.0, 4.0),
-            material: leaf_material,
-        },
-        
-        // Árbol fondo derecha
-        Cube {
-            min: Vec3::new(2.0, -0.5, 2.5),
-            max: Vec3::new(2.5, 2.0, 3.0),
-            material: wood_material,
-        },
-        Cube {
-            min: Vec3::new(1.5, 2.0, 2.0),
-            max: Vec3::new(3.0, 3.0, 3.5),
-            material: leaf_material,
-        },
-        
-        // Árbol fondo izquierda
-        Cube {
-            min: Vec3::new(-2.5, -0.5, 2.0),
-            max: Vec3::new(-2.0, 2.0, 2.5),
-            material: wood_material,
-        },
-        Cube {
-            min: Vec3::new(-3.0, 2.0, 1.5),
-            max: Vec3::new(-1.5, 3.0, 3.0),
-            material: leaf_material,
-        },
-    ];
+    // La escena se describe en un archivo TOML; por defecto se carga el bosque
+    let mut loaded = match load_scene(&options.scene_path) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("Error al cargar la escena: {}", e);
+            process::exit(1);
+        }
+    };
+    for warning in &loaded.warnings {
+        eprintln!("Aviso: {}", warning);
+    }
 
-    let mut camera = Camera::new(
-        Vec3::new(10.0, 8.0, -10.0),
-        Vec3::new(0.0, 0.0, 0.0),
-        Vec3::new(0.0, 1.0, 0.0),
-    );
+    // La línea de comandos puede reemplazar la cámara y la hora del día de la escena
+    if options.eye.is_some() || options.center.is_some() {
+        loaded.camera = Camera::new(
+            options.eye.unwrap_or(loaded.camera.eye),
+            options.center.unwrap_or(loaded.camera.center),
+            loaded.camera.up,
+        );
+    }
+    if let Some(time) = options.time {
+        loaded.cycle.time = time.rem_euclid(1.0);
+    }
 
-    let light = Light::new(
-        Vec3::new(-2.0, 3.0, -5.0),
-        Color::new(0, 0, 255),
-        0.5,
-    );
-    let light1 = Light {
-        position: Vec3::new(10.0, 10.0, -10.0),
-        color: Color::new(0, 255, 0),
-        intensity: 0.3,
-    };
-    let light2 = Light {
-        position: Vec3::new(-10.0, 15.0, 10.0),
-        color: Color::new(255, 0, 0), // Luz roja
-        intensity: 0.4,
-    };
-    let lights = vec![light,light1,light2];
+    if options.headless {
+        if let Err(e) = run_headless(&options, &mut loaded) {
+            eprintln!("Error al guardar la imagen: {}", e);
+            process::exit(1);
+        }
+    } else {
+        run_window(&options, loaded);
+    }
+}
 
-    let mut framebuffer = Framebuffer::new(800, 600);
+fn run_window(options: &Options, loaded: LoadedScene) {
+    let LoadedScene {
+        texture_manager,
+        objects,
+        lights,
+        mut camera,
+        mut cycle,
+        environment,
+        ..
+    } = loaded;
+
+    let mut framebuffer = Framebuffer::new(options.width, options.height);
+    let mut accumulation = AccumulationBuffer::new(options.width, options.height);
+    let mut all_lights = scene_lights(&cycle, environment.as_ref(), &lights);
+    let mut sky = cycle.sky();
+    let mut tone_mapper = ToneMapper::new(options.tone_map, options.exposure_ev);
+    let mut mode = options.mode;
+    let mut integrator = mode.integrator();
     
-    let mut scene = Scene::new(10.0);     // Crear la escena
     let mut last_update = Instant::now(); // Para calcular el delta_time
 
     let mut window = Window::new(
@@ -440,6 +269,7 @@ fn main() {
 
     let rotation_speed = PI/50.0;
     let zoom_speed = 0.1;
+    let exposure_step = 0.25;
     framebuffer.clear();
     framebuffer.set_background_color(Color::new(25, 20, 2));
 
@@ -464,13 +294,107 @@ fn main() {
         if window.is_key_down(Key::E) {
             camera.zoom(-zoom_speed);
         }
-        if camera.is_changed() {
-            // Calcular el delta_time
-            let delta_time = calculate_delta_time(last_update);
-            last_update = Instant::now();
-            
-            render(&mut framebuffer, &objects, &camera, &texture_manager, &lights, &mut scene, delta_time);
+        // Contadores de la BVH desde la última consulta, frente al recorrido lineal
+        if window.is_key_pressed(Key::B, KeyRepeat::No) {
+            if cfg!(feature = "bvh-stats") {
+                let counts = objects.counts();
+                println!(
+                    "BVH ({} nodos): {} rayos, {} nodos visitados, {} pruebas rayo-caja, {} pruebas de primitiva (recorrido lineal: {})",
+                    objects.node_count(),
+                    counts.rays,
+                    counts.nodes_visited,
+                    counts.box_tests,
+                    counts.primitive_tests,
+                    counts.linear_primitive_tests(objects.len()),
+                );
+                objects.reset_counts();
+            } else {
+                println!("Los contadores de la BVH necesitan compilar con --features bvh-stats");
+            }
+        }
+        // Exposición en pasos (-/=) y operador de tone mapping (T)
+        let mut tone_changed = false;
+        if window.is_key_pressed(Key::Minus, KeyRepeat::Yes) {
+            tone_mapper.adjust_exposure(-exposure_step);
+            tone_changed = true;
+        }
+        if window.is_key_pressed(Key::Equal, KeyRepeat::Yes) {
+            tone_mapper.adjust_exposure(exposure_step);
+            tone_changed = true;
+        }
+        if window.is_key_pressed(Key::T, KeyRepeat::No) {
+            tone_mapper.operator = tone_mapper.operator.next();
+            tone_changed = true;
+        }
+        // Teclas 1 a 7: integrador (Whitted, path tracing y vistas de depuración).
+        // Lo acumulado con el integrador anterior deja de servir
+        for (key, key_mode) in MODE_KEYS.iter().zip(RenderMode::ALL) {
+            if key_mode != mode && window.is_key_pressed(*key, KeyRepeat::No) {
+                mode = key_mode;
+                integrator = mode.integrator();
+                accumulation.reset();
+            }
+        }
+
+        // Ciclo día/noche: pausa (espacio), velocidad ([ y ]) y recorrido a mano (, y .).
+        // La luz se renueva cuando el sol se movió lo bastante o al recorrer la hora
+        let delta_time = calculate_delta_time(last_update);
+        last_update = Instant::now();
+        let mut time_changed = cycle.advance(delta_time);
+        if window.is_key_pressed(Key::Space, KeyRepeat::No) {
+            cycle.toggle_pause();
+        }
+        if window.is_key_pressed(Key::LeftBracket, KeyRepeat::No) {
+            cycle.slow_down();
+        }
+        if window.is_key_pressed(Key::RightBracket, KeyRepeat::No) {
+            cycle.speed_up();
+        }
+        if window.is_key_pressed(Key::Comma, KeyRepeat::Yes) {
+            cycle.scrub(-1.0);
+            time_changed = true;
+        }
+        if window.is_key_pressed(Key::Period, KeyRepeat::Yes) {
+            cycle.scrub(1.0);
+            time_changed = true;
+        }
+        if time_changed {
+            all_lights = scene_lights(&cycle, environment.as_ref(), &lights);
+            sky = cycle.sky();
+        }
+        let context = RenderContext {
+            objects: &objects,
+            lights: &all_lights,
+            texture_manager: &texture_manager,
+            sky: &sky,
+            environment: environment.as_ref(),
+            ambient: ambient_light(&cycle, environment.as_ref()),
+            pixel_spread: pixel_spread(options.height),
+        };
+
+        if camera.is_changed() || time_changed {
+            // Con la cámara en movimiento o la luz cambiando se descarta lo acumulado
+            // y se traza una sola muestra
+            accumulation.reset();
+            render(&mut accumulation, &camera, integrator.as_ref(), &context);
+            present(&mut framebuffer, &accumulation, &tone_mapper);
+        } else if accumulation.samples() < MAX_PROGRESSIVE_SAMPLES {
+            // Con la cámara quieta cada cuadro agrega una muestra desplazada por píxel
+            // (también la primera tras cambiar de integrador)
+            render(&mut accumulation, &camera, integrator.as_ref(), &context);
+            present(&mut framebuffer, &accumulation, &tone_mapper);
+        } else if tone_changed {
+            present(&mut framebuffer, &accumulation, &tone_mapper);
         }
+        window.set_title(&format!(
+            "Raytracing - {} - {}, {} muestras - {} (x{}{})",
+            mode,
+            tone_mapper,
+            accumulation.samples(),
+            cycle.clock(),
+            cycle.speed,
+            if cycle.paused { ", en pausa" } else { "" },
+        ));
 
         // Actualiza la ventana con el buffer
         window.update_with_buffer(&framebuffer.to_u32_buffer(), framebuffer.width, framebuffer.height)
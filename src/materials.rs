@@ -1,5 +1,7 @@
-use crate::textures::Texture;
-use crate::color::Color;
+use crate::textures::{EnvironmentMap, Sampler, Texture, WrapMode};
+use image::{ImageError, ImageResult};
+use crate::radiance::Radiance;
+use nalgebra_glm::Vec3;
 use std::sync::Arc;
 
 // Structure that contains textures
@@ -15,10 +17,47 @@ impl TextureManager {
     }
 
     // Add a texture to the container and return the index
-    pub fn load_texture(&mut self, path: &str) -> usize {
-        let texture = Arc::new(Texture::new(path));
+    pub fn load_texture(&mut self, path: &str) -> ImageResult<usize> {
+        let texture = Arc::new(Texture::new(path)?);
         self.textures.push(texture);
-        self.textures.len() - 1 // Returns the texture index
+        Ok(self.textures.len() - 1) // Returns the texture index
+    }
+
+    // Add an already loaded texture, such as a tile cut from an atlas
+    pub fn add_texture(&mut self, texture: Texture) -> usize {
+        self.textures.push(Arc::new(texture));
+        self.textures.len() - 1
+    }
+
+    // Add a tangent-space normal map and return its index. Normal maps are loaded
+    // without the sRGB conversion, so a texture used both ways is loaded twice
+    pub fn load_normal_map(&mut self, path: &str) -> ImageResult<usize> {
+        let texture = Arc::new(Texture::new_linear(path)?);
+        self.textures.push(texture);
+        Ok(self.textures.len() - 1)
+    }
+
+    // Load a latitude-longitude environment image, such as a Radiance .hdr file
+    pub fn load_equirectangular(&mut self, path: &str) -> ImageResult<EnvironmentMap> {
+        let index = self.load_texture(path)?;
+        Ok(EnvironmentMap::Equirectangular(self.textures[index].clone()))
+    }
+
+    // Load the six faces of a cubemap in the order +x, -x, +y, -y, +z, -z. All faces
+    // must be square and the same size, or the edges between them would not line up
+    pub fn load_cubemap(&mut self, paths: &[String; 6]) -> Result<EnvironmentMap, CubemapError> {
+        let mut faces: Vec<Arc<Texture>> = Vec::with_capacity(6);
+        for (face, path) in paths.iter().enumerate() {
+            let index = self.load_texture(path).map_err(|error| CubemapError::Image { face, error })?;
+            let texture = self.textures[index].clone();
+            let expected = faces.first().map_or(texture.width, |first| first.width);
+            if texture.width != texture.height || texture.width != expected {
+                return Err(CubemapError::Size { face, width: texture.width, height: texture.height, expected });
+            }
+            faces.push(texture);
+        }
+        let faces: [Arc<Texture>; 6] = faces.try_into().expect("a cubemap has six faces");
+        Ok(EnvironmentMap::Cubemap(faces))
     }
 
     // Get a reference to the texture by index
@@ -27,23 +66,54 @@ impl TextureManager {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+// Why a cubemap could not be loaded; `face` counts from 0 in the order +x, -x, +y, -y, +z, -z
+#[derive(Debug)]
+pub enum CubemapError {
+    Image { face: usize, error: ImageError },
+    // The face is not square, or not `expected` texels wide like the first one
+    Size { face: usize, width: usize, height: usize, expected: usize },
+}
+
+// Alpha test: texels more transparent than `threshold` are holes that rays go
+// through, both when looking for the closest hit and for shadow rays
+#[derive(Debug, Clone)]
+pub struct AlphaCutout {
+    pub texture: Arc<Texture>, // Shared with the TextureManager, for the test done while intersecting rays
+    pub threshold: f32,
+}
+
+impl AlphaCutout {
+    pub fn is_hole(&self, u: f32, v: f32, wrap: WrapMode) -> bool {
+        let (x, y) = self.texture.nearest_texel(u, 1.0 - v, wrap);
+        self.texture.get_alpha(x, y) < self.threshold
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Material {
-    pub diffuse: Color,
+    pub diffuse: Radiance,
     pub specular: f32,
     pub albedo: [f32; 2],
     pub reflectivity: f32,
     pub transparency: f32,
     pub refraction_index: f32,
     pub texture_index: Option<usize>, // Index of texture in TextureManager
-    pub emissive_color: Option<Color>,
+    pub texture_tint: Radiance,       // Color the texture is multiplied by
+    pub normal_map: Option<usize>,    // Index of a tangent-space normal map in TextureManager
+    pub alpha_cutout: Option<AlphaCutout>,
+    pub sampler: Sampler, // Filtering and wrap mode of the texture and normal map
+    pub texture_scale: [f32; 2],  // Repetitions of the texture along u and v
+    pub texture_offset: [f32; 2], // Shift of the texture, in repetitions
+    pub tile_size: Option<f32>,   // World units per repetition; None stretches the texture over each face
+    pub emissive_color: Option<Radiance>,
     pub emissive_intensity: f32,
+    pub id: usize, // Position in the scene's material table, shown by the material-index view
 }
 
 impl Material {
     // Constructor for materials without texture
     pub fn new(
-        diffuse: Color, 
+        diffuse: Radiance, 
         specular: f32, 
         albedo: [f32; 2],
         reflectivity: f32,
@@ -58,8 +128,16 @@ impl Material {
             transparency, 
             refraction_index,
             texture_index: None,
+            texture_tint: Radiance::new(1.0, 1.0, 1.0),
+            normal_map: None,
+            alpha_cutout: None,
+            sampler: Sampler::default(),
+            texture_scale: [1.0, 1.0],
+            texture_offset: [0.0, 0.0],
+            tile_size: None,
             emissive_color: None,
             emissive_intensity: 0.0,
+            id: 0,
         }
     }
 
@@ -71,27 +149,36 @@ impl Material {
         refraction_index: f32,
     ) -> Self {
         Material {
-            diffuse: Color::new(0, 0, 0),
+            diffuse: Radiance::black(),
             specular,
             albedo,
             reflectivity: 0.0,
             transparency: 0.0,
             refraction_index,
             texture_index: Some(texture_index),
+            texture_tint: Radiance::new(1.0, 1.0, 1.0),
+            normal_map: None,
+            alpha_cutout: None,
+            sampler: Sampler::default(),
+            texture_scale: [1.0, 1.0],
+            texture_offset: [0.0, 0.0],
+            tile_size: None,
             emissive_color: None,
             emissive_intensity: 0.0,
+            id: 0,
         }
     }
 
     // Constructor with emissive color
+    #[allow(dead_code, clippy::too_many_arguments)]
     pub fn new_with_emission(
-        diffuse: Color,
+        diffuse: Radiance,
         specular: f32,
         albedo: [f32; 2],
         reflectivity: f32,
         transparency: f32,
         refraction_index: f32,
-        emissive_color: Option<Color>,
+        emissive_color: Option<Radiance>,
         emissive_intensity: f32,
     ) -> Self {
         Material {
@@ -102,45 +189,98 @@ impl Material {
             transparency,
             refraction_index,
             texture_index: None,
+            texture_tint: Radiance::new(1.0, 1.0, 1.0),
+            normal_map: None,
+            alpha_cutout: None,
+            sampler: Sampler::default(),
+            texture_scale: [1.0, 1.0],
+            texture_offset: [0.0, 0.0],
+            tile_size: None,
             emissive_color,
             emissive_intensity,
+            id: 0,
         }
     }
 
+    // Texture coordinates of a surface point. (u, v) go from 0 to 1 across the face
+    // and (world_u, world_v) measure the same directions in world units, so that
+    // with a tile size neighbouring faces continue the same pattern
+    pub fn texture_uv(&self, u: f32, v: f32, world_u: f32, world_v: f32) -> (f32, f32) {
+        let (u, v) = match self.tile_size {
+            Some(size) => (world_u / size, world_v / size),
+            None => (u, v),
+        };
+        (
+            u * self.texture_scale[0] + self.texture_offset[0],
+            v * self.texture_scale[1] + self.texture_offset[1],
+        )
+    }
+
+    // World length covered by one unit of the coordinates given by texture_uv, for a
+    // face where one unit of (u, v) covers `uv_extent`
+    pub fn texture_uv_extent(&self, uv_extent: f32) -> f32 {
+        let extent = self.tile_size.unwrap_or(uv_extent);
+        extent / (self.texture_scale[0] * self.texture_scale[1]).abs().sqrt()
+    }
+
+    // Whether (u, v) falls in a hole of the alpha cutout
+    pub fn is_cut(&self, u: f32, v: f32) -> bool {
+        self.alpha_cutout.as_ref().is_some_and(|cutout| cutout.is_hole(u, v, self.sampler.wrap))
+    }
+
     pub fn is_emissive(&self) -> bool {
         self.emissive_intensity > 0.0
     }
 
-    pub fn get_emission(&self) -> Color {
+    pub fn get_emission(&self) -> Radiance {
         if let Some(color) = self.emissive_color {
             color * self.emissive_intensity
         } else {
-            Color::black()
+            Radiance::black()
         }
     }
 
-    pub fn get_diffuse_color(&self, u: f32, v: f32, texture_manager: &TextureManager) -> Color {
+    // `footprint` is the width in uv units of the area seen by the sample (see
+    // Sampler::sample); textures have v = 0 at the bottom of the image
+    pub fn get_diffuse_color(&self, u: f32, v: f32, footprint: f32, texture_manager: &TextureManager) -> Radiance {
         if let Some(texture_index) = self.texture_index {
-            let texture = texture_manager.get_texture(texture_index);
-            let x = (u * (texture.width as f32 - 1.0)) as usize;
-            let y = ((1.0 - v) * (texture.height as f32 - 1.0)) as usize;
-            texture.get_color(x, y)
+            self.sampler.sample(texture_manager.get_texture(texture_index), u, 1.0 - v, footprint) * self.texture_tint
         } else {
             self.diffuse
         }
     }
 
+    // Normal in tangent space (x along u, y along v, z out of the surface) read
+    // from the normal map, or None if the material has no normal map
+    pub fn get_tangent_normal(&self, u: f32, v: f32, texture_manager: &TextureManager) -> Option<Vec3> {
+        let texture_index = self.normal_map?;
+        let encoded = self.sampler.sample(texture_manager.get_texture(texture_index), u, 1.0 - v, 0.0);
+        let normal = Vec3::new(encoded.r * 2.0 - 1.0, encoded.g * 2.0 - 1.0, encoded.b * 2.0 - 1.0);
+        if normal.z <= 0.0 {
+            return None;
+        }
+        Some(normal.normalize())
+    }
+
     pub fn black() -> Self {
         Material {
-            diffuse: Color::new(0, 0, 0),
+            diffuse: Radiance::black(),
             specular: 0.0,
             albedo: [0.0, 0.0],
             reflectivity: 0.0,
             transparency: 0.0,
             refraction_index: 0.0,
             texture_index: None,
+            texture_tint: Radiance::new(1.0, 1.0, 1.0),
+            normal_map: None,
+            alpha_cutout: None,
+            sampler: Sampler::default(),
+            texture_scale: [1.0, 1.0],
+            texture_offset: [0.0, 0.0],
+            tile_size: None,
             emissive_color: None,
             emissive_intensity: 0.0,
+            id: 0,
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,319 @@
+use nalgebra_glm::Vec3;
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::light::{Light, LightShape};
+use crate::materials::Material;
+use crate::ray_intersect::{Intersect, RayIntersect};
+
+// Distancia mínima de un golpe, para que un rayo que sale de un triángulo no lo vuelva a golpear
+const EPSILON: f32 = 1e-6;
+
+// Triángulo de una malla. Las normales y coordenadas de textura de los vértices son
+// opcionales: sin normales se sombrea plano y sin coordenadas u y v son las
+// baricéntricas
+pub struct Triangle {
+    pub vertices: [Vec3; 3],
+    pub normals: Option<[Vec3; 3]>,
+    pub uvs: Option<[[f32; 2]; 3]>,
+    pub material: usize, // Índice en la tabla de materiales de la malla
+}
+
+impl Triangle {
+    // Coordenadas de textura de los vértices, con las baricéntricas por defecto
+    fn vertex_uvs(&self) -> [[f32; 2]; 3] {
+        self.uvs.unwrap_or([[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]])
+    }
+
+    // Direcciones en que crecen u y v sobre el plano del triángulo, sin normalizar
+    fn uv_gradients(&self) -> Option<(Vec3, Vec3)> {
+        let [uv0, uv1, uv2] = self.vertex_uvs();
+        let (du1, dv1) = (uv1[0] - uv0[0], uv1[1] - uv0[1]);
+        let (du2, dv2) = (uv2[0] - uv0[0], uv2[1] - uv0[1]);
+        let determinant = du1 * dv2 - du2 * dv1;
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+        let edge1 = self.vertices[1] - self.vertices[0];
+        let edge2 = self.vertices[2] - self.vertices[0];
+        let tangent = (edge1 * dv2 - edge2 * dv1) / determinant;
+        let bitangent = (edge2 * du1 - edge1 * du2) / determinant;
+        Some((tangent, bitangent))
+    }
+
+    // Möller–Trumbore: resuelve el punto del rayo como combinación baricéntrica de
+    // los vértices sin calcular antes el plano del triángulo. Da la distancia y las
+    // coordenadas baricéntricas del golpe
+    fn hit(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Option<(f32, [f32; 3])> {
+        let [v0, v1, v2] = self.vertices;
+        let edge1 = v1 - v0;
+        let edge2 = v2 - v0;
+        let p = ray_direction.cross(&edge2);
+        let determinant = edge1.dot(&p);
+        // El rayo es paralelo al plano del triángulo
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+        let inv_determinant = 1.0 / determinant;
+        let s = ray_origin - v0;
+        let b1 = s.dot(&p) * inv_determinant;
+        if !(0.0..=1.0).contains(&b1) {
+            return None;
+        }
+        let q = s.cross(&edge1);
+        let b2 = ray_direction.dot(&q) * inv_determinant;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(&q) * inv_determinant;
+        if t <= EPSILON {
+            return None;
+        }
+        Some((t, [1.0 - b1 - b2, b1, b2]))
+    }
+}
+
+// Triángulo listo para trazar: lo que no depende del punto golpeado se calcula una
+// vez al armar la malla y no en cada candidato a golpe más cercano
+struct MeshTriangle {
+    triangle: Triangle,
+    normal: Vec3,                        // Normal geométrica unitaria
+    uv_extent: f32,                      // Longitud en el mundo de una unidad de uv
+    uv_gradients: Option<(Vec3, Vec3)>,  // Direcciones de u y v, para el marco tangente
+}
+
+impl MeshTriangle {
+    fn new(triangle: Triangle) -> Self {
+        let [v0, v1, v2] = triangle.vertices;
+        let cross = (v1 - v0).cross(&(v2 - v0));
+        let area = cross.norm();
+        // Una unidad de uv cubre en el mundo la raíz de la razón entre las áreas
+        let [uv0, uv1, uv2] = triangle.vertex_uvs();
+        let uv_area = ((uv1[0] - uv0[0]) * (uv2[1] - uv0[1]) - (uv2[0] - uv0[0]) * (uv1[1] - uv0[1])).abs();
+        let uv_extent = if uv_area > 1e-12 { (area / uv_area).sqrt() } else { 1.0 };
+        let normal = if area > 0.0 { cross / area } else { Vec3::zeros() };
+        let uv_gradients = triangle.uv_gradients();
+        MeshTriangle { triangle, normal, uv_extent, uv_gradients }
+    }
+
+    // Coordenadas de textura del material en el punto con las baricéntricas dadas
+    fn texture_uv(&self, material: &Material, barycentric: [f32; 3]) -> (f32, f32) {
+        let [b0, b1, b2] = barycentric;
+        let [uv0, uv1, uv2] = self.triangle.vertex_uvs();
+        let u = b0 * uv0[0] + b1 * uv1[0] + b2 * uv2[0];
+        let v = b0 * uv0[1] + b1 * uv1[1] + b2 * uv2[1];
+        material.texture_uv(u, v, u * self.uv_extent, v * self.uv_extent)
+    }
+
+    // Intersección completa del golpe más cercano
+    fn intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3, t: f32, barycentric: [f32; 3], material: &Material) -> Intersect {
+        let [b0, b1, b2] = barycentric;
+        let (u, v) = self.texture_uv(material, barycentric);
+
+        // La normal geométrica queda del lado de la interpolada, así el orden de los
+        // vértices no importa si el modelo trae normales
+        let (normal, smooth_normal) = match self.triangle.normals {
+            Some([n0, n1, n2]) => {
+                let smooth = (n0 * b0 + n1 * b1 + n2 * b2).normalize();
+                let geometric = if self.normal.dot(&smooth) < 0.0 { -self.normal } else { self.normal };
+                (geometric, smooth)
+            }
+            None => (self.normal, self.normal),
+        };
+
+        let point = ray_origin + ray_direction * t;
+        let hit = Intersect::new(point, normal, t, material.clone(), u, v)
+            .with_smooth_normal(smooth_normal)
+            .with_uv_extent(material.texture_uv_extent(self.uv_extent));
+
+        // Marco tangente alineado con u y v, perpendicular a la normal de sombreado;
+        // sin él `tangent_frame` arma uno cualquiera alrededor de la normal
+        let Some((du, dv)) = self.uv_gradients else { return hit };
+        let tangent = du - smooth_normal * smooth_normal.dot(&du);
+        if tangent.norm() <= 1e-12 {
+            return hit;
+        }
+        let tangent = tangent.normalize();
+        let bitangent = smooth_normal.cross(&tangent);
+        // Con la textura espejada v crece en sentido contrario
+        let bitangent = if bitangent.dot(&dv) < 0.0 { -bitangent } else { bitangent };
+        hit.with_tangents(tangent, bitangent)
+    }
+}
+
+// Malla de triángulos con su propia BVH, para que un modelo grande cueste en la BVH
+// de la escena lo mismo que una sola primitiva. Los triángulos guardan el índice de
+// su material en la tabla de la malla. Cada triángulo emisivo es una luz de área
+pub struct Mesh {
+    triangles: Bvh<MeshTriangle>,
+    materials: Vec<Material>,
+}
+
+impl Mesh {
+    pub fn new(triangles: Vec<Triangle>, materials: Vec<Material>) -> Self {
+        debug_assert!(triangles.iter().all(|triangle| triangle.material < materials.len()));
+        let triangles = triangles.into_iter().map(MeshTriangle::new).collect();
+        Mesh {
+            triangles: Bvh::with_bounds(triangles, |face: &MeshTriangle| {
+                let [v0, v1, v2] = face.triangle.vertices;
+                Aabb::new(v0, v0).grow(&v1).grow(&v2)
+            }),
+            materials,
+        }
+    }
+}
+
+impl RayIntersect for Mesh {
+    // Los candidatos solo resuelven el corte con el rayo y, si el material tiene
+    // recortes, la textura; la intersección completa se arma para el más cercano
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
+        let closest = self.triangles.closest_by(ray_origin, ray_direction, |face| {
+            let (t, barycentric) = face.triangle.hit(ray_origin, ray_direction)?;
+            let material = &self.materials[face.triangle.material];
+            if material.alpha_cutout.is_some() {
+                let (u, v) = face.texture_uv(material, barycentric);
+                if material.is_cut(u, v) {
+                    return None;
+                }
+            }
+            Some((t, (face, t, barycentric)))
+        });
+        match closest {
+            Some((face, t, barycentric)) => {
+                face.intersect(ray_origin, ray_direction, t, barycentric, &self.materials[face.triangle.material])
+            }
+            None => Intersect::empty(),
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.triangles.bounds()
+    }
+
+    fn emitters(&self) -> Vec<Light> {
+        let emissive: Vec<bool> = self.materials.iter().map(|material| material.is_emissive()).collect();
+        self.triangles
+            .primitives()
+            .iter()
+            .filter(|face| emissive[face.triangle.material] && face.normal != Vec3::zeros())
+            .filter_map(|face| {
+                let [v0, v1, v2] = face.triangle.vertices;
+                let centroid = (v0 + v1 + v2) / 3.0;
+                let corners = face.triangle.vertices.map(|vertex| vertex - centroid);
+                Light::emitter(centroid, LightShape::Triangle { corners }, &self.materials[face.triangle.material])
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Cube;
+    use crate::integrator::{Integrator, RenderContext};
+    use crate::light::SceneLights;
+    use crate::materials::TextureManager;
+    use crate::radiance::Radiance;
+    use crate::ray_intersect::Object;
+    use crate::sky::Sky;
+    use crate::whitted::WhittedIntegrator;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn triangle(vertices: [Vec3; 3]) -> Triangle {
+        Triangle { vertices, normals: None, uvs: None, material: 0 }
+    }
+
+    #[test]
+    fn test_barycentric_uv_and_smooth_normal() {
+        let mut triangle = triangle([Vec3::new(0.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0)]);
+        triangle.uvs = Some([[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
+        triangle.normals = Some([Vec3::new(0.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 1.0).normalize(), Vec3::new(0.0, 0.0, 1.0)]);
+        let mesh = Mesh::new(vec![triangle], vec![Material::black()]);
+        let hit = mesh.ray_intersect(&Vec3::new(0.5, 0.5, 3.0), &Vec3::new(0.0, 0.0, -1.0));
+        assert!(hit.is_intersecting && (hit.distance - 3.0).abs() < 1e-5);
+        assert!((hit.u - 0.25).abs() < 1e-5 && (hit.v - 0.25).abs() < 1e-5);
+        assert!((hit.uv_extent - 2.0).abs() < 1e-5);
+        assert_eq!(hit.normal, Vec3::new(0.0, 0.0, 1.0));
+        // La normal interpolada se inclina hacia +x y el marco tangente la rodea
+        assert!(hit.smooth_normal.x > 0.0 && (hit.smooth_normal.norm() - 1.0).abs() < 1e-5);
+        assert!(hit.tangent.dot(&hit.smooth_normal).abs() < 1e-5 && hit.bitangent.y > 0.0);
+
+        // Fuera del triángulo y por detrás del origen del rayo
+        assert!(!mesh.ray_intersect(&Vec3::new(1.5, 1.5, 3.0), &Vec3::new(0.0, 0.0, -1.0)).is_intersecting);
+        assert!(!mesh.ray_intersect(&Vec3::new(0.5, 0.5, 3.0), &Vec3::new(0.0, 0.0, 1.0)).is_intersecting);
+    }
+
+    #[test]
+    fn test_mesh_matches_linear_scan() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let random_triangle = |rng: &mut StdRng| {
+            let center = Vec3::new(rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0));
+            let mut corner = || center + Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+            triangle([corner(), corner(), corner()])
+        };
+        let triangles: Vec<Triangle> = (0..300).map(|_| random_triangle(&mut rng)).collect();
+        let copies: Vec<Triangle> = triangles.iter().map(|t| triangle(t.vertices)).collect();
+        let mesh = Mesh::new(copies, vec![Material::black()]);
+
+        for _ in 0..500 {
+            let origin = Vec3::new(rng.gen_range(-10.0..10.0), rng.gen_range(-10.0..10.0), rng.gen_range(-10.0..10.0));
+            let direction = (Vec3::new(rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0)) - origin).normalize();
+            let linear = triangles
+                .iter()
+                .filter_map(|t| t.hit(&origin, &direction))
+                .map(|(distance, _)| distance)
+                .fold(f32::INFINITY, f32::min);
+            let hit = mesh.ray_intersect(&origin, &direction);
+            assert_eq!(hit.is_intersecting, linear.is_finite());
+            if hit.is_intersecting {
+                assert!((hit.distance - linear).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn test_emissive_mesh_lights_floor() {
+        // Cuadrado emisivo de dos triángulos a 1 unidad sobre un piso, en una escena
+        // sin cielo ni otras luces
+        let mut glow = Material::black();
+        glow.emissive_color = Some(Radiance::new(1.0, 0.8, 0.6));
+        glow.emissive_intensity = 3.0;
+        let corners = [Vec3::new(-1.0, 1.0, -1.0), Vec3::new(1.0, 1.0, -1.0), Vec3::new(1.0, 1.0, 1.0), Vec3::new(-1.0, 1.0, 1.0)];
+        let quad = vec![triangle([corners[0], corners[1], corners[2]]), triangle([corners[0], corners[2], corners[3]])];
+        let mesh = Mesh::new(quad, vec![glow]);
+
+        let emitters = mesh.emitters();
+        assert_eq!(emitters.len(), 2);
+        let area: f32 = emitters.iter().map(|light| match light.shape {
+            LightShape::Triangle { corners: [a, b, c] } => 0.5 * (b - a).cross(&(c - a)).norm(),
+            _ => panic!("se esperaba un triángulo"),
+        }).sum();
+        assert!((area - 4.0).abs() < 1e-5);
+
+        let floor = Material::new(Radiance::new(0.8, 0.8, 0.8), 10.0, [0.9, 0.0], 0.0, 0.0, 1.0);
+        let floor = Cube::new(Vec3::new(-5.0, -1.0, -5.0), Vec3::new(5.0, 0.0, 5.0), floor);
+        let objects = Bvh::new(vec![Box::new(floor) as Object, Box::new(mesh) as Object]);
+        let texture_manager = TextureManager::new();
+        let sky = Sky::uniform(Radiance::black());
+        let radiance = |lights: &SceneLights| {
+            let context = RenderContext {
+                objects: &objects,
+                lights,
+                texture_manager: &texture_manager,
+                sky: &sky,
+                environment: None,
+                ambient: Radiance::black(),
+                pixel_spread: 0.0,
+            };
+            // Rayo que llega al piso junto al cuadrado sin pasar por él
+            let origin = Vec3::new(4.0, 3.0, 0.0);
+            let direction = (Vec3::new(1.5, 0.0, 0.0) - origin).normalize();
+            WhittedIntegrator::new().radiance(&origin, &direction, &context, &mut StdRng::seed_from_u64(9))
+        };
+
+        assert_eq!(radiance(&SceneLights::new(Vec::new())), Radiance::black());
+        // El emisor anaranjado tiñe el piso gris
+        let lit = radiance(&SceneLights::new(objects.emitters()));
+        assert!(lit.r > 0.05 && lit.r > lit.g && lit.g > lit.b, "{:?}", lit);
+    }
+}
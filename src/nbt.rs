@@ -0,0 +1,238 @@
+use flate2::read::GzDecoder;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+
+// Anidamiento máximo de listas y compuestos, para que un archivo dañado no agote la pila
+const MAX_DEPTH: usize = 512;
+
+// Valor de NBT, el formato binario de Minecraft para mundos y estructuras
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(HashMap<String, Tag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    // Hijo de un compuesto
+    pub fn get(&self, name: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(children) => children.get(name),
+            _ => None,
+        }
+    }
+
+    // Cualquier entero como i64, para los campos que cambian de tipo entre versiones
+    pub fn as_int(&self) -> Option<i64> {
+        match *self {
+            Tag::Byte(value) => Some(value as i64),
+            Tag::Short(value) => Some(value as i64),
+            Tag::Int(value) => Some(value as i64),
+            Tag::Long(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Tag::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[i8]> {
+        match self {
+            Tag::ByteArray(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<&HashMap<String, Tag>> {
+        match self {
+            Tag::Compound(children) => Some(children),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct NbtError(pub String);
+
+impl fmt::Display for NbtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NBT inválido: {}", self.0)
+    }
+}
+
+impl std::error::Error for NbtError {}
+
+// Lee el compuesto raíz de un archivo NBT, comprimido con gzip o sin comprimir, y
+// devuelve su nombre y su contenido
+pub fn read_nbt(bytes: &[u8]) -> Result<(String, Tag), NbtError> {
+    let decompressed;
+    let bytes = if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut buffer = Vec::new();
+        GzDecoder::new(bytes).read_to_end(&mut buffer).map_err(|e| NbtError(format!("no se pudo descomprimir: {}", e)))?;
+        decompressed = buffer;
+        &decompressed[..]
+    } else {
+        bytes
+    };
+    let mut reader = Reader { bytes, position: 0 };
+    if reader.u8()? != 10 {
+        return Err(NbtError("la raíz no es un compuesto".to_string()));
+    }
+    let name = reader.string()?;
+    let root = reader.payload(10, 0)?;
+    Ok((name, root))
+}
+
+// Enteros en big endian, como los guarda Java
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], NbtError> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + N)
+            .ok_or_else(|| NbtError("el archivo está cortado".to_string()))?;
+        self.position += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, NbtError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn i32(&mut self) -> Result<i32, NbtError> {
+        Ok(i32::from_be_bytes(self.take()?))
+    }
+
+    fn length(&mut self) -> Result<usize, NbtError> {
+        let length = self.i32()?;
+        // Cada elemento ocupa al menos un byte, así que un largo mayor que lo que queda
+        // es un archivo dañado y no una reserva de memoria enorme
+        if length < 0 || length as usize > self.bytes.len() - self.position {
+            return Err(NbtError(format!("largo {} fuera de rango", length)));
+        }
+        Ok(length as usize)
+    }
+
+    fn string(&mut self) -> Result<String, NbtError> {
+        let length = u16::from_be_bytes(self.take()?) as usize;
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + length)
+            .ok_or_else(|| NbtError("el archivo está cortado".to_string()))?;
+        self.position += length;
+        // Java usa UTF-8 modificado; para nombres de bloques coincide con UTF-8
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    fn payload(&mut self, kind: u8, depth: usize) -> Result<Tag, NbtError> {
+        if depth > MAX_DEPTH {
+            return Err(NbtError("anidamiento demasiado profundo".to_string()));
+        }
+        Ok(match kind {
+            1 => Tag::Byte(self.u8()? as i8),
+            2 => Tag::Short(i16::from_be_bytes(self.take()?)),
+            3 => Tag::Int(self.i32()?),
+            4 => Tag::Long(i64::from_be_bytes(self.take()?)),
+            5 => Tag::Float(f32::from_be_bytes(self.take()?)),
+            6 => Tag::Double(f64::from_be_bytes(self.take()?)),
+            7 => {
+                let length = self.length()?;
+                Tag::ByteArray((0..length).map(|_| self.u8().map(|b| b as i8)).collect::<Result<_, _>>()?)
+            }
+            8 => Tag::String(self.string()?),
+            9 => {
+                let element = self.u8()?;
+                let length = self.length()?;
+                if element == 0 && length > 0 {
+                    return Err(NbtError("lista de elementos vacíos".to_string()));
+                }
+                Tag::List((0..length).map(|_| self.payload(element, depth + 1)).collect::<Result<_, _>>()?)
+            }
+            10 => {
+                let mut children = HashMap::new();
+                loop {
+                    let child = self.u8()?;
+                    if child == 0 {
+                        break;
+                    }
+                    let name = self.string()?;
+                    children.insert(name, self.payload(child, depth + 1)?);
+                }
+                Tag::Compound(children)
+            }
+            11 => {
+                let length = self.length()?;
+                Tag::IntArray((0..length).map(|_| self.i32()).collect::<Result<_, _>>()?)
+            }
+            12 => {
+                let length = self.length()?;
+                Tag::LongArray((0..length).map(|_| self.take().map(i64::from_be_bytes)).collect::<Result<_, _>>()?)
+            }
+            other => return Err(NbtError(format!("tipo de etiqueta {} desconocido", other))),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    #[test]
+    fn test_read_gzipped_nbt() {
+        // {"nivel": {nombre: "hola", alto: 7s, datos: [1b, -2b], pos: [1, 2], capas: [{}]}}
+        let mut raw = vec![10, 0, 5];
+        raw.extend_from_slice(b"nivel");
+        raw.extend_from_slice(&[8, 0, 6]);
+        raw.extend_from_slice(b"nombre");
+        raw.extend_from_slice(&[0, 4]);
+        raw.extend_from_slice(b"hola");
+        raw.extend_from_slice(&[2, 0, 4]);
+        raw.extend_from_slice(b"alto");
+        raw.extend_from_slice(&7i16.to_be_bytes());
+        raw.extend_from_slice(&[7, 0, 5]);
+        raw.extend_from_slice(b"datos");
+        raw.extend_from_slice(&[0, 0, 0, 2, 1, 0xfe]);
+        raw.extend_from_slice(&[11, 0, 3]);
+        raw.extend_from_slice(b"pos");
+        raw.extend_from_slice(&[0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2]);
+        raw.extend_from_slice(&[9, 0, 5]);
+        raw.extend_from_slice(b"capas");
+        raw.extend_from_slice(&[10, 0, 0, 0, 1, 0]);
+        raw.push(0);
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&raw).unwrap();
+        let (name, root) = read_nbt(&encoder.finish().unwrap()).unwrap();
+        assert_eq!(name, "nivel");
+        assert_eq!(root.get("nombre").and_then(Tag::as_str), Some("hola"));
+        assert_eq!(root.get("alto").and_then(Tag::as_int), Some(7));
+        assert_eq!(root.get("datos").and_then(Tag::as_bytes), Some(&[1i8, -2][..]));
+        assert_eq!(root.get("pos"), Some(&Tag::IntArray(vec![1, 2])));
+        assert_eq!(root.get("capas"), Some(&Tag::List(vec![Tag::Compound(HashMap::new())])));
+
+        // El mismo archivo sin comprimir, y cortado a la mitad
+        assert_eq!(read_nbt(&raw).unwrap().1, root);
+        assert!(read_nbt(&raw[..20]).is_err());
+    }
+}
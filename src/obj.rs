@@ -0,0 +1,385 @@
+use nalgebra_glm::Vec3;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use crate::materials::{AlphaCutout, Material, TextureManager};
+use crate::mesh::Triangle;
+use crate::radiance::Radiance;
+
+// Triángulos de un archivo Wavefront .obj con los materiales de sus .mtl
+pub struct ObjModel {
+    pub triangles: Vec<Triangle>,
+    pub materials: Vec<Material>, // Tabla de los triángulos; el primero es el de respaldo
+    pub material_count: usize,    // Materiales leídos de los .mtl
+    pub warnings: Vec<String>,    // Materiales pedidos que no están en los .mtl
+}
+
+#[derive(Debug)]
+pub struct ObjError {
+    pub path: String,
+    pub line: Option<usize>, // Empieza en 1
+    pub message: String,
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.path, line, self.message),
+            None => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+// Lee un modelo .obj. Las caras sin material o con uno que no está en los .mtl usan
+// `fallback`; los materiales de los .mtl reciben ids a partir de `first_id` para la
+// vista de índice de material. Los polígonos se dividen en abanicos de triángulos
+pub fn load_obj(path: &str, texture_manager: &mut TextureManager, fallback: Material, first_id: usize) -> Result<ObjModel, ObjError> {
+    let source = fs::read_to_string(path).map_err(|e| ObjError {
+        path: path.to_string(),
+        line: None,
+        message: format!("no se pudo leer el modelo: {}", e),
+    })?;
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+    let error = |line: usize, message: String| ObjError { path: path.to_string(), line: Some(line + 1), message };
+
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut materials: HashMap<String, Material> = HashMap::new();
+    let mut textures = TextureCache::default();
+    let mut table = vec![fallback];
+    let mut slots: HashMap<String, usize> = HashMap::new();
+    let mut current = 0;
+    let mut triangles = Vec::new();
+    let mut warnings = Vec::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else { continue };
+        let rest: Vec<&str> = tokens.collect();
+        match keyword {
+            "v" => positions.push(parse_vec3(&rest).map_err(|message| error(number, message))?),
+            "vn" => normals.push(parse_vec3(&rest).map_err(|message| error(number, message))?.normalize()),
+            "vt" => {
+                let values = parse_floats(&rest).map_err(|message| error(number, message))?;
+                if values.is_empty() {
+                    return Err(error(number, "coordenada de textura sin valores".to_string()));
+                }
+                uvs.push([values[0], values.get(1).copied().unwrap_or(0.0)]);
+            }
+            "f" => {
+                if rest.len() < 3 {
+                    return Err(error(number, "una cara necesita al menos tres vértices".to_string()));
+                }
+                let corners = rest
+                    .iter()
+                    .map(|corner| parse_corner(corner, positions.len(), uvs.len(), normals.len()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|message| error(number, message))?;
+                for i in 1..corners.len() - 1 {
+                    let face = [corners[0], corners[i], corners[i + 1]];
+                    let uv_indices: Option<Vec<usize>> = face.iter().map(|corner| corner.1).collect();
+                    let normal_indices: Option<Vec<usize>> = face.iter().map(|corner| corner.2).collect();
+                    triangles.push(Triangle {
+                        vertices: face.map(|corner| positions[corner.0]),
+                        uvs: uv_indices.map(|indices| [uvs[indices[0]], uvs[indices[1]], uvs[indices[2]]]),
+                        normals: normal_indices.map(|indices| [normals[indices[0]], normals[indices[1]], normals[indices[2]]]),
+                        material: current,
+                    });
+                }
+            }
+            "mtllib" => {
+                for library in &rest {
+                    let library_path = directory.join(library);
+                    let first = first_id + materials.len();
+                    let loaded = load_mtl(&library_path, texture_manager, &mut textures, first)?;
+                    materials.extend(loaded);
+                }
+            }
+            "usemtl" => {
+                current = match rest.first() {
+                    Some(name) => match materials.get(*name) {
+                        Some(material) => *slots.entry(name.to_string()).or_insert_with(|| {
+                            table.push(material.clone());
+                            table.len() - 1
+                        }),
+                        None => {
+                            warnings.push(format!("línea {}: el material {} no está en los .mtl; se usa el de respaldo", number + 1, name));
+                            0
+                        }
+                    },
+                    None => 0,
+                };
+            }
+            // Grupos, objetos y grupos de suavizado no cambian la geometría
+            _ => {}
+        }
+    }
+
+    Ok(ObjModel { triangles, materials: table, material_count: materials.len(), warnings })
+}
+
+// Material de las caras sin material: el gris difuso que el formato da por defecto
+pub fn default_material() -> Material {
+    let mtl = MtlMaterial::default();
+    let diffuse = Radiance::new(mtl.diffuse.x, mtl.diffuse.y, mtl.diffuse.z);
+    Material::new(diffuse, mtl.shininess, [1.0, 0.0], 0.0, 0.0, mtl.refraction_index)
+}
+
+// Texturas ya cargadas por ruta, para no repetirlas entre materiales
+#[derive(Default)]
+struct TextureCache {
+    colors: HashMap<String, usize>,
+    normal_maps: HashMap<String, usize>,
+}
+
+fn load_mtl(
+    path: &Path,
+    texture_manager: &mut TextureManager,
+    textures: &mut TextureCache,
+    first_id: usize,
+) -> Result<HashMap<String, Material>, ObjError> {
+    let display = path.display().to_string();
+    let source = fs::read_to_string(path).map_err(|e| ObjError {
+        path: display.clone(),
+        line: None,
+        message: format!("no se pudo leer la biblioteca de materiales: {}", e),
+    })?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let error = |line: usize, message: String| ObjError { path: display.clone(), line: Some(line + 1), message };
+
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+    for (number, line) in source.lines().enumerate() {
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else { continue };
+        let rest: Vec<&str> = tokens.collect();
+        if keyword == "newmtl" {
+            let name = rest.join(" ");
+            if let Some((name, definition)) = current.replace((name, MtlMaterial::default())) {
+                materials.insert(name, definition);
+            }
+            continue;
+        }
+        let Some((_, definition)) = current.as_mut() else { continue };
+        let number_value = |rest: &[&str]| parse_number(rest).map_err(|message| error(number, message));
+        match keyword {
+            "Kd" => definition.diffuse = parse_vec3(&rest).map_err(|message| error(number, message))?,
+            "Ks" => definition.specular = parse_vec3(&rest).map_err(|message| error(number, message))?,
+            "Ke" => definition.emission = parse_vec3(&rest).map_err(|message| error(number, message))?,
+            "Ns" => definition.shininess = number_value(&rest)?,
+            "Ni" => definition.refraction_index = number_value(&rest)?,
+            "d" => definition.opacity = number_value(&rest)?,
+            "Tr" => definition.opacity = 1.0 - number_value(&rest)?,
+            "illum" => definition.illumination = number_value(&rest)? as u32,
+            "map_Kd" | "map_Bump" | "map_bump" | "bump" | "norm" | "map_d" => {
+                // Las opciones como -s o -bm van antes del nombre del archivo
+                let Some(file) = rest.last() else {
+                    return Err(error(number, format!("'{}' sin archivo", keyword)));
+                };
+                let texture_path = directory.join(file).display().to_string();
+                let normal_map = matches!(keyword, "map_Bump" | "map_bump" | "bump" | "norm");
+                let (cache, load): (_, fn(&mut TextureManager, &str) -> image::ImageResult<usize>) = if normal_map {
+                    (&mut textures.normal_maps, TextureManager::load_normal_map)
+                } else {
+                    (&mut textures.colors, TextureManager::load_texture)
+                };
+                let index = match cache.get(&texture_path) {
+                    Some(index) => *index,
+                    None => {
+                        let index = load(texture_manager, &texture_path)
+                            .map_err(|e| error(number, format!("no se pudo cargar la textura {}: {}", texture_path, e)))?;
+                        cache.insert(texture_path, index);
+                        index
+                    }
+                };
+                match keyword {
+                    "map_Kd" => definition.texture = Some(index),
+                    "map_d" => definition.alpha = Some(index),
+                    _ => definition.normal_map = Some(index),
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some((name, definition)) = current {
+        materials.insert(name, definition);
+    }
+
+    // Orden estable de ids aunque HashMap no lo tenga
+    let mut names: Vec<String> = materials.keys().cloned().collect();
+    names.sort();
+    Ok(names
+        .into_iter()
+        .enumerate()
+        .map(|(offset, name)| {
+            let material = materials[&name].to_material(texture_manager, first_id + offset);
+            (name, material)
+        })
+        .collect())
+}
+
+// Propiedades de un material de .mtl, con los valores por defecto del formato
+struct MtlMaterial {
+    diffuse: Vec3,
+    specular: Vec3,
+    emission: Vec3,
+    shininess: f32,
+    refraction_index: f32,
+    opacity: f32,
+    illumination: u32,
+    texture: Option<usize>,
+    normal_map: Option<usize>,
+    alpha: Option<usize>,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            diffuse: Vec3::repeat(0.8),
+            specular: Vec3::zeros(),
+            emission: Vec3::zeros(),
+            shininess: 10.0,
+            refraction_index: 1.0,
+            opacity: 1.0,
+            illumination: 2,
+            texture: None,
+            normal_map: None,
+            alpha: None,
+        }
+    }
+}
+
+impl MtlMaterial {
+    // Los colores de los .mtl ya son reflectancias lineales. Los modos de iluminación
+    // 3, 5 y 7 son los de reflejo especular, que aquí se vuelve reflectividad
+    fn to_material(&self, texture_manager: &TextureManager, id: usize) -> Material {
+        let specular = self.specular.max();
+        let reflectivity = if matches!(self.illumination, 3 | 5 | 7) { specular } else { 0.0 };
+        let transparency = (1.0 - self.opacity).clamp(0.0, 1.0);
+        let mut material = match self.texture {
+            Some(index) => {
+                let mut material = Material::new_with_texture(index, self.shininess, [1.0, specular], self.refraction_index);
+                material.reflectivity = reflectivity;
+                material.transparency = transparency;
+                material
+            }
+            None => Material::new(
+                Radiance::new(self.diffuse.x, self.diffuse.y, self.diffuse.z),
+                self.shininess,
+                [1.0, specular],
+                reflectivity,
+                transparency,
+                self.refraction_index,
+            ),
+        };
+        material.normal_map = self.normal_map;
+        if let Some(index) = self.alpha {
+            material.alpha_cutout = Some(AlphaCutout { texture: Arc::clone(texture_manager.get_texture(index)), threshold: 0.5 });
+        }
+        if self.emission.max() > 0.0 {
+            material.emissive_color = Some(Radiance::new(self.emission.x, self.emission.y, self.emission.z));
+            material.emissive_intensity = 1.0;
+        }
+        material.id = id;
+        material
+    }
+}
+
+fn parse_floats(tokens: &[&str]) -> Result<Vec<f32>, String> {
+    tokens
+        .iter()
+        .map(|token| token.parse::<f32>().map_err(|_| format!("número inválido '{}'", token)))
+        .collect()
+}
+
+fn parse_number(tokens: &[&str]) -> Result<f32, String> {
+    parse_floats(tokens)?.first().copied().ok_or_else(|| "falta el valor".to_string())
+}
+
+fn parse_vec3(tokens: &[&str]) -> Result<Vec3, String> {
+    let values = parse_floats(tokens)?;
+    if values.len() < 3 {
+        return Err(format!("se esperaban tres números y hay {}", values.len()));
+    }
+    Ok(Vec3::new(values[0], values[1], values[2]))
+}
+
+// Índices base 0 de posición, coordenada de textura y normal de un vértice de cara
+// ("v", "v/vt", "v//vn" o "v/vt/vn"); los negativos cuentan desde el final
+fn parse_corner(corner: &str, positions: usize, uvs: usize, normals: usize) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let resolve = |text: &str, count: usize| -> Result<usize, String> {
+        let index: i64 = text.parse().map_err(|_| format!("índice inválido '{}'", text))?;
+        let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+        if resolved < 0 || resolved >= count as i64 {
+            return Err(format!("índice {} fuera de rango (hay {})", index, count));
+        }
+        Ok(resolved as usize)
+    };
+    let mut parts = corner.split('/');
+    let position = resolve(parts.next().unwrap_or(""), positions)?;
+    let uv = match parts.next() {
+        Some(text) if !text.is_empty() => Some(resolve(text, uvs)?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(text) if !text.is_empty() => Some(resolve(text, normals)?),
+        _ => None,
+    };
+    Ok((position, uv, normal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_obj_with_mtl() {
+        let dir = std::env::temp_dir().join("modelo_prueba_obj");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("cuadro.mtl"),
+            "newmtl rojo\nKd 0.8 0.1 0.1\nKs 0.5 0.5 0.5\nNs 40\nillum 3\n\nnewmtl vidrio\nd 0.25\nNi 1.5\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("cuadro.obj"),
+            "mtllib cuadro.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\n\
+             usemtl rojo\nf 1/1/1 2/2/1 3/3/1 4/4/1\nusemtl vidrio\nf -4 -2 -1\nusemtl otro\nf 1//1 2//1 3//1\n",
+        )
+        .unwrap();
+
+        let mut texture_manager = TextureManager::new();
+        let path = dir.join("cuadro.obj").display().to_string();
+        let model = load_obj(&path, &mut texture_manager, Material::black(), 7).unwrap();
+        assert_eq!(model.triangles.len(), 4);
+        assert_eq!(model.material_count, 2);
+
+        let quad = &model.triangles[1];
+        assert_eq!(quad.vertices, [Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0)]);
+        assert_eq!(quad.uvs, Some([[0.0, 0.0], [1.0, 1.0], [0.0, 1.0]]));
+        let red = &model.materials[quad.material];
+        assert!((red.diffuse.r - 0.8).abs() < 1e-6 && red.reflectivity == 0.5);
+        assert_eq!((red.specular, red.id), (40.0, 7));
+        assert_eq!(model.triangles[0].material, quad.material);
+
+        let glass = &model.triangles[2];
+        assert!(glass.uvs.is_none() && glass.normals.is_none());
+        let material = &model.materials[glass.material];
+        assert!((material.transparency - 0.75).abs() < 1e-6 && material.refraction_index == 1.5);
+        // Un material que no está en el .mtl usa el de respaldo y se avisa
+        assert_eq!(model.triangles[3].material, 0);
+        assert!(model.materials[0].diffuse.r == 0.0 && model.triangles[3].normals.is_some());
+        assert_eq!(model.warnings, vec!["línea 15: el material otro no está en los .mtl; se usa el de respaldo".to_string()]);
+
+        fs::write(dir.join("roto.obj"), "v 0 0 0\nv 1 0 0\nf 1 2 3\n").unwrap();
+        let error = load_obj(&dir.join("roto.obj").display().to_string(), &mut texture_manager, Material::black(), 0)
+            .err()
+            .unwrap();
+        assert!(error.to_string().ends_with(":3: índice 3 fuera de rango (hay 2)"), "{}", error);
+    }
+}
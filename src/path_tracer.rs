@@ -0,0 +1,180 @@
+use nalgebra_glm::Vec3;
+use rand::{Rng, RngCore};
+use std::f32::consts::PI;
+use crate::integrator::{reflect, refract, Integrator, RenderContext};
+use crate::light::Light;
+use crate::radiance::Radiance;
+use crate::sampling::{cosine_sample_hemisphere, power_heuristic};
+use crate::whitted::SHADOW_EPSILON;
+
+// Rebotes máximos de un camino; la ruleta rusa suele cortarlo mucho antes
+const MAX_BOUNCES: u32 = 16;
+// A partir de este rebote el camino puede terminar por ruleta rusa
+const ROULETTE_START: u32 = 3;
+const EPSILON: f32 = 1e-3;
+
+// Integrador de Monte Carlo: sigue un camino por píxel con muestreo coseno del
+// hemisferio, estimación del siguiente evento hacia las luces y ruleta rusa.
+// Agrega la luz indirecta difusa que el trazador Whitted no tiene. Con un mapa de
+// entorno, su luz se muestrea por importancia y se combina con el muestreo coseno (MIS).
+pub struct PathTracer {
+    max_bounces: u32,
+    roulette_start: u32,
+}
+
+impl PathTracer {
+    pub fn new() -> Self {
+        PathTracer {
+            max_bounces: MAX_BOUNCES,
+            roulette_start: ROULETTE_START,
+        }
+    }
+}
+
+impl Integrator for PathTracer {
+    fn radiance(&self, ray_origin: &Vec3, ray_direction: &Vec3, context: &RenderContext, rng: &mut dyn RngCore) -> Radiance {
+        let mut radiance = Radiance::black();
+        let mut throughput = Radiance::new(1.0, 1.0, 1.0);
+        let mut origin = *ray_origin;
+        let mut direction = *ray_direction;
+        let mut after_diffuse = false;
+        // Densidad coseno con que se eligió la dirección tras el último rebote difuso
+        let mut diffuse_pdf = 0.0;
+        // Longitud del camino desde la cámara, que da el ancho del rayo para las texturas
+        let mut travelled = 0.0;
+
+        for bounce in 0..self.max_bounces {
+            let intersect = context.objects.closest_hit(&origin, &direction);
+            if !intersect.is_intersecting {
+                // El cielo ilumina la escena. Tras un rebote difuso se omiten los discos
+                // del sol y la luna, que ya se muestrearon como luces direccionales, y la
+                // luz del mapa de entorno se pondera con la de su muestreo por importancia
+                let sky = if !after_diffuse {
+                    context.background(&direction)
+                } else if let Some(environment) = context.environment {
+                    environment.radiance(&direction) * power_heuristic(diffuse_pdf, environment.pdf(&direction))
+                } else {
+                    context.sky_light(&direction)
+                };
+                radiance += throughput * sky;
+                break;
+            }
+
+            let material = &intersect.material;
+            travelled += intersect.distance;
+            // Las primitivas emisivas son luces de la escena: tras un rebote difuso su
+            // emisión ya se sumó al muestrear las luces y no se cuenta dos veces
+            if material.is_emissive() && !after_diffuse {
+                radiance += throughput * material.get_emission();
+            }
+
+            // Normal orientada hacia el lado por el que llega el rayo. La geométrica
+            // desplaza los rayos; la del mapa de normales se usa para la luz
+            let normal = if intersect.normal.dot(&direction) < 0.0 {
+                intersect.normal
+            } else {
+                -intersect.normal
+            };
+            let perturbed = intersect.shading_normal(context.texture_manager);
+            let shading_normal = if perturbed.dot(&normal) < 0.0 { -perturbed } else { perturbed };
+
+            // Elegimos un lóbulo según los pesos del material
+            let reflectivity = material.reflectivity.max(0.0);
+            let transparency = material.transparency.max(0.0);
+            let diffuse_weight = (1.0 - reflectivity - transparency).max(0.0);
+            let total = reflectivity + transparency + diffuse_weight;
+            if total <= 0.0 {
+                break;
+            }
+            let choice = rng.gen::<f32>() * total;
+            // Elegir el lóbulo con probabilidad peso/total se compensa multiplicando por
+            // total; se limita a 1 para que materiales como el agua no creen energía
+            throughput = throughput * total.min(1.0);
+
+            if choice < reflectivity {
+                direction = reflect(&direction, &shading_normal).normalize();
+                // Con la normal inclinada la dirección puede quedar bajo la superficie
+                if direction.dot(&normal) <= 0.0 {
+                    break;
+                }
+                after_diffuse = false;
+                origin = intersect.point + normal * EPSILON;
+            } else if choice < reflectivity + transparency {
+                direction = refract(&direction, &perturbed, material.refraction_index).normalize();
+                after_diffuse = false;
+                origin = if direction.dot(&normal) < 0.0 {
+                    intersect.point - normal * EPSILON
+                } else {
+                    intersect.point + normal * EPSILON
+                };
+            } else {
+                let footprint = intersect.texture_footprint(&direction, travelled * context.pixel_spread);
+                let albedo = material.get_diffuse_color(intersect.u, intersect.v, footprint, context.texture_manager) * material.albedo[0];
+                let shading_origin = intersect.point + normal * EPSILON;
+
+                // Estimación del siguiente evento: luz directa de cada fuente, promediando
+                // varios puntos de las luces de área. La intensidad de una luz es la
+                // radiancia que refleja una superficie blanca frente a ella, igual que en
+                // el modo Whitted
+                let direct_light = |light: &Light, samples: u32, rng: &mut dyn RngCore| {
+                    let mut direct = 0.0;
+                    for _ in 0..samples {
+                        let sample = light.sample(&shading_origin, rng);
+                        let cos_theta = shading_normal.dot(&sample.direction);
+                        if cos_theta > 0.0
+                            && sample.weight > 0.0
+                            && !context.objects.any_hit(&shading_origin, &sample.direction, sample.distance - SHADOW_EPSILON)
+                        {
+                            direct += cos_theta * sample.weight;
+                        }
+                    }
+                    albedo * light.color * (light.intensity * direct / samples as f32)
+                };
+                for light in context.lights.lights() {
+                    radiance += throughput * direct_light(light, light.sample_count(), rng);
+                }
+                // De las primitivas emisivas se toman unas pocas, elegidas según su potencia
+                let picks = context.lights.emitter_picks();
+                for _ in 0..picks {
+                    if let Some((light, probability)) = context.lights.pick_emitter(rng.gen()) {
+                        radiance += throughput * direct_light(light, 1, rng) / (probability * picks as f32);
+                    }
+                }
+
+                // Iluminación basada en imagen: una dirección del mapa de entorno elegida
+                // según su brillo, con la BRDF de Lambert (albedo / pi)
+                if let Some(environment) = context.environment {
+                    let sample = environment.sample(rng);
+                    let cos_theta = shading_normal.dot(&sample.direction);
+                    if cos_theta > 0.0
+                        && sample.pdf > 0.0
+                        && !context.objects.any_hit(&shading_origin, &sample.direction, f32::INFINITY)
+                    {
+                        let weight = power_heuristic(sample.pdf, cos_theta / PI);
+                        radiance += throughput * albedo * sample.radiance * (cos_theta / PI * weight / sample.pdf);
+                    }
+                }
+
+                // Con muestreo coseno el peso coseno/pdf se cancela con el 1/pi de Lambert
+                throughput = throughput * albedo;
+                direction = cosine_sample_hemisphere(&shading_normal, rng);
+                diffuse_pdf = shading_normal.dot(&direction).max(0.0) / PI;
+                if direction.dot(&normal) <= 0.0 {
+                    break;
+                }
+                after_diffuse = true;
+                origin = shading_origin;
+            }
+
+            if bounce >= self.roulette_start {
+                let survival = throughput.r.max(throughput.g).max(throughput.b).clamp(0.05, 0.95);
+                if rng.gen::<f32>() > survival {
+                    break;
+                }
+                throughput = throughput / survival;
+            }
+        }
+
+        radiance
+    }
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+use std::ops::{Add, AddAssign, Div, Mul};
+use crate::color::Color;
+
+// Radiancia lineal en punto flotante. A diferencia de Color no satura en cada
+// operación: solo se cuantiza a sRGB de 8 bits al escribir en el framebuffer.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Radiance {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
+// Conversión de un canal sRGB (0..1) a lineal
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Conversión de un canal lineal (0..1) a sRGB
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl Radiance {
+    pub fn new(r: f32, g: f32, b: f32) -> Radiance {
+        Radiance { r, g, b }
+    }
+
+    pub fn black() -> Radiance {
+        Radiance::new(0.0, 0.0, 0.0)
+    }
+
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    // Cuantiza a 8 bits sRGB, recortando lo que quede fuera de 0..1
+    pub fn to_srgb(self) -> Color {
+        let quantize = |c: f32| (linear_to_srgb(c.clamp(0.0, 1.0)) * 255.0).round() as i32;
+        Color::new(quantize(self.r), quantize(self.g), quantize(self.b))
+    }
+}
+
+// Un Color de 8 bits se interpreta como sRGB y se pasa a lineal
+impl From<Color> for Radiance {
+    fn from(color: Color) -> Radiance {
+        Radiance::new(
+            srgb_to_linear(color.red as f32 / 255.0),
+            srgb_to_linear(color.green as f32 / 255.0),
+            srgb_to_linear(color.blue as f32 / 255.0),
+        )
+    }
+}
+
+impl Add for Radiance {
+    type Output = Radiance;
+
+    fn add(self, other: Radiance) -> Radiance {
+        Radiance::new(self.r + other.r, self.g + other.g, self.b + other.b)
+    }
+}
+
+impl AddAssign for Radiance {
+    fn add_assign(&mut self, other: Radiance) {
+        *self = *self + other;
+    }
+}
+
+impl Mul<f32> for Radiance {
+    type Output = Radiance;
+
+    fn mul(self, scalar: f32) -> Radiance {
+        Radiance::new(self.r * scalar, self.g * scalar, self.b * scalar)
+    }
+}
+
+// Producto componente a componente (por ejemplo, color de la luz por albedo)
+impl Mul<Radiance> for Radiance {
+    type Output = Radiance;
+
+    fn mul(self, other: Radiance) -> Radiance {
+        Radiance::new(self.r * other.r, self.g * other.g, self.b * other.b)
+    }
+}
+
+impl Div<f32> for Radiance {
+    type Output = Radiance;
+
+    fn div(self, scalar: f32) -> Radiance {
+        Radiance::new(self.r / scalar, self.g / scalar, self.b / scalar)
+    }
+}
+
+impl fmt::Display for Radiance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Radiance: R={:.4}, G={:.4}, B={:.4}", self.r, self.g, self.b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srgb_round_trip() {
+        for value in [0, 1, 10, 64, 128, 200, 254, 255] {
+            let color = Color::new(value, value, value);
+            let back = Radiance::from(color).to_srgb();
+            assert_eq!(back.red, value as u8);
+            assert_eq!(back.green, value as u8);
+            assert_eq!(back.blue, value as u8);
+        }
+    }
+
+    #[test]
+    fn test_sum_of_lights_does_not_clip_early() {
+        // Tres luces que por separado ya llenan el canal rojo
+        let contributions = [
+            Radiance::new(0.9, 0.2, 0.1),
+            Radiance::new(0.8, 0.3, 0.1),
+            Radiance::new(0.7, 0.1, 0.1),
+        ];
+        let sum = contributions.iter().fold(Radiance::black(), |acc, c| acc + *c);
+        assert!((sum.r - 2.4).abs() < 1e-5);
+        assert!((sum.g - 0.6).abs() < 1e-5);
+
+        // Al atenuar después de sumar se conserva la proporción entre canales
+        let dimmed = sum * 0.25;
+        assert!((dimmed.r - 0.6).abs() < 1e-5);
+        assert!((dimmed.g - 0.15).abs() < 1e-5);
+        assert!(dimmed.r > dimmed.g * 3.9);
+
+        // Con el Color de 8 bits la suma satura y la atenuación pierde la proporción
+        let clipped = contributions
+            .iter()
+            .map(|c| c.to_srgb())
+            .fold(Color::black(), |acc, c| acc + c)
+            * 0.25;
+        assert_eq!(clipped.red, 63);
+        assert!(dimmed.to_srgb().red > clipped.red);
+    }
+
+    #[test]
+    fn test_intensity_above_one_survives_until_quantization() {
+        let light = Radiance::new(1.0, 1.0, 1.0) * 4.0;
+        let halved = light * 0.5 * 0.5;
+        assert!((halved.r - 1.0).abs() < 1e-6);
+        assert_eq!(halved.to_srgb().red, 255);
+        assert_eq!((light * 2.0).to_srgb().red, 255);
+    }
+
+    #[test]
+    fn test_componentwise_mul() {
+        let result = Radiance::new(0.5, 2.0, 1.0) * Radiance::new(0.5, 0.25, 0.0);
+        assert_eq!(result, Radiance::new(0.25, 0.5, 0.0));
+    }
+}
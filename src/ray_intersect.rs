@@ -1,27 +1,41 @@
 use nalgebra_glm::Vec3;
-use crate::materials::Material;
+use crate::materials::{Material, TextureManager};
+use crate::aabb::Aabb;
+use crate::light::Light;
+use crate::sampling::orthonormal_basis;
+use std::sync::Arc;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct Intersect {
     pub point: Vec3,
-    pub normal: Vec3,
+    pub normal: Vec3,    // Normal geométrica: se usa para desplazar los rayos secundarios
+    pub smooth_normal: Vec3, // Normal interpolada de los vértices, base de la normal de sombreado
+    pub tangent: Vec3,   // Dirección en que crece u sobre la superficie; cero sin marco propio
+    pub bitangent: Vec3, // Dirección en que crece v
     pub distance: f32,
     pub is_intersecting: bool,
     pub material: Material,
     pub u: f32,
     pub v: f32,
+    pub uv_extent: f32,  // Longitud en el mundo que recorre una unidad de uv
 }
 
 impl Intersect {
+    // El marco tangente lo agrega la primitiva con `with_tangents`; si no lo hace,
+    // `tangent_frame` arma uno solo cuando se necesita
     pub fn new(point: Vec3, normal: Vec3, distance: f32, material: Material, u:f32, v:f32) -> Self {
         Intersect {
             point,
             normal,
+            smooth_normal: normal,
+            tangent: Vec3::zeros(),
+            bitangent: Vec3::zeros(),
             distance,
             is_intersecting: true,
             material,
             u, v,
+            uv_extent: 1.0,
         }
     }
 
@@ -29,15 +43,105 @@ impl Intersect {
         Intersect {
             point: Vec3::zeros(),
             normal: Vec3::zeros(),
+            smooth_normal: Vec3::zeros(),
+            tangent: Vec3::zeros(),
+            bitangent: Vec3::zeros(),
             distance: 0.0,
             is_intersecting: false,
             material: Material::black(),
             u: 0.0,
             v: 0.0,
+            uv_extent: 1.0,
+        }
+    }
+
+    // Marco tangente de la primitiva, alineado con sus coordenadas de textura
+    pub fn with_tangents(mut self, tangent: Vec3, bitangent: Vec3) -> Self {
+        self.tangent = tangent;
+        self.bitangent = bitangent;
+        self
+    }
+
+    // Marco tangente de la primitiva o, si no tiene uno, una base cualquiera alrededor
+    // de la normal. Se calcula aquí y no al crear cada candidato a golpe más cercano
+    pub fn tangent_frame(&self) -> (Vec3, Vec3) {
+        if self.tangent == Vec3::zeros() {
+            orthonormal_basis(&self.smooth_normal)
+        } else {
+            (self.tangent, self.bitangent)
+        }
+    }
+
+    // Normal de sombreado de una superficie curva aproximada con caras planas; el marco
+    // tangente debe ser perpendicular a ella
+    pub fn with_smooth_normal(mut self, smooth_normal: Vec3) -> Self {
+        self.smooth_normal = smooth_normal;
+        self
+    }
+
+    pub fn with_uv_extent(mut self, uv_extent: f32) -> Self {
+        self.uv_extent = uv_extent;
+        self
+    }
+
+    // Ancho en unidades de uv de la huella de un rayo que llega con `cone_width`
+    // unidades de ancho; se estira cuanto más rasante es el rayo, hasta cuatro veces
+    pub fn texture_footprint(&self, ray_direction: &Vec3, cone_width: f32) -> f32 {
+        let cos_theta = self.normal.dot(&ray_direction.normalize()).abs().max(0.25);
+        cone_width / (cos_theta * self.uv_extent)
+    }
+
+    // Normal para la iluminación: la interpolada (la geométrica salvo en las mallas)
+    // inclinada por el mapa de normales del material, si tiene uno
+    pub fn shading_normal(&self, texture_manager: &TextureManager) -> Vec3 {
+        match self.material.get_tangent_normal(self.u, self.v, texture_manager) {
+            Some(local) => {
+                let (tangent, bitangent) = self.tangent_frame();
+                (tangent * local.x + bitangent * local.y + self.smooth_normal * local.z).normalize()
+            }
+            None => self.smooth_normal,
         }
     }
 }
 
 pub trait RayIntersect {
     fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect;
-}
\ No newline at end of file
+    // Caja envolvente usada para construir la BVH
+    fn bounding_box(&self) -> Aabb;
+    // Luces que aporta la primitiva si su material es emisivo
+    fn emitters(&self) -> Vec<Light> {
+        Vec::new()
+    }
+}
+
+// Primitiva de la escena de cualquier tipo, compartible entre los hilos de render
+pub type Object = Box<dyn RayIntersect + Send + Sync>;
+
+impl<T: RayIntersect + ?Sized> RayIntersect for Box<T> {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
+        (**self).ray_intersect(ray_origin, ray_direction)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        (**self).bounding_box()
+    }
+
+    fn emitters(&self) -> Vec<Light> {
+        (**self).emitters()
+    }
+}
+// Un mismo objeto puede estar en la escena varias veces, por ejemplo bajo varias
+// Transformed, sin copiar su geometría
+impl<T: RayIntersect + ?Sized> RayIntersect for Arc<T> {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
+        (**self).ray_intersect(ray_origin, ray_direction)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        (**self).bounding_box()
+    }
+
+    fn emitters(&self) -> Vec<Light> {
+        (**self).emitters()
+    }
+}
@@ -0,0 +1,164 @@
+use nalgebra_glm::Vec3;
+use rand::Rng;
+use std::f32::consts::PI;
+
+// Base ortonormal (tangente, bitangente) alrededor de una normal unitaria
+pub fn orthonormal_basis(normal: &Vec3) -> (Vec3, Vec3) {
+    // Duff et al., "Building an Orthonormal Basis, Revisited"
+    let sign = 1.0f32.copysign(normal.z);
+    let a = -1.0 / (sign + normal.z);
+    let b = normal.x * normal.y * a;
+    let tangent = Vec3::new(1.0 + sign * normal.x * normal.x * a, sign * b, -sign * normal.x);
+    let bitangent = Vec3::new(b, sign + normal.y * normal.y * a, -normal.y);
+    (tangent, bitangent)
+}
+
+// Dirección en el hemisferio de `normal` con densidad proporcional al coseno (pdf = cos / pi)
+pub fn cosine_sample_hemisphere(normal: &Vec3, rng: &mut (impl Rng + ?Sized)) -> Vec3 {
+    let u1: f32 = rng.gen();
+    let u2: f32 = rng.gen();
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    let (tangent, bitangent) = orthonormal_basis(normal);
+    let local_z = (1.0 - u1).max(0.0).sqrt();
+    (tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normal * local_z).normalize()
+}
+
+// Heurística de la potencia (beta = 2) para combinar dos estrategias de muestreo
+// (MIS): peso de la muestra tomada con la pdf `f_pdf` cuando la otra estrategia
+// habría elegido la misma dirección con `g_pdf`
+pub fn power_heuristic(f_pdf: f32, g_pdf: f32) -> f32 {
+    let f = f_pdf * f_pdf;
+    let g = g_pdf * g_pdf;
+    if f + g <= 0.0 {
+        0.0
+    } else {
+        f / (f + g)
+    }
+}
+
+// Distribución constante a trozos sobre [0, 1): cada celda se elige con
+// probabilidad proporcional a su valor
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    func: Vec<f32>,
+    cdf: Vec<f32>,
+    integral: f32,
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f32>) -> Self {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i].max(0.0) / n as f32;
+        }
+        let integral = cdf[n];
+        for (i, value) in cdf.iter_mut().enumerate() {
+            // Sin valores positivos se muestrea de forma uniforme
+            *value = if integral > 0.0 { *value / integral } else { i as f32 / n as f32 };
+        }
+        Distribution1D { func, cdf, integral }
+    }
+
+    // Devuelve la posición en [0, 1), su densidad y la celda elegida
+    pub fn sample(&self, u: f32) -> (f32, f32, usize) {
+        let n = self.func.len();
+        let offset = self.cdf.partition_point(|&c| c <= u).clamp(1, n) - 1;
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0.0 { ((u - self.cdf[offset]) / width).clamp(0.0, 1.0) } else { 0.5 };
+        let x = ((offset as f32 + du) / n as f32).min(1.0 - f32::EPSILON);
+        (x, self.cell_pdf(offset), offset)
+    }
+
+    fn cell_pdf(&self, cell: usize) -> f32 {
+        if self.integral > 0.0 {
+            self.func[cell].max(0.0) / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+// Distribución constante a trozos sobre [0, 1)², guardada por filas: primero se
+// elige la fila con la distribución marginal y luego la columna dentro de ella
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f32], width: usize, height: usize) -> Self {
+        let rows: Vec<Distribution1D> = func.chunks(width).take(height).map(|row| Distribution1D::new(row.to_vec())).collect();
+        let marginal = Distribution1D::new(rows.iter().map(|row| row.integral).collect());
+        Distribution2D { rows, marginal }
+    }
+
+    // Punto (x, y) en [0, 1)² y su densidad
+    pub fn sample(&self, u: f32, v: f32) -> ((f32, f32), f32) {
+        let (y, row_pdf, row) = self.marginal.sample(v);
+        let (x, column_pdf, _) = self.rows[row].sample(u);
+        ((x, y), row_pdf * column_pdf)
+    }
+
+    pub fn pdf(&self, x: f32, y: f32) -> f32 {
+        let height = self.rows.len();
+        let row = ((y * height as f32) as usize).min(height - 1);
+        let width = self.rows[row].func.len();
+        let column = ((x * width as f32) as usize).min(width - 1);
+        self.marginal.cell_pdf(row) * self.rows[row].cell_pdf(column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn test_basis_is_orthonormal() {
+        for normal in [Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0), Vec3::new(1.0, 2.0, -3.0).normalize()] {
+            let (t, b) = orthonormal_basis(&normal);
+            assert!((t.norm() - 1.0).abs() < 1e-5);
+            assert!((b.norm() - 1.0).abs() < 1e-5);
+            assert!(t.dot(&b).abs() < 1e-5);
+            assert!(t.dot(&normal).abs() < 1e-5);
+            assert!(b.dot(&normal).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_cosine_samples_mean_cosine() {
+        // Con pdf = cos/pi, el coseno promedio es 2/3
+        let mut rng = StdRng::seed_from_u64(1);
+        let normal = Vec3::new(0.3, 0.9, -0.2).normalize();
+        let n = 20000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let direction = cosine_sample_hemisphere(&normal, &mut rng);
+            let cos = direction.dot(&normal);
+            assert!(cos >= -1e-5);
+            sum += cos;
+        }
+        assert!((sum / n as f32 - 2.0 / 3.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_distribution_follows_values() {
+        // Solo la celda (2, 1) tiene peso: toda muestra cae en ella con densidad 8
+        let mut func = vec![0.0; 8];
+        func[4 + 2] = 5.0;
+        let distribution = Distribution2D::new(&func, 4, 2);
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..100 {
+            let ((x, y), pdf) = distribution.sample(rng.gen(), rng.gen());
+            assert!((0.5..0.75).contains(&x) && (0.5..1.0).contains(&y));
+            assert!((pdf - 8.0).abs() < 1e-4);
+            assert!((distribution.pdf(x, y) - 8.0).abs() < 1e-4);
+        }
+        assert_eq!(distribution.pdf(0.1, 0.1), 0.0);
+        assert!((power_heuristic(1.0, 1.0) - 0.5).abs() < 1e-6);
+    }
+}
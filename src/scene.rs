@@ -1,54 +1,334 @@
 use crate::light::Light;
 use crate::color::Color;
+use crate::radiance::Radiance;
+use crate::sky::Sky;
 use nalgebra_glm::Vec3;
+use std::f32::consts::PI;
 
-pub struct Scene {
-    pub time_of_day: f32, // Valor entre 0.0 (medianoche) y 1.0 (medianoche siguiente)
-    pub light: Light,
-    pub cycle_duration: f32, 
-}
-
-impl Scene {
-    pub fn new(cycle_duration: f32) -> Self {
-        Self {
-            time_of_day: 0.0, // Empezar en medianoche
-            light: Light {
-                position: Vec3::new(0.0, 10.0, 10.0), // Posición inicial de la luz
-                color: Color::new(255, 255, 255),     // Color inicial (luz blanca)
-                intensity: 1.0,                       // Intensidad inicial
-            },
-            cycle_duration,
+// Inclinación de la órbita del sol y la luna respecto al plano vertical x-y
+const ORBIT_TILT: f32 = 0.4;
+// Paso de la hora del día por pulsación al recorrer el ciclo a mano (media hora)
+const SCRUB_STEP: f32 = 1.0 / 48.0;
+// Avance de la hora con que el sol se mueve lo bastante para notarse (un cuarto de
+// hora): antes de eso la ventana sigue acumulando muestras con la luz anterior
+const RELIGHT_STEP: f32 = 1.0 / 96.0;
+const MIN_SPEED: f32 = 0.125;
+const MAX_SPEED: f32 = 64.0;
+
+// Valores que se pueden interpolar entre fotogramas clave
+pub trait Lerp: Copy {
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, other: f32, t: f32) -> f32 {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for Radiance {
+    fn lerp(self, other: Radiance, t: f32) -> Radiance {
+        self * (1.0 - t) + other * t
+    }
+}
+
+// Degradado cíclico sobre la hora del día (0 = medianoche, 1 = la siguiente):
+// entre dos claves se interpola con una curva suave, y después de la última
+// clave se vuelve a la primera
+#[derive(Debug, Clone)]
+pub struct Gradient<T: Lerp> {
+    keys: Vec<(f32, T)>,
+}
+
+impl<T: Lerp> Gradient<T> {
+    pub fn new(mut keys: Vec<(f32, T)>) -> Self {
+        assert!(!keys.is_empty(), "un degradado necesita al menos una clave");
+        for key in keys.iter_mut() {
+            key.0 = key.0.rem_euclid(1.0);
         }
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Gradient { keys }
     }
 
-    pub fn update_time(&mut self, delta_time: f32) {
-        self.time_of_day = (self.time_of_day + delta_time) % 1.0; // Ciclo continuo
-        self.update_light();
-    }
-
-    fn update_light(&mut self) {
-        let angle = self.time_of_day * std::f32::consts::PI * 2.0; // Ángulo para el ciclo de rotación
-        let light_intensity = (angle.sin() + 1.0) * 0.5; // Intensidad entre 0.0 y 1.0
-
-        // Cambiar la posición de la luz, simulando la rotación del sol
-        self.light.position = Vec3::new(
-            angle.cos() * 10.0, // 10 unidades de distancia en el eje X
-            angle.sin() * 10.0, // 10 unidades de distancia en el eje Y
-            10.0, // Mantener una altura constante
-        );
-
-        // Cambiar el color de la luz, simulando diferentes tonos durante el día
-        if self.time_of_day < 0.25 || self.time_of_day > 0.75 {
-            // Noche: Luz más fría y tenue
-            self.light.color = Color::new(50, 50, 100); // Color azul oscuro
-        } else if self.time_of_day < 0.5 {
-            // Mañana: Luz cálida
-            self.light.color = Color::new(255, 200, 150); // Luz cálida de amanecer
-        } else {
-            // Tarde: Luz más intensa
-            self.light.color = Color::new(255, 255, 255); // Luz blanca de mediodía
+    pub fn sample(&self, time: f32) -> T {
+        let time = time.rem_euclid(1.0);
+        let next = self.keys.partition_point(|key| key.0 <= time);
+        let (start, end) = match next {
+            // Antes de la primera clave o después de la última: tramo que cruza la medianoche
+            0 => (self.keys[self.keys.len() - 1], self.keys[0]),
+            n if n == self.keys.len() => (self.keys[n - 1], self.keys[0]),
+            n => (self.keys[n - 1], self.keys[n]),
+        };
+        let span = (end.0 - start.0).rem_euclid(1.0);
+        if span <= f32::EPSILON {
+            return start.1;
         }
+        let t = ((time - start.0).rem_euclid(1.0) / span).clamp(0.0, 1.0);
+        start.1.lerp(end.1, t * t * (3.0 - 2.0 * t))
+    }
+}
+
+fn srgb(r: i32, g: i32, b: i32) -> Radiance {
+    Radiance::from(Color::new(r, g, b))
+}
+
+// Ciclo de día y noche: la hora avanza con el tiempo real y de ella salen el sol,
+// la luna, el color del cielo y el nivel de luz ambiente
+pub struct DayNightCycle {
+    pub time: f32,     // Hora del día como fracción: 0.25 amanece, 0.5 mediodía, 0.75 anochece
+    pub duration: f32, // Segundos reales que dura un día completo a velocidad 1
+    pub speed: f32,
+    pub paused: bool,
+    pub sun_color: Gradient<Radiance>,
+    pub sun_intensity: Gradient<f32>,
+    pub sky_color: Gradient<Radiance>,
+    pub ambient: Gradient<f32>,
+    pub moon_color: Radiance,
+    pub moon_intensity: f32,
+    pub turbidity: f32, // Turbidez del aire para el cielo de Preetham: 2 es despejado, 10 brumoso
+    pending: f32,       // Avance desde la última vez que `advance` pidió volver a iluminar
+}
 
-        self.light.intensity = light_intensity; // Ajustar intensidad según el ángulo
+impl DayNightCycle {
+    pub fn new(duration: f32) -> Self {
+        let day_sky = srgb(130, 189, 188);
+        let night_sky = srgb(12, 16, 36);
+        let daylight = srgb(255, 250, 235);
+        let dusk = srgb(255, 150, 80);
+        DayNightCycle {
+            time: 0.0,
+            duration,
+            speed: 1.0,
+            paused: false,
+            sun_color: Gradient::new(vec![
+                (0.22, dusk),
+                (0.32, daylight),
+                (0.68, daylight),
+                (0.78, dusk),
+            ]),
+            sun_intensity: Gradient::new(vec![
+                (0.22, 0.0),
+                (0.3, 0.7),
+                (0.38, 1.0),
+                (0.62, 1.0),
+                (0.7, 0.7),
+                (0.78, 0.0),
+            ]),
+            sky_color: Gradient::new(vec![
+                (0.0, night_sky),
+                (0.21, night_sky),
+                (0.27, srgb(235, 150, 110)),
+                (0.35, day_sky),
+                (0.65, day_sky),
+                (0.73, srgb(235, 130, 80)),
+                (0.79, night_sky),
+            ]),
+            ambient: Gradient::new(vec![
+                (0.0, 0.08),
+                (0.25, 0.15),
+                (0.35, 0.3),
+                (0.65, 0.3),
+                (0.75, 0.15),
+            ]),
+            moon_color: srgb(150, 170, 255),
+            moon_intensity: 0.15,
+            turbidity: 2.5,
+            pending: 0.0,
+        }
+    }
+
+    // Avanza el reloj; devuelve si desde la última vez que lo hizo la hora avanzó lo
+    // bastante para que la luz se vea distinta
+    pub fn advance(&mut self, delta_time: f32) -> bool {
+        if self.paused || delta_time <= 0.0 || self.duration <= 0.0 {
+            return false;
+        }
+        let step = delta_time * self.speed / self.duration;
+        self.time = (self.time + step).rem_euclid(1.0);
+        self.pending += step;
+        if self.pending < RELIGHT_STEP {
+            return false;
+        }
+        self.pending = 0.0;
+        true
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    // Duplica o divide a la mitad la velocidad del ciclo
+    pub fn speed_up(&mut self) {
+        self.speed = (self.speed * 2.0).min(MAX_SPEED);
+    }
+
+    pub fn slow_down(&mut self) {
+        self.speed = (self.speed * 0.5).max(MIN_SPEED);
+    }
+
+    // Mueve la hora a mano, hacia adelante (pasos > 0) o hacia atrás
+    pub fn scrub(&mut self, steps: f32) {
+        self.time = (self.time + steps * SCRUB_STEP).rem_euclid(1.0);
+        self.pending = 0.0;
+    }
+
+    // Dirección unitaria hacia el sol: sale por +x al amanecer y está en lo más alto a mediodía
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = (self.time - 0.25) * 2.0 * PI;
+        Vec3::new(angle.cos(), angle.sin(), ORBIT_TILT).normalize()
     }
-}
\ No newline at end of file
+
+    // La luna queda en el lado opuesto del cielo
+    pub fn moon_direction(&self) -> Vec3 {
+        let sun = self.sun_direction();
+        Vec3::new(-sun.x, -sun.y, sun.z)
+    }
+
+    // Color base del cielo; de día el cielo de Preetham lo cubre y queda a la vista
+    // en el crepúsculo y la noche
+    pub fn sky_color(&self) -> Radiance {
+        self.sky_color.sample(self.time)
+    }
+
+    // Cielo de la hora actual, con el disco del sol, la luna y las estrellas girando
+    // con ellos
+    pub fn sky(&self) -> Sky {
+        Sky::new(self.sun_direction(), self.turbidity, self.sky_color())
+            .with_sun_disk(self.sun_color.sample(self.time), self.sun_intensity.sample(self.time))
+            .with_moon(self.moon_direction(), self.moon_color, self.moon_intensity)
+            .with_star_rotation((self.time - 0.25) * 2.0 * PI)
+    }
+
+    // Luz ambiente: el color del cielo escalado por el nivel de la hora
+    pub fn ambient_light(&self) -> Radiance {
+        self.sky_color() * self.ambient.sample(self.time)
+    }
+
+    // Sol y luna como luces direccionales; se omiten mientras están bajo el horizonte
+    pub fn lights(&self) -> Vec<Light> {
+        let mut lights = Vec::with_capacity(2);
+        let sun = self.sun_direction();
+        let sun_intensity = self.sun_intensity.sample(self.time) * horizon_fade(sun.y);
+        if sun_intensity > 0.0 {
+            lights.push(Light::directional(-sun, self.sun_color.sample(self.time), sun_intensity));
+        }
+        let moon = self.moon_direction();
+        let moon_intensity = self.moon_intensity * horizon_fade(moon.y);
+        if moon_intensity > 0.0 {
+            lights.push(Light::directional(-moon, self.moon_color, moon_intensity));
+        }
+        lights
+    }
+
+    // Hora del día como HH:MM
+    pub fn clock(&self) -> String {
+        let minutes = (self.time * 24.0 * 60.0) as u32 % (24 * 60);
+        format!("{:02}:{:02}", minutes / 60, minutes % 60)
+    }
+}
+
+// Atenúa un astro al acercarse al horizonte (altura = componente y de su dirección)
+fn horizon_fade(height: f32) -> f32 {
+    let t = (height / 0.1).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::Bvh;
+    use crate::cube::Cube;
+    use crate::integrator::{Integrator, RenderContext};
+    use crate::light::SceneLights;
+    use crate::materials::{Material, TextureManager};
+    use crate::ray_intersect::Object;
+    use crate::whitted::WhittedIntegrator;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_gradient_interpolates_and_wraps() {
+        let gradient = Gradient::new(vec![(0.25, 1.0), (0.75, 3.0)]);
+        assert_eq!(gradient.sample(0.25), 1.0);
+        assert_eq!(gradient.sample(0.5), 2.0);
+        assert_eq!(gradient.sample(0.75), 3.0);
+        // Tramo que cruza la medianoche, de 0.75 a 1.25
+        assert_eq!(gradient.sample(0.0), 2.0);
+        assert_eq!(gradient.sample(1.0), 2.0);
+        let early = gradient.sample(0.8);
+        assert!(early > 2.0 && early < 3.0);
+    }
+
+    #[test]
+    fn test_sun_by_day_and_moon_by_night() {
+        let mut cycle = DayNightCycle::new(10.0);
+        cycle.time = 0.5;
+        assert!(cycle.sun_direction().y > 0.9);
+        let lights = cycle.lights();
+        assert_eq!(lights.len(), 1);
+        assert!((lights[0].intensity - 1.0).abs() < 1e-5);
+
+        cycle.time = 0.0;
+        let lights = cycle.lights();
+        assert_eq!(lights.len(), 1);
+        assert_eq!(lights[0].color, cycle.moon_color);
+        assert!(cycle.sky_color().luminance() < 0.05);
+    }
+
+    #[test]
+    fn test_advance_pause_speed_and_scrub() {
+        let mut cycle = DayNightCycle::new(10.0);
+        assert!(cycle.advance(2.5));
+        assert!((cycle.time - 0.25).abs() < 1e-6);
+
+        cycle.speed_up();
+        cycle.advance(2.5);
+        assert!((cycle.time - 0.75).abs() < 1e-6);
+
+        cycle.toggle_pause();
+        assert!(!cycle.advance(1.0));
+        assert!((cycle.time - 0.75).abs() < 1e-6);
+
+        cycle.scrub(-12.0);
+        assert!((cycle.time - 0.5).abs() < 1e-6);
+        assert_eq!(cycle.clock(), "12:00");
+
+        // Los pasos chicos se juntan hasta que el sol se mueve lo bastante
+        cycle.toggle_pause();
+        assert!(!cycle.advance(0.05));
+        assert!(cycle.advance(0.01));
+        assert!((cycle.time - 0.512).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_sun_color_tints_diffuse_light() {
+        // Piso blanco sin brillo visto desde arriba al mediodía, sin cielo ni ambiente
+        let floor = Material::new(Radiance::new(1.0, 1.0, 1.0), 10.0, [1.0, 0.0], 0.0, 0.0, 1.0);
+        let floor = Cube::new(Vec3::new(-5.0, -1.0, -5.0), Vec3::new(5.0, 0.0, 5.0), floor);
+        let objects = Bvh::new(vec![Box::new(floor) as Object]);
+        let texture_manager = TextureManager::new();
+        let sky = Sky::uniform(Radiance::black());
+        let radiance = |cycle: &DayNightCycle| {
+            let lights = SceneLights::new(cycle.lights());
+            let context = RenderContext {
+                objects: &objects,
+                lights: &lights,
+                texture_manager: &texture_manager,
+                sky: &sky,
+                environment: None,
+                ambient: Radiance::black(),
+                pixel_spread: 0.0,
+            };
+            let origin = Vec3::new(0.0, 3.0, 0.0);
+            WhittedIntegrator::new().radiance(&origin, &Vec3::new(0.0, -1.0, 0.0), &context, &mut StdRng::seed_from_u64(3))
+        };
+
+        let mut cycle = DayNightCycle::new(10.0);
+        cycle.time = 0.5;
+        let white = radiance(&cycle);
+        cycle.sun_color = Gradient::new(vec![(0.0, Radiance::new(1.0, 0.2, 0.2))]);
+        let red = radiance(&cycle);
+        assert!(white.r > 0.1 && (red.r - white.r).abs() < 0.3 * white.r, "{:?} {:?}", white, red);
+        assert!(red.g < 0.5 * white.g && red.b < 0.5 * white.b, "{:?} {:?}", white, red);
+    }
+}
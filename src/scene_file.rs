@@ -0,0 +1,1563 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::sync::Arc;
+use nalgebra_glm::{self as glm, Vec3};
+use serde::Deserialize;
+use toml::Spanned;
+use crate::bvh::Bvh;
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::cube::Cube;
+use crate::environment::Environment;
+use crate::light::{Falloff, Light, LightShape};
+use crate::materials::{AlphaCutout, CubemapError, Material, TextureManager};
+use crate::radiance::Radiance;
+use crate::ray_intersect::{Object, RayIntersect};
+use crate::scene::{DayNightCycle, Gradient, Lerp};
+use crate::mesh::Mesh;
+use crate::obj::{default_material, load_obj};
+use crate::gltf::load_gltf;
+use crate::vox::load_vox;
+use crate::schematic::{load_schematic, placeholder_material};
+use crate::transformed::{affine, Transformed};
+use crate::sphere::Sphere;
+use crate::textures::{parse_atlas_layout, AtlasRect, Filter, Texture, WrapMode};
+use crate::voxel::{VoxelGrid, AIR};
+
+// Descripción de la escena tal como aparece en el archivo TOML
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    camera: CameraDef,
+    #[serde(default)]
+    cycle: CycleDef,
+    environment: Option<Spanned<EnvironmentDef>>,
+    #[serde(default)]
+    textures: BTreeMap<String, Spanned<String>>,
+    #[serde(default)]
+    atlases: BTreeMap<String, Spanned<AtlasDef>>,
+    #[serde(default)]
+    materials: BTreeMap<String, MaterialDef>,
+    #[serde(default)]
+    cubes: Vec<CubeDef>,
+    #[serde(default)]
+    spheres: Vec<SphereDef>,
+    #[serde(default)]
+    voxel_grids: Vec<VoxelGridDef>,
+    #[serde(default)]
+    meshes: Vec<Spanned<MeshDef>>,
+    #[serde(default)]
+    gltf: Vec<Spanned<GltfDef>>,
+    #[serde(default)]
+    vox: Vec<Spanned<VoxDef>>,
+    // Bloque de Minecraft ("minecraft:oak_log", "oak_log" o "17:0") -> nombre del material
+    #[serde(default)]
+    blocks: BTreeMap<String, Spanned<String>>,
+    #[serde(default)]
+    schematics: Vec<Spanned<SchematicDef>>,
+    #[serde(default)]
+    groups: BTreeMap<String, Spanned<GroupDef>>,
+    #[serde(default)]
+    instances: Vec<Spanned<InstanceDef>>,
+    #[serde(default)]
+    lights: Vec<Spanned<LightDef>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDef {
+    eye: [f32; 3],
+    center: [f32; 3],
+    #[serde(default = "default_up")]
+    up: [f32; 3],
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CycleDef {
+    duration: f32,   // Segundos que dura un día completo
+    #[serde(default)]
+    start: f32,      // Momento inicial como fracción del ciclo (0 = medianoche)
+    moon_color: Option<[u8; 3]>,
+    moon_intensity: Option<f32>,
+    turbidity: Option<f32>, // Bruma del cielo de Preetham, de 2 (despejado) a 10
+    // Claves de los degradados; cada una aporta solo los valores que define.
+    // Los degradados sin claves usan los del ciclo por defecto
+    #[serde(default)]
+    keyframes: Vec<KeyframeDef>,
+}
+
+impl Default for CycleDef {
+    fn default() -> Self {
+        CycleDef { duration: 10.0, start: 0.0, moon_color: None, moon_intensity: None, turbidity: None, keyframes: Vec::new() }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyframeDef {
+    time: f32, // Hora del día como fracción del ciclo
+    sun_color: Option<[u8; 3]>,
+    sun_intensity: Option<f32>,
+    sky_color: Option<[u8; 3]>,
+    ambient: Option<f32>,
+}
+
+// Mapa de entorno que reemplaza al cielo procedural: una imagen equirectangular
+// (.hdr, .png...) o las seis caras de un cubemap en el orden +x, -x, +y, -y, +z, -z
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentDef {
+    equirectangular: Option<String>,
+    cubemap: Option<[String; 6]>,
+    #[serde(default)]
+    rotation: f32, // Giro alrededor del eje y, en grados
+    #[serde(default = "default_environment_intensity")]
+    intensity: f32,
+}
+
+// Imagen con muchas texturas, como un paquete de texturas de bloques. Cada baldosa
+// se usa como la textura "atlas/baldosa"; su rectángulo sale de una rejilla de celdas
+// iguales o de un JSON con el formato de TexturePacker
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AtlasDef {
+    image: String,
+    tile_size: Option<[u32; 2]>, // Ancho y alto en píxeles de las celdas de la rejilla
+    #[serde(default)]
+    tiles: BTreeMap<String, [u32; 2]>, // Columna y fila de cada baldosa en la rejilla
+    layout: Option<String>,            // JSON con el rectángulo de cada baldosa
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialDef {
+    texture: Option<Spanned<String>>,
+    normal_map: Option<Spanned<String>>, // Textura de [textures] con normales en espacio tangente
+    alpha_cutoff: Option<Spanned<f32>>, // Los texeles con menos alfa que este umbral son huecos
+    filter: Option<Spanned<String>>, // "nearest", "bilinear" o "trilinear"
+    wrap: Option<Spanned<String>>,   // "repeat", "clamp" o "mirror"
+    #[serde(default = "default_texture_scale")]
+    texture_scale: Spanned<[f32; 2]>, // Repeticiones de la textura en u y v
+    #[serde(default)]
+    texture_offset: [f32; 2],
+    tile_size: Option<Spanned<f32>>, // Unidades del mundo por repetición, en lugar de una por cara
+    #[serde(default = "default_diffuse")]
+    diffuse: [u8; 3], // sRGB de 0 a 255, como Color
+    #[serde(default = "default_specular")]
+    specular: f32,
+    #[serde(default = "default_albedo")]
+    albedo: [f32; 2],
+    #[serde(default)]
+    reflectivity: f32,
+    #[serde(default)]
+    transparency: f32,
+    #[serde(default = "default_refraction_index")]
+    refraction_index: f32,
+    emissive: Option<[u8; 3]>,
+    #[serde(default)]
+    emissive_intensity: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CubeDef {
+    min: [f32; 3],
+    max: [f32; 3],
+    material: Spanned<String>,     // Lados, y las caras sin material propio
+    top: Option<Spanned<String>>,    // Cara superior
+    bottom: Option<Spanned<String>>, // Cara inferior
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SphereDef {
+    center: [f32; 3],
+    radius: Spanned<f32>,
+    material: Spanned<String>,
+}
+
+// Primitivas que se definen una vez y se colocan muchas con [[instances]]; las
+// coordenadas son relativas al origen del grupo
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GroupDef {
+    #[serde(default)]
+    cubes: Vec<CubeDef>,
+    #[serde(default)]
+    spheres: Vec<SphereDef>,
+    #[serde(default)]
+    meshes: Vec<Spanned<MeshDef>>,
+}
+
+// Copia de un grupo escalada, girada (grados alrededor de x, y y z, en ese orden) y
+// trasladada, que comparte la geometría con las demás
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InstanceDef {
+    group: Spanned<String>,
+    #[serde(default)]
+    position: [f32; 3],
+    #[serde(default)]
+    rotation: [f32; 3],
+    #[serde(default = "default_instance_scale")]
+    scale: ScaleDef,
+}
+
+// Escala igual en los tres ejes o una por eje
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScaleDef {
+    Uniform(f32),
+    Axes([f32; 3]),
+}
+
+// Modelo Wavefront .obj con los materiales de sus .mtl, movido y escalado
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MeshDef {
+    path: String,
+    material: Option<Spanned<String>>, // Reemplaza a los materiales del .mtl
+    #[serde(default)]
+    position: [f32; 3],
+    #[serde(default = "default_mesh_scale")]
+    scale: f32,
+}
+
+// Modelo glTF 2.0 (.gltf o .glb) con sus materiales, luces y cámara, movido y escalado
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GltfDef {
+    path: String,
+    #[serde(default)]
+    position: [f32; 3],
+    #[serde(default = "default_mesh_scale")]
+    scale: f32,
+    #[serde(default)]
+    use_camera: bool, // La primera cámara del modelo reemplaza a la de la escena
+    #[serde(default = "default_light_scale")]
+    light_scale: f32, // Multiplica la intensidad de las luces del modelo
+}
+
+// Modelo de MagicaVoxel (.vox) como rejilla de bloques con los materiales de su paleta
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VoxDef {
+    path: String,
+    #[serde(default)]
+    position: [f32; 3], // Dónde queda el origen de la escena de MagicaVoxel
+    voxel_size: f32,
+}
+
+// Construcción de Minecraft (.schem de Sponge o .schematic de MCEdit); los bloques
+// toman el material que les da [blocks]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SchematicDef {
+    path: String,
+    #[serde(default)]
+    position: [f32; 3], // Esquina mínima
+    voxel_size: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VoxelGridDef {
+    origin: [f32; 3],
+    voxel_size: Spanned<f32>,
+    // Carácter de las capas -> nombre del material
+    palette: BTreeMap<String, Spanned<String>>,
+    // Una capa por altura (de abajo arriba), una fila por z y un carácter por x; '.' es aire
+    layers: Spanned<Vec<Vec<String>>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LightDef {
+    #[serde(rename = "type")]
+    kind: Option<Spanned<String>>, // "point" (por defecto), "directional" o "spot"
+    position: Option<[f32; 3]>,    // Centro en las luces de área; no aplica a las direccionales
+    direction: Option<[f32; 3]>,   // Hacia donde viaja la luz (direccionales y focos)
+    inner_angle: Option<f32>,      // Conos de los focos, en grados desde el eje
+    outer_angle: Option<f32>,
+    falloff: Option<Spanned<FalloffDef>>,
+    color: [u8; 3],
+    intensity: f32,
+    edges: Option<[[f32; 3]; 2]>, // Lados de una luz rectangular
+    radius: Option<f32>,          // Radio de una luz esférica
+    #[serde(default = "default_light_samples")]
+    samples: u32,                 // Rayos de sombra por punto en las luces de área
+}
+
+// "constant", "inverse_square" o coeficientes [constante, lineal, cuadrático]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FalloffDef {
+    Name(String),
+    Coefficients([f32; 3]),
+}
+
+// Los colores del archivo son sRGB de 8 bits, igual que Color::new
+fn srgb(color: [u8; 3]) -> Radiance {
+    let [r, g, b] = color;
+    Radiance::from(Color::new(r as i32, g as i32, b as i32))
+}
+
+// Degradado con las claves que definen el valor, o el de por defecto si no hay ninguna
+fn keyframe_gradient<T: Lerp>(
+    keyframes: &[KeyframeDef],
+    value: impl Fn(&KeyframeDef) -> Option<T>,
+    default: Gradient<T>,
+) -> Gradient<T> {
+    let keys: Vec<(f32, T)> = keyframes
+        .iter()
+        .filter_map(|key| value(key).map(|v| (key.time, v)))
+        .collect();
+    if keys.is_empty() {
+        default
+    } else {
+        Gradient::new(keys)
+    }
+}
+
+fn build_cycle(def: &CycleDef) -> DayNightCycle {
+    let mut cycle = DayNightCycle::new(def.duration);
+    cycle.time = def.start.rem_euclid(1.0);
+    cycle.sun_color = keyframe_gradient(&def.keyframes, |key| key.sun_color.map(srgb), cycle.sun_color);
+    cycle.sun_intensity = keyframe_gradient(&def.keyframes, |key| key.sun_intensity, cycle.sun_intensity);
+    cycle.sky_color = keyframe_gradient(&def.keyframes, |key| key.sky_color.map(srgb), cycle.sky_color);
+    cycle.ambient = keyframe_gradient(&def.keyframes, |key| key.ambient, cycle.ambient);
+    if let Some(color) = def.moon_color {
+        cycle.moon_color = srgb(color);
+    }
+    if let Some(intensity) = def.moon_intensity {
+        cycle.moon_intensity = intensity;
+    }
+    if let Some(turbidity) = def.turbidity {
+        cycle.turbidity = turbidity.clamp(1.7, 10.0);
+    }
+    cycle
+}
+
+fn default_up() -> [f32; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_diffuse() -> [u8; 3] {
+    [255, 255, 255]
+}
+
+fn default_specular() -> f32 {
+    50.0
+}
+
+fn default_albedo() -> [f32; 2] {
+    [0.6, 0.3]
+}
+
+fn default_texture_scale() -> Spanned<[f32; 2]> {
+    Spanned::new(0..0, [1.0, 1.0])
+}
+
+fn default_mesh_scale() -> f32 {
+    1.0
+}
+
+fn default_light_scale() -> f32 {
+    1.0
+}
+
+fn default_instance_scale() -> ScaleDef {
+    ScaleDef::Uniform(1.0)
+}
+
+fn default_refraction_index() -> f32 {
+    1.0
+}
+
+fn default_environment_intensity() -> f32 {
+    1.0
+}
+
+fn default_light_samples() -> u32 {
+    8
+}
+
+// Escena lista para renderizar
+pub struct LoadedScene {
+    pub texture_manager: TextureManager,
+    pub objects: Bvh<Object>,
+    pub lights: Vec<Light>,
+    pub camera: Camera,
+    pub cycle: DayNightCycle,
+    pub environment: Option<Environment>,
+    pub warnings: Vec<String>, // Problemas que no impiden dibujar la escena
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io { path: String, message: String },
+    // Error con posición dentro del archivo (línea y columna empiezan en 1)
+    At { path: String, line: usize, column: usize, message: String },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, message } => write!(f, "{}: {}", path, message),
+            SceneError::At { path, line, column, message } => {
+                write!(f, "{}:{}:{}: {}", path, line, column, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+// Convierte un desplazamiento en bytes en línea y columna
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(source.len());
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rfind('\n').map_or(offset, |newline| offset - newline - 1) + 1;
+    (line, column)
+}
+
+struct Loader<'a> {
+    path: &'a str,
+    source: &'a str,
+}
+
+impl Loader<'_> {
+    fn error(&self, span: Range<usize>, message: String) -> SceneError {
+        let (line, column) = line_column(self.source, span.start);
+        SceneError::At { path: self.path.to_string(), line, column, message }
+    }
+
+    fn lookup<'m, T>(&self, map: &'m BTreeMap<String, T>, name: &Spanned<String>, kind: &str) -> Result<&'m T, SceneError> {
+        map.get(name.get_ref()).ok_or_else(|| {
+            let known: Vec<&str> = map.keys().map(|k| k.as_str()).collect();
+            self.error(
+                name.span(),
+                format!("{} desconocido '{}' (definidos: {})", kind, name.get_ref(), known.join(", ")),
+            )
+        })
+    }
+
+    fn load_textures(&self, file: &SceneFile, texture_manager: &mut TextureManager) -> Result<BTreeMap<String, usize>, SceneError> {
+        let mut indices = BTreeMap::new();
+        for (name, path) in &file.textures {
+            let index = texture_manager
+                .load_texture(path.get_ref())
+                .map_err(|e| self.error(path.span(), format!("no se pudo cargar la textura '{}' ({}): {}", name, path.get_ref(), e)))?;
+            indices.insert(name.clone(), index);
+        }
+        for (atlas_name, def) in &file.atlases {
+            for (tile_name, texture) in self.load_atlas(atlas_name, def)? {
+                indices.insert(format!("{}/{}", atlas_name, tile_name), texture_manager.add_texture(texture));
+            }
+        }
+        Ok(indices)
+    }
+
+    // Recorta cada baldosa del atlas en una textura propia, así el filtrado y los
+    // mipmaps no mezclan los bordes de las baldosas vecinas
+    fn load_atlas(&self, name: &str, def: &Spanned<AtlasDef>) -> Result<Vec<(String, Texture)>, SceneError> {
+        let atlas = def.get_ref();
+        let rects: Vec<(String, AtlasRect)> = match (&atlas.tile_size, &atlas.layout) {
+            (Some([width, height]), None) => atlas
+                .tiles
+                .iter()
+                .map(|(tile, [column, row])| {
+                    (tile.clone(), AtlasRect { x: column * width, y: row * height, width: *width, height: *height, rotated: false })
+                })
+                .collect(),
+            (None, Some(layout)) => {
+                let json = fs::read_to_string(layout)
+                    .map_err(|e| self.error(def.span(), format!("no se pudo leer el JSON del atlas '{}' ({}): {}", name, layout, e)))?;
+                parse_atlas_layout(&json)
+                    .map_err(|e| self.error(def.span(), format!("JSON del atlas '{}' inválido ({}): {}", name, layout, e)))?
+            }
+            _ => {
+                return Err(self.error(
+                    def.span(),
+                    format!("el atlas '{}' necesita 'tile_size' y 'tiles' o 'layout', pero no ambos", name),
+                ))
+            }
+        };
+
+        let image = Texture::new(&atlas.image)
+            .map_err(|e| self.error(def.span(), format!("no se pudo cargar el atlas '{}' ({}): {}", name, atlas.image, e)))?;
+        rects
+            .into_iter()
+            .map(|(tile, rect)| match image.tile(&rect) {
+                Some(texture) => Ok((tile, texture)),
+                None => Err(self.error(
+                    def.span(),
+                    format!(
+                        "la baldosa '{}' del atlas '{}' ({}x{} en {}, {}) no cabe en la imagen de {}x{}",
+                        tile, name, rect.width, rect.height, rect.x, rect.y, image.width, image.height
+                    ),
+                )),
+            })
+            .collect()
+    }
+
+    fn build_environment(&self, def: &Spanned<EnvironmentDef>, texture_manager: &mut TextureManager) -> Result<Environment, SceneError> {
+        let environment = def.get_ref();
+        let map = match (&environment.equirectangular, &environment.cubemap) {
+            (Some(path), None) => texture_manager
+                .load_equirectangular(path)
+                .map_err(|e| self.error(def.span(), format!("no se pudo cargar el mapa de entorno ({}): {}", path, e)))?,
+            (None, Some(paths)) => texture_manager.load_cubemap(paths).map_err(|e| match e {
+                CubemapError::Image { face, error } => self.error(
+                    def.span(),
+                    format!("no se pudo cargar la cara {} del cubemap ({}): {}", face + 1, paths[face], error),
+                ),
+                CubemapError::Size { face, width, height, expected } => self.error(
+                    def.span(),
+                    format!(
+                        "la cara {} del cubemap ({}) mide {}x{}, pero las seis caras deben ser cuadradas de {}x{}",
+                        face + 1, paths[face], width, height, expected, expected
+                    ),
+                ),
+            })?,
+            _ => {
+                return Err(self.error(
+                    def.span(),
+                    "el entorno necesita 'equirectangular' o 'cubemap', pero no ambos".to_string(),
+                ))
+            }
+        };
+        if environment.intensity < 0.0 {
+            return Err(self.error(def.span(), "la intensidad del entorno no puede ser negativa".to_string()));
+        }
+        Ok(Environment::new(map, environment.rotation.to_radians(), environment.intensity))
+    }
+
+    // Los mapas de normales guardan direcciones y no colores, así que las texturas
+    // que los materiales usan como tales se cargan otra vez sin pasar de sRGB a lineal
+    fn load_normal_maps(&self, file: &SceneFile, texture_manager: &mut TextureManager) -> Result<BTreeMap<String, usize>, SceneError> {
+        let mut indices = BTreeMap::new();
+        for name in file.materials.values().filter_map(|def| def.normal_map.as_ref()) {
+            if indices.contains_key(name.get_ref()) {
+                continue;
+            }
+            let path = self.lookup(&file.textures, name, "textura")?;
+            let index = texture_manager
+                .load_normal_map(path.get_ref())
+                .map_err(|e| self.error(path.span(), format!("no se pudo cargar el mapa de normales '{}' ({}): {}", name.get_ref(), path.get_ref(), e)))?;
+            indices.insert(name.get_ref().clone(), index);
+        }
+        Ok(indices)
+    }
+
+    fn build_materials(
+        &self,
+        file: &SceneFile,
+        textures: &BTreeMap<String, usize>,
+        normal_maps: &BTreeMap<String, usize>,
+        texture_manager: &TextureManager,
+    ) -> Result<BTreeMap<String, Material>, SceneError> {
+        let mut materials = BTreeMap::new();
+        for (id, (name, def)) in file.materials.iter().enumerate() {
+            let mut material = match &def.texture {
+                Some(texture) => {
+                    let index = *self.lookup(textures, texture, "textura")?;
+                    Material::new_with_texture(index, def.specular, def.albedo, def.refraction_index)
+                }
+                None => Material::new(
+                    srgb(def.diffuse),
+                    def.specular,
+                    def.albedo,
+                    def.reflectivity,
+                    def.transparency,
+                    def.refraction_index,
+                ),
+            };
+            material.reflectivity = def.reflectivity;
+            material.transparency = def.transparency;
+            if let Some(normal_map) = &def.normal_map {
+                material.normal_map = Some(*self.lookup(normal_maps, normal_map, "textura")?);
+            }
+            if let Some(filter) = &def.filter {
+                material.sampler.filter = match filter.get_ref().as_str() {
+                    "nearest" => Filter::Nearest,
+                    "bilinear" => Filter::Bilinear,
+                    "trilinear" => Filter::Trilinear,
+                    name => {
+                        return Err(self.error(
+                            filter.span(),
+                            format!("filtro desconocido '{}' (nearest, bilinear o trilinear)", name),
+                        ))
+                    }
+                };
+            }
+            if let Some(wrap) = &def.wrap {
+                material.sampler.wrap = match wrap.get_ref().as_str() {
+                    "repeat" => WrapMode::Repeat,
+                    "clamp" => WrapMode::Clamp,
+                    "mirror" => WrapMode::Mirror,
+                    name => {
+                        return Err(self.error(
+                            wrap.span(),
+                            format!("modo de repetición desconocido '{}' (repeat, clamp o mirror)", name),
+                        ))
+                    }
+                };
+            }
+            let [scale_u, scale_v] = *def.texture_scale.get_ref();
+            if scale_u == 0.0 || scale_v == 0.0 {
+                return Err(self.error(def.texture_scale.span(), "la escala de la textura no puede ser cero".to_string()));
+            }
+            material.texture_scale = [scale_u, scale_v];
+            material.texture_offset = def.texture_offset;
+            if let Some(tile_size) = &def.tile_size {
+                if *tile_size.get_ref() <= 0.0 {
+                    return Err(self.error(tile_size.span(), "el tamaño de la baldosa debe ser positivo".to_string()));
+                }
+                material.tile_size = Some(*tile_size.get_ref());
+            }
+            if let Some(cutoff) = &def.alpha_cutoff {
+                let index = material.texture_index.ok_or_else(|| {
+                    self.error(cutoff.span(), format!("el material '{}' necesita una textura para usar 'alpha_cutoff'", name))
+                })?;
+                material.alpha_cutout = Some(AlphaCutout {
+                    texture: Arc::clone(texture_manager.get_texture(index)),
+                    threshold: *cutoff.get_ref(),
+                });
+            }
+            if let Some(emissive) = def.emissive {
+                material.emissive_color = Some(srgb(emissive));
+                material.emissive_intensity = def.emissive_intensity;
+            }
+            material.id = id;
+            materials.insert(name.clone(), material);
+        }
+        Ok(materials)
+    }
+
+    fn build_mesh(
+        &self,
+        def: &Spanned<MeshDef>,
+        materials: &BTreeMap<String, Material>,
+        texture_manager: &mut TextureManager,
+        next_material_id: &mut usize,
+        warnings: &mut Vec<String>,
+    ) -> Result<Mesh, SceneError> {
+        let mesh = def.get_ref();
+        if mesh.scale <= 0.0 {
+            return Err(self.error(def.span(), "la escala de la malla debe ser positiva".to_string()));
+        }
+        let material = match &mesh.material {
+            Some(name) => Some(self.lookup(materials, name, "material")?.clone()),
+            None => None,
+        };
+        let model = load_obj(&mesh.path, texture_manager, material.clone().unwrap_or_else(default_material), *next_material_id)
+            .map_err(|e| self.error(def.span(), format!("no se pudo cargar la malla: {}", e)))?;
+        *next_material_id += model.material_count;
+        if material.is_none() {
+            warnings.extend(model.warnings.into_iter().map(|warning| format!("{}: {}", mesh.path, warning)));
+        }
+        if model.triangles.is_empty() {
+            return Err(self.error(def.span(), format!("la malla {} no tiene caras", mesh.path)));
+        }
+
+        // Con un material en la escena todas las caras lo usan, el primero de la tabla
+        let position = Vec3::from(mesh.position);
+        let triangles = model
+            .triangles
+            .into_iter()
+            .map(|mut triangle| {
+                triangle.vertices = triangle.vertices.map(|vertex| vertex * mesh.scale + position);
+                if material.is_some() {
+                    triangle.material = 0;
+                }
+                triangle
+            })
+            .collect();
+        let materials = match material {
+            Some(material) => vec![material],
+            None => model.materials,
+        };
+        Ok(Mesh::new(triangles, materials))
+    }
+
+    fn build_cube(&self, cube: &CubeDef, materials: &BTreeMap<String, Material>) -> Result<Cube, SceneError> {
+        let material = self.lookup(materials, &cube.material, "material")?.clone();
+        let face = |name: &Option<Spanned<String>>| match name {
+            Some(name) => self.lookup(materials, name, "material").map(|material| Some(material.clone())),
+            None => Ok(None),
+        };
+        Ok(Cube {
+            top: face(&cube.top)?,
+            bottom: face(&cube.bottom)?,
+            ..Cube::new(Vec3::from(cube.min), Vec3::from(cube.max), material)
+        })
+    }
+
+    fn build_sphere(&self, sphere: &SphereDef, materials: &BTreeMap<String, Material>) -> Result<Sphere, SceneError> {
+        let radius = *sphere.radius.get_ref();
+        if radius <= 0.0 {
+            return Err(self.error(sphere.radius.span(), "el radio de la esfera debe ser positivo".to_string()));
+        }
+        let material = self.lookup(materials, &sphere.material, "material")?.clone();
+        Ok(Sphere {
+            center: Vec3::from(sphere.center),
+            radius,
+            material,
+        })
+    }
+
+    fn build_voxel_grid(&self, def: &VoxelGridDef, materials: &BTreeMap<String, Material>) -> Result<VoxelGrid, SceneError> {
+        let voxel_size = *def.voxel_size.get_ref();
+        if voxel_size <= 0.0 {
+            return Err(self.error(def.voxel_size.span(), "el tamaño de los vóxeles debe ser positivo".to_string()));
+        }
+        let layers = def.layers.get_ref();
+        let height = layers.len();
+        let depth = layers.iter().map(|rows| rows.len()).max().unwrap_or(0);
+        let width = layers.iter().flatten().map(|row| row.chars().count()).max().unwrap_or(0);
+        if height == 0 || depth == 0 || width == 0 {
+            return Err(self.error(def.layers.span(), "la rejilla de vóxeles no tiene bloques".to_string()));
+        }
+
+        let mut grid = VoxelGrid::new(Vec3::from(def.origin), voxel_size, [width, height, depth]);
+        let mut ids = BTreeMap::new();
+        for (symbol, material_name) in &def.palette {
+            let mut chars = symbol.chars();
+            let symbol = match (chars.next(), chars.next()) {
+                (Some(c), None) if c != '.' => c,
+                _ => {
+                    return Err(self.error(
+                        material_name.span(),
+                        format!("la clave '{}' de la paleta debe ser un solo carácter distinto de '.'", symbol),
+                    ))
+                }
+            };
+            let material = self.lookup(materials, material_name, "material")?.clone();
+            ids.insert(symbol, grid.add_material(material));
+        }
+
+        for (y, rows) in layers.iter().enumerate() {
+            for (z, row) in rows.iter().enumerate() {
+                for (x, symbol) in row.chars().enumerate() {
+                    let id = match symbol {
+                        '.' | ' ' => AIR,
+                        _ => *ids.get(&symbol).ok_or_else(|| {
+                            self.error(
+                                def.layers.span(),
+                                format!("el carácter '{}' (capa {}, fila {}) no está en la paleta", symbol, y, z),
+                            )
+                        })?,
+                    };
+                    grid.set(x, y, z, id);
+                }
+            }
+        }
+        Ok(grid)
+    }
+
+    fn build_light(&self, def: &Spanned<LightDef>) -> Result<Light, SceneError> {
+        let light = def.get_ref();
+        let color = srgb(light.color);
+        let kind = light.kind.as_ref().map_or("point", |kind| kind.get_ref().as_str());
+        let required = |value: Option<[f32; 3]>, field: &str| {
+            value
+                .map(Vec3::from)
+                .ok_or_else(|| self.error(def.span(), format!("una luz de tipo '{}' necesita '{}'", kind, field)))
+        };
+
+        let mut built = match kind {
+            "directional" => {
+                if light.position.is_some() || light.edges.is_some() || light.radius.is_some() {
+                    return Err(self.error(def.span(), "una luz direccional no tiene posición ni forma".to_string()));
+                }
+                return Ok(Light::directional(required(light.direction, "direction")?, color, light.intensity));
+            }
+            "point" => Light::new(required(light.position, "position")?, color, light.intensity),
+            "spot" => {
+                let (inner, outer) = match (light.inner_angle, light.outer_angle) {
+                    (Some(inner), Some(outer)) if 0.0 <= inner && inner <= outer && outer < 90.0 => (inner, outer),
+                    _ => {
+                        return Err(self.error(
+                            def.span(),
+                            "un foco necesita 'inner_angle' <= 'outer_angle' en grados, menores que 90".to_string(),
+                        ))
+                    }
+                };
+                Light::spot(
+                    required(light.position, "position")?,
+                    required(light.direction, "direction")?,
+                    inner.to_radians(),
+                    outer.to_radians(),
+                    color,
+                    light.intensity,
+                )
+            }
+            other => {
+                let span = light.kind.as_ref().map_or(def.span(), |kind| kind.span());
+                return Err(self.error(span, format!("tipo de luz desconocido '{}' (point, directional o spot)", other)));
+            }
+        };
+
+        if let Some(falloff) = &light.falloff {
+            built.falloff = match falloff.get_ref() {
+                FalloffDef::Name(name) if name == "constant" => Falloff::Constant,
+                FalloffDef::Name(name) if name == "inverse_square" => Falloff::InverseSquare,
+                FalloffDef::Coefficients([constant, linear, quadratic]) => Falloff::Custom {
+                    constant: *constant,
+                    linear: *linear,
+                    quadratic: *quadratic,
+                },
+                FalloffDef::Name(name) => {
+                    return Err(self.error(
+                        falloff.span(),
+                        format!("atenuación desconocida '{}' (constant, inverse_square o [c, l, q])", name),
+                    ))
+                }
+            };
+        }
+
+        built.shape = match (light.edges, light.radius) {
+            (None, None) => return Ok(built),
+            (Some([u, v]), None) => LightShape::Rectangle { u: Vec3::from(u), v: Vec3::from(v) },
+            (None, Some(radius)) if radius > 0.0 => LightShape::Sphere { radius },
+            (None, Some(_)) => return Err(self.error(def.span(), "el radio de la luz debe ser positivo".to_string())),
+            (Some(_), Some(_)) => {
+                return Err(self.error(def.span(), "una luz no puede tener 'edges' y 'radius' a la vez".to_string()))
+            }
+        };
+        if light.samples == 0 {
+            return Err(self.error(def.span(), "una luz de área necesita al menos una muestra".to_string()));
+        }
+        built.samples = light.samples;
+        Ok(built)
+    }
+
+    fn build(&self, file: SceneFile) -> Result<LoadedScene, SceneError> {
+        let mut texture_manager = TextureManager::new();
+        let textures = self.load_textures(&file, &mut texture_manager)?;
+        let normal_maps = self.load_normal_maps(&file, &mut texture_manager)?;
+        let materials = self.build_materials(&file, &textures, &normal_maps, &texture_manager)?;
+
+        let mut primitives: Vec<Object> = Vec::new();
+        for cube in &file.cubes {
+            primitives.push(Box::new(self.build_cube(cube, &materials)?));
+        }
+        for sphere in &file.spheres {
+            primitives.push(Box::new(self.build_sphere(sphere, &materials)?));
+        }
+        for grid in &file.voxel_grids {
+            primitives.push(Box::new(self.build_voxel_grid(grid, &materials)?));
+        }
+        let mut next_material_id = materials.len();
+        let mut warnings = Vec::new();
+        for mesh in &file.meshes {
+            primitives.push(Box::new(self.build_mesh(mesh, &materials, &mut texture_manager, &mut next_material_id, &mut warnings)?));
+        }
+
+        // Cada grupo se construye una vez y sus instancias lo comparten
+        let mut groups = BTreeMap::new();
+        for (name, def) in &file.groups {
+            let group = def.get_ref();
+            let mut members: Vec<Object> = Vec::new();
+            for cube in &group.cubes {
+                members.push(Box::new(self.build_cube(cube, &materials)?));
+            }
+            for sphere in &group.spheres {
+                members.push(Box::new(self.build_sphere(sphere, &materials)?));
+            }
+            for mesh in &group.meshes {
+                members.push(Box::new(self.build_mesh(mesh, &materials, &mut texture_manager, &mut next_material_id, &mut warnings)?));
+            }
+            if members.is_empty() {
+                return Err(self.error(def.span(), format!("el grupo '{}' no tiene primitivas", name)));
+            }
+            groups.insert(name.clone(), Arc::new(Bvh::new(members)));
+        }
+        for def in &file.instances {
+            let instance = def.get_ref();
+            let group = self.lookup(&groups, &instance.group, "grupo")?;
+            let scale = match instance.scale {
+                ScaleDef::Uniform(scale) => Vec3::repeat(scale),
+                ScaleDef::Axes(scale) => Vec3::from(scale),
+            };
+            let transform = affine(Vec3::from(instance.position), Vec3::from(instance.rotation), scale);
+            let transformed = Transformed::new(Arc::clone(group), transform)
+                .ok_or_else(|| self.error(def.span(), "la escala de la instancia no puede ser cero".to_string()))?;
+            primitives.push(Box::new(transformed));
+        }
+
+        for def in &file.vox {
+            let model = def.get_ref();
+            if model.voxel_size <= 0.0 {
+                return Err(self.error(def.span(), "el tamaño de los vóxeles debe ser positivo".to_string()));
+            }
+            let loaded = load_vox(&model.path, Vec3::from(model.position), model.voxel_size, next_material_id)
+                .map_err(|e| self.error(def.span(), format!("no se pudo cargar el modelo: {}", e)))?;
+            next_material_id += loaded.material_count;
+            warnings.extend(loaded.warnings.into_iter().map(|warning| format!("{}: {}", model.path, warning)));
+            primitives.push(Box::new(loaded.grid));
+        }
+
+        if !file.schematics.is_empty() {
+            let mut blocks = HashMap::new();
+            for (block, material) in &file.blocks {
+                blocks.insert(block.clone(), self.lookup(&materials, material, "material")?.clone());
+            }
+            let mut placeholder = placeholder_material(&mut texture_manager);
+            placeholder.id = next_material_id;
+            next_material_id += 1;
+            for def in &file.schematics {
+                let build = def.get_ref();
+                if build.voxel_size <= 0.0 {
+                    return Err(self.error(def.span(), "el tamaño de los bloques debe ser positivo".to_string()));
+                }
+                let schematic = load_schematic(&build.path, &blocks, placeholder.clone(), Vec3::from(build.position), build.voxel_size)
+                    .map_err(|e| self.error(def.span(), format!("no se pudo cargar la construcción: {}", e)))?;
+                warnings.extend(schematic.summary().map(|summary| format!("{}: {}", build.path, summary)));
+                primitives.push(Box::new(schematic.grid));
+            }
+        }
+
+        let mut lights = file
+            .lights
+            .iter()
+            .map(|light| self.build_light(light))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut camera = Camera::new(
+            Vec3::from(file.camera.eye),
+            Vec3::from(file.camera.center),
+            Vec3::from(file.camera.up),
+        );
+
+        for def in &file.gltf {
+            let model = def.get_ref();
+            if model.scale <= 0.0 {
+                return Err(self.error(def.span(), "la escala del modelo debe ser positiva".to_string()));
+            }
+            let transform = glm::translation(&Vec3::from(model.position)) * glm::scaling(&Vec3::repeat(model.scale));
+            let loaded = load_gltf(&model.path, &mut texture_manager, &transform, default_material(), next_material_id)
+                .map_err(|e| self.error(def.span(), format!("no se pudo cargar el modelo: {}", e)))?;
+            next_material_id += loaded.material_count;
+            warnings.extend(loaded.warnings.into_iter().map(|warning| format!("{}: {}", model.path, warning)));
+            for mesh in loaded.meshes {
+                primitives.push(Box::new(mesh));
+            }
+            lights.extend(loaded.lights.into_iter().map(|light| Light { intensity: light.intensity * model.light_scale, ..light }));
+            match (model.use_camera, loaded.camera) {
+                (true, Some(model_camera)) => camera = model_camera,
+                (true, None) => warnings.push(format!("{}: el modelo no tiene cámara; se usa la de la escena", model.path)),
+                (false, _) => {}
+            }
+        }
+
+        let cycle = build_cycle(&file.cycle);
+        let environment = match &file.environment {
+            Some(def) => Some(self.build_environment(def, &mut texture_manager)?),
+            None => None,
+        };
+
+        // Las primitivas emisivas iluminan la escena sin declarar una luz a mano
+        let objects = Bvh::new(primitives);
+        lights.extend(objects.emitters());
+
+        Ok(LoadedScene {
+            texture_manager,
+            objects,
+            lights,
+            camera,
+            cycle,
+            environment,
+            warnings,
+        })
+    }
+}
+
+// Lee y valida un archivo de escena; los errores indican archivo, línea y columna
+pub fn load_scene(path: &str) -> Result<LoadedScene, SceneError> {
+    let source = fs::read_to_string(path).map_err(|e| SceneError::Io {
+        path: path.to_string(),
+        message: format!("no se pudo leer el archivo de escena: {}", e),
+    })?;
+    parse_scene(path, &source)
+}
+
+fn parse_scene(path: &str, source: &str) -> Result<LoadedScene, SceneError> {
+    let loader = Loader { path, source };
+    let file: SceneFile = toml::from_str(source).map_err(|e| {
+        let span = e.span().unwrap_or(0..0);
+        loader.error(span, e.message().to_string())
+    })?;
+    loader.build(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::LightKind;
+
+    fn parse(source: &str) -> Result<LoadedScene, SceneError> {
+        parse_scene("prueba.toml", source)
+    }
+
+    #[test]
+    fn test_line_column() {
+        let source = "a = 1\nbb = 2\n";
+        assert_eq!(line_column(source, 0), (1, 1));
+        assert_eq!(line_column(source, 6), (2, 1));
+        assert_eq!(line_column(source, 9), (2, 4));
+    }
+
+    #[test]
+    fn test_minimal_scene() {
+        let loaded = parse(
+            r##"
+[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[materials.red]
+diffuse = [255, 0, 0]
+
+[[cubes]]
+min = [-1.0, -1.0, -1.0]
+max = [1.0, 1.0, 1.0]
+material = "red"
+
+[[voxel_grids]]
+origin = [2.0, 0.0, 0.0]
+voxel_size = 1.0
+palette = { "#" = "red" }
+layers = [["#.#", "..#"], ["#"]]
+
+[[lights]]
+position = [0.0, 5.0, 0.0]
+color = [255, 255, 255]
+intensity = 1.0
+"##,
+        )
+        .unwrap();
+        assert_eq!(loaded.objects.len(), 2);
+        assert_eq!(loaded.lights.len(), 1);
+    }
+
+    #[test]
+    fn test_cycle_keyframes() {
+        let loaded = parse(
+            r#"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[cycle]
+duration = 60.0
+start = 0.5
+moon_intensity = 0.3
+turbidity = 4.0
+
+[[cycle.keyframes]]
+time = 0.25
+ambient = 0.1
+sky_color = [255, 0, 0]
+
+[[cycle.keyframes]]
+time = 0.75
+ambient = 0.5
+"#,
+        )
+        .unwrap();
+        let cycle = &loaded.cycle;
+        assert_eq!(cycle.time, 0.5);
+        assert_eq!(cycle.moon_intensity, 0.3);
+        assert_eq!(cycle.turbidity, 4.0);
+        assert!((cycle.ambient.sample(0.5) - 0.3).abs() < 1e-6);
+        // Una sola clave de cielo deja el color fijo todo el día
+        assert_eq!(cycle.sky_color.sample(0.9), srgb([255, 0, 0]));
+        // El sol sigue con el degradado por defecto
+        assert!(cycle.sun_intensity.sample(0.5) > 0.9);
+    }
+
+    #[test]
+    fn test_light_types() {
+        let loaded = parse(
+            r#"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[[lights]]
+type = "directional"
+direction = [0.0, -2.0, 0.0]
+color = [255, 255, 255]
+intensity = 1.0
+
+[[lights]]
+type = "spot"
+position = [0.0, 5.0, 0.0]
+direction = [0.0, -1.0, 0.0]
+inner_angle = 20.0
+outer_angle = 30.0
+color = [255, 255, 255]
+intensity = 10.0
+
+[[lights]]
+position = [0.0, 5.0, 0.0]
+color = [255, 255, 255]
+intensity = 1.0
+falloff = [1.0, 0.0, 0.25]
+"#,
+        )
+        .unwrap();
+        assert_eq!(loaded.lights[0].kind, LightKind::Directional { direction: Vec3::new(0.0, -1.0, 0.0) });
+        match loaded.lights[1].kind {
+            LightKind::Spot { inner_angle, outer_angle, .. } => {
+                assert!((inner_angle.to_degrees() - 20.0).abs() < 1e-4);
+                assert!((outer_angle.to_degrees() - 30.0).abs() < 1e-4);
+            }
+            other => panic!("tipo inesperado {:?}", other),
+        }
+        assert_eq!(loaded.lights[1].falloff, Falloff::InverseSquare);
+        assert_eq!(loaded.lights[2].falloff, Falloff::Custom { constant: 1.0, linear: 0.0, quadratic: 0.25 });
+
+        let error = parse(
+            r#"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[[lights]]
+type = "laser"
+position = [0.0, 5.0, 0.0]
+color = [255, 255, 255]
+intensity = 1.0
+"#,
+        )
+        .err()
+        .unwrap();
+        assert!(error.to_string().starts_with("prueba.toml:6:8:"), "{}", error);
+    }
+
+    #[test]
+    fn test_area_lights() {
+        let loaded = parse(
+            r#"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[[lights]]
+position = [0.0, 5.0, 0.0]
+color = [255, 255, 255]
+intensity = 1.0
+edges = [[2.0, 0.0, 0.0], [0.0, 0.0, 2.0]]
+samples = 16
+
+[[lights]]
+position = [3.0, 5.0, 0.0]
+color = [255, 200, 150]
+intensity = 0.5
+radius = 0.5
+"#,
+        )
+        .unwrap();
+        assert_eq!(loaded.lights[0].shape, LightShape::Rectangle { u: Vec3::new(2.0, 0.0, 0.0), v: Vec3::new(0.0, 0.0, 2.0) });
+        assert_eq!(loaded.lights[0].sample_count(), 16);
+        assert_eq!(loaded.lights[1].shape, LightShape::Sphere { radius: 0.5 });
+        assert_eq!(loaded.lights[1].sample_count(), 8);
+
+        let error = parse(
+            r#"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[[lights]]
+position = [0.0, 5.0, 0.0]
+color = [255, 255, 255]
+intensity = 1.0
+radius = 0.5
+samples = 0
+"#,
+        )
+        .err()
+        .unwrap();
+        assert!(error.to_string().starts_with("prueba.toml:5:"), "{}", error);
+    }
+
+    #[test]
+    fn test_environment_map() {
+        let path = std::env::temp_dir().join("escena_prueba_entorno.hdr");
+        let image = image::ImageBuffer::from_pixel(8, 4, image::Rgb([0.5f32, 1.0, 2.0]));
+        image::DynamicImage::ImageRgb32F(image).save(&path).unwrap();
+        let loaded = parse(&format!(
+            r#"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[environment]
+equirectangular = "{}"
+rotation = 90.0
+intensity = 2.0
+"#,
+            path.display()
+        ))
+        .unwrap();
+        let radiance = loaded.environment.unwrap().radiance(&Vec3::new(0.0, 1.0, 0.0));
+        assert!((radiance.b - 4.0).abs() < 1e-3);
+
+        let error = parse(
+            r#"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[environment]
+intensity = 2.0
+"#,
+        )
+        .err()
+        .unwrap();
+        assert!(error.to_string().starts_with("prueba.toml:5:"), "{}", error);
+
+        // Un cubemap con una cara rectangular no se carga
+        let square = std::env::temp_dir().join("escena_prueba_cara.png");
+        let wide = std::env::temp_dir().join("escena_prueba_cara_ancha.png");
+        image::RgbImage::from_pixel(4, 4, image::Rgb([10, 20, 30])).save(&square).unwrap();
+        image::RgbImage::from_pixel(8, 4, image::Rgb([10, 20, 30])).save(&wide).unwrap();
+        let (square, wide) = (square.display(), wide.display());
+        let error = parse(&format!(
+            r#"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[environment]
+cubemap = ["{square}", "{square}", "{wide}", "{square}", "{square}", "{square}"]
+"#
+        ))
+        .err()
+        .unwrap();
+        assert!(error.to_string().contains("cara 3 del cubemap"), "{}", error);
+        assert!(error.to_string().contains("8x4"), "{}", error);
+    }
+
+    #[test]
+    fn test_normal_map() {
+        let loaded = parse(
+            r#"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[textures]
+ball = "assets/ball.png"
+ball_normal = "assets/ball_normal.png"
+
+[materials.ball]
+texture = "ball"
+normal_map = "ball_normal"
+
+[[spheres]]
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "ball"
+"#,
+        )
+        .unwrap();
+        let direction = Vec3::new(0.05, 0.1, -1.0).normalize();
+        let hit = loaded.objects.closest_hit(&Vec3::new(0.0, 0.0, 5.0), &direction);
+        assert!(hit.is_intersecting);
+        assert!(hit.material.normal_map.is_some() && hit.material.normal_map != hit.material.texture_index);
+        // Marco tangente ortonormal alrededor de la normal geométrica
+        assert!(hit.tangent.dot(&hit.normal).abs() < 1e-5 && hit.bitangent.dot(&hit.normal).abs() < 1e-5);
+        assert!((hit.tangent.norm() - 1.0).abs() < 1e-5 && (hit.bitangent.norm() - 1.0).abs() < 1e-5);
+        let shading = hit.shading_normal(&loaded.texture_manager);
+        assert!((shading.norm() - 1.0).abs() < 1e-5);
+        assert!(shading.dot(&hit.normal) > 0.0);
+
+        let error = parse(
+            r#"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[materials.ball]
+normal_map = "bumps"
+"#,
+        )
+        .err()
+        .unwrap();
+        assert!(error.to_string().contains("'bumps'"), "{}", error);
+    }
+
+    #[test]
+    fn test_alpha_cutoff() {
+        // Textura 4x1: la mitad izquierda transparente y la derecha opaca
+        let path = std::env::temp_dir().join("escena_prueba_recorte.png");
+        let image = image::ImageBuffer::from_fn(4, 1, |x, _| image::Rgba([0u8, 200, 0, if x < 2 { 0 } else { 255 }]));
+        image::DynamicImage::ImageRgba8(image).save(&path).unwrap();
+        let source = format!(
+            r#"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[textures]
+leaves = "{}"
+
+[materials.leaves]
+texture = "leaves"
+alpha_cutoff = 0.5
+
+[[cubes]]
+min = [-1.0, -1.0, -1.0]
+max = [1.0, 1.0, 1.0]
+material = "leaves"
+"#,
+            path.display()
+        );
+        let loaded = parse(&source).unwrap();
+        let direction = Vec3::new(0.0, 0.0, -1.0);
+
+        // Por los huecos de las caras delantera y trasera el rayo cruza el cubo entero,
+        // también como rayo de sombra
+        let through_hole = Vec3::new(-0.5, 0.0, 5.0);
+        assert!(!loaded.objects.closest_hit(&through_hole, &direction).is_intersecting);
+        assert!(!loaded.objects.any_hit(&through_hole, &direction, f32::INFINITY));
+
+        let solid = Vec3::new(0.5, 0.0, 5.0);
+        let hit = loaded.objects.closest_hit(&solid, &direction);
+        assert!(hit.is_intersecting && (hit.distance - 4.0).abs() < 1e-4);
+        assert!(loaded.objects.any_hit(&solid, &direction, f32::INFINITY));
+
+        let error = parse(&source.replace("texture = \"leaves\"\n", "")).err().unwrap();
+        assert!(error.to_string().contains("alpha_cutoff"), "{}", error);
+    }
+
+    #[test]
+    fn test_face_materials_and_tiling() {
+        let loaded = parse(
+            r#"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[textures]
+grass = "assets/grass.jpg"
+
+[materials.grass]
+texture = "grass"
+tile_size = 0.5
+texture_offset = [0.25, 0.0]
+
+[materials.dirt]
+diffuse = [120, 80, 50]
+texture_scale = [2.0, 3.0]
+
+[[cubes]]
+min = [-5.0, -1.0, -5.0]
+max = [5.0, 0.0, 5.0]
+material = "dirt"
+top = "grass"
+"#,
+        )
+        .unwrap();
+        let top = loaded.objects.closest_hit(&Vec3::new(1.3, 2.0, 0.2), &Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(top.material.tile_size, Some(0.5));
+        // Dos baldosas por unidad del mundo, medidas desde el origen
+        assert!((top.u - (1.3 / 0.5 + 0.25)).abs() < 1e-4 && (top.v - 0.2 / 0.5).abs() < 1e-4);
+        assert!((top.uv_extent - 0.5).abs() < 1e-5);
+
+        // Los lados y la cara inferior usan el material del cubo, escalado sobre la cara
+        let side = loaded.objects.closest_hit(&Vec3::new(8.0, -0.25, 0.0), &Vec3::new(-1.0, 0.0, 0.0));
+        assert_eq!(side.material.tile_size, None);
+        assert!((side.u - 1.0).abs() < 1e-4 && (side.v - 2.25).abs() < 1e-4);
+        let bottom = loaded.objects.closest_hit(&Vec3::new(0.0, -3.0, 0.0), &Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(bottom.material.id, side.material.id);
+        assert_ne!(top.material.id, side.material.id);
+    }
+
+    #[test]
+    fn test_texture_atlas() {
+        // Atlas de 4x2 píxeles: una baldosa roja a la izquierda y una verde a la derecha
+        let dir = std::env::temp_dir();
+        let image_path = dir.join("escena_prueba_atlas.png");
+        let layout_path = dir.join("escena_prueba_atlas.json");
+        let image = image::ImageBuffer::from_fn(4, 2, |x, _| if x < 2 { image::Rgb([255u8, 0, 0]) } else { image::Rgb([0u8, 255, 0]) });
+        image::DynamicImage::ImageRgb8(image).save(&image_path).unwrap();
+        std::fs::write(&layout_path, r#"{"frames": {"leaf": {"frame": {"x": 2, "y": 0, "w": 2, "h": 2}}}}"#).unwrap();
+        let source = format!(
+            r#"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[atlases.grid]
+image = "{image}"
+tile_size = [2, 2]
+tiles = {{ red = [0, 0], green = [1, 0] }}
+
+[atlases.packed]
+image = "{image}"
+layout = "{layout}"
+
+[materials.red]
+texture = "grid/red"
+
+[materials.leaf]
+texture = "packed/leaf"
+
+[[cubes]]
+min = [-3.0, -1.0, -1.0]
+max = [-1.0, 1.0, 1.0]
+material = "red"
+
+[[cubes]]
+min = [1.0, -1.0, -1.0]
+max = [3.0, 1.0, 1.0]
+material = "leaf"
+"#,
+            image = image_path.display(),
+            layout = layout_path.display()
+        );
+        let loaded = parse(&source).unwrap();
+        let tm = &loaded.texture_manager;
+        // Ni en el borde de la baldosa ni con mipmaps se cuela el color de la vecina
+        for (x, red) in [(-2.0, true), (2.0, false)] {
+            let material = loaded.objects.closest_hit(&Vec3::new(x, 0.0, 5.0), &Vec3::new(0.0, 0.0, -1.0)).material;
+            for (u, footprint) in [(0.99, 0.0), (0.01, 0.0), (0.5, 1.0)] {
+                let color = material.get_diffuse_color(u, 0.5, footprint, tm);
+                assert_eq!((color.r > 0.99, color.g > 0.99), (red, !red), "{} {}", u, footprint);
+                assert!(color.r.min(color.g) < 1e-6);
+            }
+        }
+
+        let error = parse(&source.replace("green = [1, 0]", "green = [2, 0]")).err().unwrap();
+        assert!(error.to_string().contains("'green'"), "{}", error);
+    }
+
+    #[test]
+    fn test_group_instances() {
+        let source = r#"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[materials.wood]
+diffuse = [120, 80, 40]
+
+[groups.post]
+cubes = [{ min = [-0.5, 0.0, -0.5], max = [0.5, 1.0, 0.5], material = "wood" }]
+
+[[instances]]
+group = "post"
+position = [-3.0, 0.0, 0.0]
+
+[[instances]]
+group = "post"
+position = [3.0, 0.0, 0.0]
+rotation = [0.0, 45.0, 0.0]
+scale = [1.0, 2.0, 1.0]
+"#;
+        let loaded = parse(source).unwrap();
+        assert_eq!(loaded.objects.len(), 2);
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let hit = loaded.objects.closest_hit(&Vec3::new(-3.0, 5.0, 0.0), &down);
+        assert!(hit.is_intersecting && (hit.distance - 4.0).abs() < 1e-4);
+        let hit = loaded.objects.closest_hit(&Vec3::new(3.0, 5.0, 0.0), &down);
+        assert!(hit.is_intersecting && (hit.distance - 3.0).abs() < 1e-4);
+        // Girado 45°, el poste cubre hasta 0.707 en diagonal pero no la esquina sin girar
+        assert!(!loaded.objects.closest_hit(&Vec3::new(3.48, 5.0, 0.48), &down).is_intersecting);
+        assert!(loaded.objects.closest_hit(&Vec3::new(3.65, 5.0, 0.0), &down).is_intersecting);
+
+        let error = parse(&source.replace("group = \"post\"\nposition = [-3.0", "group = \"poste\"\nposition = [-3.0")).err().unwrap();
+        assert!(error.to_string().contains("'poste'"), "{}", error);
+        let error = parse(&source.replace("scale = [1.0, 2.0, 1.0]", "scale = 0.0")).err().unwrap();
+        assert!(error.to_string().contains("escala"), "{}", error);
+    }
+
+    #[test]
+    fn test_unknown_material_reports_line() {
+        let error = parse(
+            r#"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[[cubes]]
+min = [-1.0, -1.0, -1.0]
+max = [1.0, 1.0, 1.0]
+material = "lava"
+"#,
+        )
+        .err()
+        .unwrap();
+        let message = error.to_string();
+        assert!(message.starts_with("prueba.toml:8:12:"), "{}", message);
+        assert!(message.contains("'lava'"));
+    }
+
+    #[test]
+    fn test_sphere_radius_must_be_positive() {
+        let error = parse(
+            r#"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[materials.red]
+diffuse = [255, 0, 0]
+
+[[spheres]]
+center = [0.0, 0.0, 0.0]
+radius = 0.0
+material = "red"
+"#,
+        )
+        .err()
+        .unwrap();
+        let message = error.to_string();
+        assert!(message.starts_with("prueba.toml:10:10:"), "{}", message);
+        assert!(message.contains("radio"), "{}", message);
+    }
+
+    #[test]
+    fn test_voxel_size_must_be_positive() {
+        let error = parse(
+            r##"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[materials.red]
+diffuse = [255, 0, 0]
+
+[[voxel_grids]]
+origin = [0.0, 0.0, 0.0]
+voxel_size = -1.0
+palette = { "#" = "red" }
+layers = [["#"]]
+"##,
+        )
+        .err()
+        .unwrap();
+        let message = error.to_string();
+        assert!(message.starts_with("prueba.toml:10:14:"), "{}", message);
+        assert!(message.contains("tamaño de los vóxeles"), "{}", message);
+    }
+
+    #[test]
+    fn test_syntax_error_reports_line() {
+        let error = parse("[camera]\neye = [0.0, 0.0\n").err().unwrap();
+        match error {
+            SceneError::At { line, .. } => assert!(line >= 2),
+            other => panic!("error inesperado: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_unknown_field_is_rejected() {
+        let error = parse(
+            r#"[camera]
+eye = [0.0, 0.0, 5.0]
+center = [0.0, 0.0, 0.0]
+
+[materials.red]
+difuse = [255, 0, 0]
+"#,
+        )
+        .err()
+        .unwrap();
+        assert!(error.to_string().contains("difuse"));
+    }
+}
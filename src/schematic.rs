@@ -0,0 +1,518 @@
+use image::{DynamicImage, Rgb, RgbImage};
+use nalgebra_glm::Vec3;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use crate::materials::{Material, TextureManager};
+use crate::nbt::{read_nbt, Tag};
+use crate::textures::{Filter, Sampler, Texture, WrapMode};
+use crate::voxel::{BlockId, VoxelGrid, AIR};
+
+// Bloques que no ocupan nada
+const AIR_BLOCKS: [&str; 3] = ["minecraft:air", "minecraft:cave_air", "minecraft:void_air"];
+
+// Nombres actuales de los ids numéricos más comunes del formato .schematic de MCEdit,
+// para que la misma tabla de bloques sirva para los dos formatos. En los bloques con
+// variantes el valor de datos elige cuál, como la madera de un tronco o el color de
+// la lana; una variante que no está aquí no recibe nombre actual
+const LEGACY_NAMES: [(u16, u8, &str); 119] = [
+    (1, 0, "minecraft:stone"),
+    (1, 1, "minecraft:granite"),
+    (1, 2, "minecraft:polished_granite"),
+    (1, 3, "minecraft:diorite"),
+    (1, 4, "minecraft:polished_diorite"),
+    (1, 5, "minecraft:andesite"),
+    (1, 6, "minecraft:polished_andesite"),
+    (2, 0, "minecraft:grass_block"),
+    (3, 0, "minecraft:dirt"),
+    (3, 1, "minecraft:coarse_dirt"),
+    (3, 2, "minecraft:podzol"),
+    (4, 0, "minecraft:cobblestone"),
+    (5, 0, "minecraft:oak_planks"),
+    (5, 1, "minecraft:spruce_planks"),
+    (5, 2, "minecraft:birch_planks"),
+    (5, 3, "minecraft:jungle_planks"),
+    (5, 4, "minecraft:acacia_planks"),
+    (5, 5, "minecraft:dark_oak_planks"),
+    (7, 0, "minecraft:bedrock"),
+    (8, 0, "minecraft:water"),
+    (9, 0, "minecraft:water"),
+    (10, 0, "minecraft:lava"),
+    (11, 0, "minecraft:lava"),
+    (12, 0, "minecraft:sand"),
+    (12, 1, "minecraft:red_sand"),
+    (13, 0, "minecraft:gravel"),
+    (14, 0, "minecraft:gold_ore"),
+    (15, 0, "minecraft:iron_ore"),
+    (16, 0, "minecraft:coal_ore"),
+    (17, 0, "minecraft:oak_log"),
+    (17, 1, "minecraft:spruce_log"),
+    (17, 2, "minecraft:birch_log"),
+    (17, 3, "minecraft:jungle_log"),
+    (18, 0, "minecraft:oak_leaves"),
+    (18, 1, "minecraft:spruce_leaves"),
+    (18, 2, "minecraft:birch_leaves"),
+    (18, 3, "minecraft:jungle_leaves"),
+    (20, 0, "minecraft:glass"),
+    (24, 0, "minecraft:sandstone"),
+    (24, 1, "minecraft:chiseled_sandstone"),
+    (24, 2, "minecraft:cut_sandstone"),
+    (35, 0, "minecraft:white_wool"),
+    (35, 1, "minecraft:orange_wool"),
+    (35, 2, "minecraft:magenta_wool"),
+    (35, 3, "minecraft:light_blue_wool"),
+    (35, 4, "minecraft:yellow_wool"),
+    (35, 5, "minecraft:lime_wool"),
+    (35, 6, "minecraft:pink_wool"),
+    (35, 7, "minecraft:gray_wool"),
+    (35, 8, "minecraft:light_gray_wool"),
+    (35, 9, "minecraft:cyan_wool"),
+    (35, 10, "minecraft:purple_wool"),
+    (35, 11, "minecraft:blue_wool"),
+    (35, 12, "minecraft:brown_wool"),
+    (35, 13, "minecraft:green_wool"),
+    (35, 14, "minecraft:red_wool"),
+    (35, 15, "minecraft:black_wool"),
+    (41, 0, "minecraft:gold_block"),
+    (42, 0, "minecraft:iron_block"),
+    (43, 0, "minecraft:smooth_stone"),
+    (43, 1, "minecraft:sandstone"),
+    (43, 3, "minecraft:cobblestone"),
+    (43, 4, "minecraft:bricks"),
+    (43, 5, "minecraft:stone_bricks"),
+    (43, 6, "minecraft:nether_bricks"),
+    (43, 7, "minecraft:quartz_block"),
+    (44, 0, "minecraft:smooth_stone_slab"),
+    (44, 1, "minecraft:sandstone_slab"),
+    (44, 3, "minecraft:cobblestone_slab"),
+    (44, 4, "minecraft:brick_slab"),
+    (44, 5, "minecraft:stone_brick_slab"),
+    (44, 6, "minecraft:nether_brick_slab"),
+    (44, 7, "minecraft:quartz_slab"),
+    (45, 0, "minecraft:bricks"),
+    (47, 0, "minecraft:bookshelf"),
+    (48, 0, "minecraft:mossy_cobblestone"),
+    (49, 0, "minecraft:obsidian"),
+    (50, 0, "minecraft:torch"),
+    (53, 0, "minecraft:oak_stairs"),
+    (54, 0, "minecraft:chest"),
+    (56, 0, "minecraft:diamond_ore"),
+    (57, 0, "minecraft:diamond_block"),
+    (58, 0, "minecraft:crafting_table"),
+    (61, 0, "minecraft:furnace"),
+    (67, 0, "minecraft:cobblestone_stairs"),
+    (79, 0, "minecraft:ice"),
+    (80, 0, "minecraft:snow_block"),
+    (82, 0, "minecraft:clay"),
+    (85, 0, "minecraft:oak_fence"),
+    (87, 0, "minecraft:netherrack"),
+    (89, 0, "minecraft:glowstone"),
+    (98, 0, "minecraft:stone_bricks"),
+    (98, 1, "minecraft:mossy_stone_bricks"),
+    (98, 2, "minecraft:cracked_stone_bricks"),
+    (98, 3, "minecraft:chiseled_stone_bricks"),
+    (102, 0, "minecraft:glass_pane"),
+    (112, 0, "minecraft:nether_bricks"),
+    (155, 0, "minecraft:quartz_block"),
+    (155, 1, "minecraft:chiseled_quartz_block"),
+    (155, 2, "minecraft:quartz_pillar"),
+    (155, 3, "minecraft:quartz_pillar"),
+    (155, 4, "minecraft:quartz_pillar"),
+    (159, 0, "minecraft:white_terracotta"),
+    (159, 1, "minecraft:orange_terracotta"),
+    (159, 2, "minecraft:magenta_terracotta"),
+    (159, 3, "minecraft:light_blue_terracotta"),
+    (159, 4, "minecraft:yellow_terracotta"),
+    (159, 5, "minecraft:lime_terracotta"),
+    (159, 6, "minecraft:pink_terracotta"),
+    (159, 7, "minecraft:gray_terracotta"),
+    (159, 8, "minecraft:light_gray_terracotta"),
+    (159, 9, "minecraft:cyan_terracotta"),
+    (159, 10, "minecraft:purple_terracotta"),
+    (159, 11, "minecraft:blue_terracotta"),
+    (159, 12, "minecraft:brown_terracotta"),
+    (159, 13, "minecraft:green_terracotta"),
+    (159, 14, "minecraft:red_terracotta"),
+    (159, 15, "minecraft:black_terracotta"),
+    (172, 0, "minecraft:terracotta"),
+];
+
+// Bits del valor de datos que eligen la variante; los demás guardan la orientación o
+// el estado, como el eje de un tronco o la losa de arriba. En los bloques que no
+// están aquí el valor de datos no cambia el bloque y se ignora
+const VARIANT_BITS: [(u16, u8); 13] = [
+    (1, 0x0f),
+    (3, 0x0f),
+    (5, 0x0f),
+    (12, 0x0f),
+    (17, 0x03),
+    (18, 0x03),
+    (24, 0x0f),
+    (35, 0x0f),
+    (43, 0x07),
+    (44, 0x07),
+    (98, 0x0f),
+    (155, 0x0f),
+    (159, 0x0f),
+];
+
+// Construcción de Minecraft como rejilla de bloques
+pub struct Schematic {
+    pub grid: VoxelGrid,
+    pub unknown: BTreeMap<String, usize>, // Bloques sin material en la tabla y cuántos hay
+}
+
+impl Schematic {
+    // Resumen de los bloques desconocidos, o None si todos tenían material
+    pub fn summary(&self) -> Option<String> {
+        if self.unknown.is_empty() {
+            return None;
+        }
+        let total: usize = self.unknown.values().sum();
+        let mut counts: Vec<(&String, &usize)> = self.unknown.iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        let mut listed: Vec<String> = counts.iter().take(10).map(|(name, count)| format!("{} ({})", name, count)).collect();
+        if counts.len() > 10 {
+            listed.push(format!("y {} tipos más", counts.len() - 10));
+        }
+        Some(format!("{} bloques sin material en [blocks] se dibujan con el de relleno: {}", total, listed.join(", ")))
+    }
+}
+
+#[derive(Debug)]
+pub struct SchematicError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchematicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for SchematicError {}
+
+// Material de los bloques desconocidos: el damero magenta y negro de las texturas
+// que faltan, imposible de confundir con un bloque de verdad
+pub fn placeholder_material(texture_manager: &mut TextureManager) -> Material {
+    let image = RgbImage::from_fn(2, 2, |x, y| if (x + y) % 2 == 0 { Rgb([255, 0, 255]) } else { Rgb([0, 0, 0]) });
+    let index = texture_manager.add_texture(Texture::from_image(DynamicImage::ImageRgb8(image)));
+    let mut material = Material::new_with_texture(index, 10.0, [0.9, 0.1], 1.0);
+    material.sampler = Sampler::new(Filter::Nearest, WrapMode::Repeat);
+    material
+}
+
+// Lee un .schem de Sponge (versiones 1 a 3) o un .schematic de MCEdit. `blocks` da
+// el material de cada bloque, por estado completo ("minecraft:oak_log[axis=y]"), por
+// nombre ("minecraft:oak_log" u "oak_log") o, en el formato viejo, por id numérico
+// ("35:14" o "35"). La esquina mínima queda en `position` y cada bloque mide `voxel_size`
+pub fn load_schematic(
+    path: &str,
+    blocks: &HashMap<String, Material>,
+    placeholder: Material,
+    position: Vec3,
+    voxel_size: f32,
+) -> Result<Schematic, SchematicError> {
+    let error = |message: String| SchematicError { path: path.to_string(), message };
+    let bytes = fs::read(path).map_err(|e| error(format!("no se pudo leer la construcción: {}", e)))?;
+    let (_, root) = read_nbt(&bytes).map_err(|e| error(e.to_string()))?;
+    // La versión 3 guarda todo dentro de un compuesto "Schematic"
+    let root = match root.get("Schematic") {
+        Some(nested) if nested.as_compound().is_some() => nested,
+        _ => &root,
+    };
+
+    // Los lados son shorts sin signo
+    let side = |name: &str| {
+        root.get(name)
+            .and_then(Tag::as_int)
+            .map(|value| (value & 0xffff) as usize)
+            .ok_or_else(|| error(format!("falta '{}'", name)))
+    };
+    let dims = [side("Width")?, side("Height")?, side("Length")?];
+    let volume = dims[0] * dims[1] * dims[2];
+    if volume == 0 {
+        return Err(error("la construcción no tiene bloques".to_string()));
+    }
+    let (palette, cells) = match (root.get("Blocks"), root.get("Palette")) {
+        (Some(Tag::Compound(blocks)), _) => {
+            let palette = blocks.get("Palette").ok_or_else(|| error("falta 'Blocks.Palette'".to_string()))?;
+            let data = blocks.get("Data").and_then(Tag::as_bytes).ok_or_else(|| error("falta 'Blocks.Data'".to_string()))?;
+            sponge_blocks(palette, data).map_err(error)?
+        }
+        (_, Some(palette)) => {
+            let data = root.get("BlockData").and_then(Tag::as_bytes).ok_or_else(|| error("falta 'BlockData'".to_string()))?;
+            sponge_blocks(palette, data).map_err(error)?
+        }
+        (Some(Tag::ByteArray(ids)), None) => legacy_blocks(root, ids).map_err(error)?,
+        _ => return Err(error("no es un .schem de Sponge ni un .schematic de MCEdit".to_string())),
+    };
+    if cells.len() != volume {
+        return Err(error(format!("hay {} bloques y el tamaño {}x{}x{} pide {}", cells.len(), dims[0], dims[1], dims[2], volume)));
+    }
+
+    // Cada estado de bloque de la paleta se resuelve una vez
+    let mut grid = VoxelGrid::new(position, voxel_size, dims);
+    let mut placeholder_id = None;
+    let ids: Vec<Option<BlockId>> = palette
+        .iter()
+        .map(|block| {
+            if block.names.iter().any(|name| AIR_BLOCKS.contains(&name.as_str())) {
+                return Some(AIR);
+            }
+            block.names.iter().find_map(|name| blocks.get(name)).map(|material| grid.add_material(material.clone()))
+        })
+        .collect();
+
+    let mut unknown = BTreeMap::new();
+    // Orden de los datos: x, luego z, luego y
+    for (index, &cell) in cells.iter().enumerate() {
+        let x = index % dims[0];
+        let z = (index / dims[0]) % dims[2];
+        let y = index / (dims[0] * dims[2]);
+        let id = match ids.get(cell as usize) {
+            Some(Some(id)) => *id,
+            _ => {
+                let name = palette.get(cell as usize).map_or_else(|| format!("índice {} fuera de la paleta", cell), |block| block.label.clone());
+                *unknown.entry(name).or_insert(0) += 1;
+                *placeholder_id.get_or_insert_with(|| grid.add_material(placeholder.clone()))
+            }
+        };
+        grid.set(x, y, z, id);
+    }
+    Ok(Schematic { grid, unknown })
+}
+
+// Entrada de la paleta: los nombres a buscar en la tabla, del más al menos
+// específico, y cómo se muestra en el resumen
+struct PaletteBlock {
+    names: Vec<String>,
+    label: String,
+}
+
+impl PaletteBlock {
+    fn from_state(state: &str) -> PaletteBlock {
+        let base = state.split('[').next().unwrap_or(state);
+        let mut names = vec![state.to_string(), base.to_string()];
+        if let Some(short) = base.strip_prefix("minecraft:") {
+            names.push(short.to_string());
+        }
+        names.dedup();
+        PaletteBlock { names, label: base.to_string() }
+    }
+
+    fn from_legacy(id: u16, data: u8) -> PaletteBlock {
+        let bits = VARIANT_BITS.iter().find(|(legacy, _)| *legacy == id).map_or(0, |(_, bits)| *bits);
+        let variant = data & bits;
+        let mut names = vec![format!("{}:{}", id, data)];
+        if variant != data {
+            names.push(format!("{}:{}", id, variant));
+        }
+        names.push(id.to_string());
+        if id == 0 {
+            names.push(AIR_BLOCKS[0].to_string());
+        }
+        let modern = LEGACY_NAMES
+            .iter()
+            .find(|(legacy, value, _)| *legacy == id && *value == variant)
+            .map(|(_, _, name)| *name);
+        let label = match modern {
+            Some(name) => {
+                names.extend(PaletteBlock::from_state(name).names);
+                format!("{} ({}:{})", name, id, data)
+            }
+            None => format!("{}:{}", id, data),
+        };
+        PaletteBlock { names, label }
+    }
+}
+
+// Paleta y bloques de un .schem: la paleta va de estado a índice y los índices de
+// cada bloque están en varints (7 bits por byte, el bit alto indica que sigue otro)
+fn sponge_blocks(palette: &Tag, data: &[i8]) -> Result<(Vec<PaletteBlock>, Vec<u32>), String> {
+    let entries = palette.as_compound().ok_or("la paleta no es un compuesto")?;
+    let mut states: Vec<(usize, &String)> = Vec::with_capacity(entries.len());
+    for (state, index) in entries {
+        let index = index.as_int().filter(|index| (0..=u16::MAX as i64).contains(index)).ok_or_else(|| format!("índice inválido para {}", state))?;
+        states.push((index as usize, state));
+    }
+    let size = states.iter().map(|(index, _)| index + 1).max().unwrap_or(0);
+    let mut palette: Vec<PaletteBlock> =
+        (0..size).map(|index| PaletteBlock { names: Vec::new(), label: format!("índice {} sin estado", index) }).collect();
+    for (index, state) in states {
+        palette[index] = PaletteBlock::from_state(state);
+    }
+
+    let mut cells = Vec::new();
+    let mut value = 0u32;
+    let mut shift = 0;
+    for &byte in data {
+        let byte = byte as u8;
+        value |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            cells.push(value);
+            value = 0;
+            shift = 0;
+        } else {
+            shift += 7;
+            if shift > 28 {
+                return Err("varint demasiado largo en los datos de bloques".to_string());
+            }
+        }
+    }
+    Ok((palette, cells))
+}
+
+// Bloques de un .schematic: un byte de id por bloque, cuatro bits más por bloque en
+// AddBlocks para los ids mayores que 255 y la variante en Data. Los ids son los de
+// la edición Java ("Alpha"); los de Pocket o Classic significan otros bloques
+fn legacy_blocks(root: &Tag, ids: &[i8]) -> Result<(Vec<PaletteBlock>, Vec<u32>), String> {
+    match root.get("Materials").and_then(Tag::as_str) {
+        Some("Alpha") | None => {}
+        Some(other) => return Err(format!("los bloques usan los ids de '{}' y sólo se admiten los de 'Alpha'", other)),
+    }
+    let data = root.get("Data").and_then(Tag::as_bytes).ok_or("falta 'Data'")?;
+    let add = root.get("AddBlocks").and_then(Tag::as_bytes);
+    let mut indices: HashMap<(u16, u8), u32> = HashMap::new();
+    let mut palette = Vec::new();
+    let mut cells = Vec::with_capacity(ids.len());
+    for (index, &id) in ids.iter().enumerate() {
+        let high = add.and_then(|add| add.get(index / 2)).map_or(0, |&byte| {
+            let byte = byte as u8;
+            if index % 2 == 0 { byte >> 4 } else { byte & 0x0f }
+        });
+        let id = (high as u16) << 8 | id as u8 as u16;
+        let variant = data.get(index).map_or(0, |&value| value as u8 & 0x0f);
+        let cell = *indices.entry((id, variant)).or_insert_with(|| {
+            palette.push(PaletteBlock::from_legacy(id, variant));
+            (palette.len() - 1) as u32
+        });
+        cells.push(cell);
+    }
+    Ok((palette, cells))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray_intersect::RayIntersect;
+
+    // Escritor mínimo de NBT sin comprimir para armar construcciones de prueba
+    fn named(kind: u8, name: &str, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![kind];
+        bytes.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    fn compound(children: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = children.concat();
+        bytes.push(0);
+        bytes
+    }
+
+    fn byte_array(values: &[u8]) -> Vec<u8> {
+        let mut bytes = (values.len() as i32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(values);
+        bytes
+    }
+
+    fn write(name: &str, root: Vec<u8>) -> String {
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, named(10, "Schematic", &root)).unwrap();
+        path.display().to_string()
+    }
+
+    fn table() -> HashMap<String, Material> {
+        let mut stone = Material::black();
+        stone.id = 1;
+        let mut log = Material::black();
+        log.id = 2;
+        HashMap::from([("minecraft:stone".to_string(), stone), ("oak_log".to_string(), log)])
+    }
+
+    fn placeholder() -> Material {
+        let mut material = Material::black();
+        material.id = 9;
+        material
+    }
+
+    #[test]
+    fn test_sponge_schem() {
+        // 2x2x1: piedra y tronco abajo, aire y un bloque desconocido arriba. El índice
+        // 130 de la paleta ocupa dos bytes de varint
+        let palette = compound(&[
+            named(3, "minecraft:stone", &0i32.to_be_bytes()),
+            named(3, "minecraft:oak_log[axis=y]", &1i32.to_be_bytes()),
+            named(3, "minecraft:air", &2i32.to_be_bytes()),
+            named(3, "minecraft:beacon", &130i32.to_be_bytes()),
+        ]);
+        let path = write(
+            "construccion_prueba.schem",
+            compound(&[
+                named(2, "Width", &2i16.to_be_bytes()),
+                named(2, "Height", &2i16.to_be_bytes()),
+                named(2, "Length", &1i16.to_be_bytes()),
+                named(10, "Palette", &palette),
+                named(7, "BlockData", &byte_array(&[0, 1, 2, 0x82, 0x01])),
+            ]),
+        );
+
+        let schematic = load_schematic(&path, &table(), placeholder(), Vec3::new(0.0, 0.0, 0.0), 1.0).unwrap();
+        assert_eq!(schematic.grid.block_count(), 3);
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let hit = |x: f32| schematic.grid.ray_intersect(&Vec3::new(x, 5.0, 0.5), &down);
+        // Arriba a la izquierda hay aire, así que se ve la piedra de abajo
+        assert_eq!((hit(0.5).material.id, hit(0.5).distance), (1, 4.0));
+        assert_eq!((hit(1.5).material.id, hit(1.5).distance), (9, 3.0));
+        assert_eq!(schematic.unknown, BTreeMap::from([("minecraft:beacon".to_string(), 1)]));
+        assert_eq!(
+            schematic.summary().unwrap(),
+            "1 bloques sin material en [blocks] se dibujan con el de relleno: minecraft:beacon (1)"
+        );
+    }
+
+    #[test]
+    fn test_legacy_schematic() {
+        // 4x1x1: piedra, tronco de abedul (17:2), tronco de roble acostado (17:4, el
+        // eje en los bits altos) y lana roja (35:14). Solo el roble tiene material
+        let path = write(
+            "construccion_prueba.schematic",
+            compound(&[
+                named(2, "Width", &4i16.to_be_bytes()),
+                named(2, "Height", &1i16.to_be_bytes()),
+                named(2, "Length", &1i16.to_be_bytes()),
+                named(8, "Materials", &[0, 5, b'A', b'l', b'p', b'h', b'a']),
+                named(7, "Blocks", &byte_array(&[1, 17, 17, 35])),
+                named(7, "Data", &byte_array(&[0, 2, 4, 14])),
+            ]),
+        );
+        let schematic = load_schematic(&path, &table(), placeholder(), Vec3::new(0.0, 0.0, 0.0), 0.5).unwrap();
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let ids: Vec<usize> = [0.25, 0.75, 1.25, 1.75]
+            .iter()
+            .map(|&x| schematic.grid.ray_intersect(&Vec3::new(x, 2.0, 0.25), &down).material.id)
+            .collect();
+        assert_eq!(ids, vec![1, 9, 2, 9]);
+        assert_eq!(
+            schematic.unknown,
+            BTreeMap::from([("minecraft:birch_log (17:2)".to_string(), 1), ("minecraft:red_wool (35:14)".to_string(), 1)])
+        );
+
+        let path = write(
+            "construccion_pocket.schematic",
+            compound(&[
+                named(2, "Width", &1i16.to_be_bytes()),
+                named(2, "Height", &1i16.to_be_bytes()),
+                named(2, "Length", &1i16.to_be_bytes()),
+                named(8, "Materials", &[0, 6, b'P', b'o', b'c', b'k', b'e', b't']),
+                named(7, "Blocks", &byte_array(&[1])),
+                named(7, "Data", &byte_array(&[0])),
+            ]),
+        );
+        let error = load_schematic(&path, &table(), placeholder(), Vec3::new(0.0, 0.0, 0.0), 0.5).err().unwrap();
+        assert!(error.to_string().contains("'Pocket'"), "{}", error);
+    }
+}
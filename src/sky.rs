@@ -0,0 +1,286 @@
+use nalgebra_glm::Vec3;
+use crate::radiance::Radiance;
+
+// Modelo de cielo analítico de Preetham, Shirley y Smits (1999): la luminancia y la
+// cromaticidad de cada dirección salen de la turbidez del aire y de la posición del
+// sol. De noche se apaga y quedan el color base del cielo, las estrellas y la luna.
+
+// Escala de las kcd/m² del modelo a la radiancia de la escena: el cenit a mediodía
+// queda cerca del color de cielo fijo que usaba el raytracer
+const SKY_SCALE: f32 = 0.04;
+// Radios angulares (en radianes) de los discos del sol y la luna, exagerados
+// respecto a los reales para que se vean a la resolución de la ventana
+const SUN_RADIUS: f32 = 0.035;
+const MOON_RADIUS: f32 = 0.03;
+// Radiancia de los discos por unidad de intensidad de su luz direccional
+const SUN_DISK_RADIANCE: f32 = 40.0;
+const MOON_DISK_RADIANCE: f32 = 12.0;
+// Celdas por arista de cada cara del cubo en que se reparten las estrellas y
+// fracción de celdas que tienen una
+const STAR_GRID: f32 = 150.0;
+const STAR_DENSITY: f32 = 0.06;
+const STAR_RADIUS: f32 = 0.003;
+const STAR_RADIANCE: f32 = 2.0;
+
+// Coeficientes de la función de distribución de Perez para un canal (Y, x o y)
+#[derive(Debug, Clone, Copy)]
+struct Perez {
+    a: f32,
+    b: f32,
+    c: f32,
+    d: f32,
+    e: f32,
+}
+
+impl Perez {
+    // `cos_theta` es el coseno del ángulo con el cenit y `gamma` el ángulo con el sol
+    fn eval(&self, cos_theta: f32, gamma: f32) -> f32 {
+        (1.0 + self.a * (self.b / cos_theta).exp())
+            * (1.0 + self.c * (self.d * gamma).exp() + self.e * gamma.cos() * gamma.cos())
+    }
+}
+
+// Cielo de un instante del ciclo: se construye una vez por cuadro y los
+// integradores lo consultan por dirección
+#[derive(Debug, Clone)]
+pub struct Sky {
+    sun_direction: Vec3,
+    sun_radiance: Radiance,  // Radiancia del disco solar (negro si no se dibuja)
+    moon_direction: Vec3,
+    moon_radiance: Radiance,
+    night_color: Radiance,   // Color base del cielo sin sol: noche y crepúsculo
+    daylight: f32,           // Peso del modelo de Preetham: 1 de día, 0 de noche
+    starlight: f32,          // Brillo de las estrellas: 1 de noche, 0 de día
+    star_rotation: f32,      // Giro de la bóveda de estrellas alrededor del eje de la órbita
+    perez: [Perez; 3],       // Distribuciones de Y, x e y
+    zenith: [f32; 3],        // Luminancia y cromaticidad en el cenit
+    perez_zenith: [f32; 3],  // F(0, θs) de cada canal, para normalizar
+}
+
+impl Sky {
+    // `sun_direction` apunta hacia el sol; con el sol bajo el horizonte el modelo de
+    // Preetham se evalúa con el sol justo en el horizonte y se atenúa hasta apagarse
+    pub fn new(sun_direction: Vec3, turbidity: f32, night_color: Radiance) -> Self {
+        let sun_direction = sun_direction.normalize();
+        let t = turbidity;
+        // El modelo no está definido con el sol bajo el horizonte
+        let theta_s = sun_direction.y.clamp(0.02, 1.0).acos();
+        let perez = [
+            Perez { a: 0.1787 * t - 1.4630, b: -0.3554 * t + 0.4275, c: -0.0227 * t + 5.3251, d: 0.1206 * t - 2.5771, e: -0.0670 * t + 0.3703 },
+            Perez { a: -0.0193 * t - 0.2592, b: -0.0665 * t + 0.0008, c: -0.0004 * t + 0.2125, d: -0.0641 * t - 0.8989, e: -0.0033 * t + 0.0452 },
+            Perez { a: -0.0167 * t - 0.2608, b: -0.0950 * t + 0.0092, c: -0.0079 * t + 0.2102, d: -0.0441 * t - 1.6537, e: -0.0109 * t + 0.0529 },
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (std::f32::consts::PI - 2.0 * theta_s);
+        let zenith_luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let (t1, t2, t3) = (theta_s, theta_s * theta_s, theta_s * theta_s * theta_s);
+        let zenith_x = t * t * (0.00166 * t3 - 0.00375 * t2 + 0.00209 * t1)
+            + t * (-0.02903 * t3 + 0.06377 * t2 - 0.03202 * t1 + 0.00394)
+            + (0.11693 * t3 - 0.21196 * t2 + 0.06052 * t1 + 0.25886);
+        let zenith_y = t * t * (0.00275 * t3 - 0.00610 * t2 + 0.00317 * t1)
+            + t * (-0.04214 * t3 + 0.08970 * t2 - 0.04153 * t1 + 0.00516)
+            + (0.15346 * t3 - 0.26756 * t2 + 0.06670 * t1 + 0.26688);
+        let perez_zenith = [
+            perez[0].eval(1.0, theta_s),
+            perez[1].eval(1.0, theta_s),
+            perez[2].eval(1.0, theta_s),
+        ];
+
+        Sky {
+            sun_direction,
+            sun_radiance: Radiance::black(),
+            moon_direction: -sun_direction,
+            moon_radiance: Radiance::black(),
+            night_color,
+            daylight: smoothstep(-0.1, 0.05, sun_direction.y),
+            starlight: 1.0 - smoothstep(-0.15, 0.0, sun_direction.y),
+            star_rotation: 0.0,
+            perez,
+            zenith: [zenith_luminance, zenith_x, zenith_y],
+            perez_zenith,
+        }
+    }
+
+    // Cielo de un solo color en todas direcciones, sin sol, luna ni estrellas
+    #[cfg(test)]
+    pub fn uniform(color: Radiance) -> Self {
+        let mut sky = Sky::new(Vec3::new(0.0, -1.0, 0.0), 2.0, color);
+        sky.daylight = 0.0;
+        sky.starlight = 0.0;
+        sky
+    }
+
+    // Disco del sol con el color y la intensidad de su luz direccional
+    pub fn with_sun_disk(mut self, color: Radiance, intensity: f32) -> Self {
+        self.sun_radiance = color * (intensity * SUN_DISK_RADIANCE);
+        self
+    }
+
+    pub fn with_moon(mut self, direction: Vec3, color: Radiance, intensity: f32) -> Self {
+        self.moon_direction = direction.normalize();
+        self.moon_radiance = color * (intensity * MOON_DISK_RADIANCE);
+        self
+    }
+
+    // Las estrellas giran con el cielo: `angle` es el giro alrededor del eje z
+    pub fn with_star_rotation(mut self, angle: f32) -> Self {
+        self.star_rotation = angle;
+        self
+    }
+
+    // Luz difusa del cielo en una dirección, sin los discos del sol y la luna. Es la
+    // que reciben las superficies como luz ambiente: el sol y la luna ya se muestrean
+    // como luces direccionales y no se cuentan dos veces
+    pub fn radiance(&self, direction: &Vec3) -> Radiance {
+        let direction = direction.normalize();
+        let mut radiance = self.night_color;
+        if self.daylight > 0.0 {
+            radiance = radiance * (1.0 - self.daylight) + self.daylight_radiance(&direction) * self.daylight;
+        }
+        // Bajo el horizonte el cielo se oscurece hacia el suelo
+        if direction.y < 0.0 {
+            radiance = radiance * (1.0 - 0.6 * smoothstep(0.0, 0.3, -direction.y));
+        }
+        radiance
+    }
+
+    // Lo que ve un rayo que no golpea nada: el cielo con el sol, la luna y las estrellas
+    pub fn background(&self, direction: &Vec3) -> Radiance {
+        let direction = direction.normalize();
+        let mut radiance = self.radiance(&direction);
+        // El horizonte tapa los astros
+        if direction.y < 0.0 {
+            return radiance;
+        }
+        if self.starlight > 0.0 {
+            radiance += Radiance::new(1.0, 1.0, 1.0) * (self.star(&direction) * self.starlight);
+        }
+        radiance += self.moon_radiance * disk(&direction, &self.moon_direction, MOON_RADIUS);
+        radiance += self.sun_radiance * disk(&direction, &self.sun_direction, SUN_RADIUS);
+        radiance
+    }
+
+    // Modelo de Preetham en coordenadas xyY, convertido a sRGB lineal
+    fn daylight_radiance(&self, direction: &Vec3) -> Radiance {
+        let cos_theta = direction.y.max(0.01);
+        let gamma = direction.dot(&self.sun_direction).clamp(-1.0, 1.0).acos();
+        let luminance = self.zenith[0] * self.perez[0].eval(cos_theta, gamma) / self.perez_zenith[0];
+        let x = self.zenith[1] * self.perez[1].eval(cos_theta, gamma) / self.perez_zenith[1];
+        let y = self.zenith[2] * self.perez[2].eval(cos_theta, gamma) / self.perez_zenith[2];
+        if y <= 0.0 {
+            return Radiance::black();
+        }
+
+        let big_x = x / y * luminance;
+        let big_z = (1.0 - x - y) / y * luminance;
+        let big_y = luminance;
+        Radiance::new(
+            (3.2406 * big_x - 1.5372 * big_y - 0.4986 * big_z).max(0.0),
+            (-0.9689 * big_x + 1.8758 * big_y + 0.0415 * big_z).max(0.0),
+            (0.0557 * big_x - 0.2040 * big_y + 1.0570 * big_z).max(0.0),
+        ) * SKY_SCALE
+    }
+
+    // Brillo de estrella en una dirección. Cada cara de un cubo alrededor de la
+    // cámara se divide en celdas y algunas, elegidas por un hash, tienen una
+    // estrella cerca de su centro
+    fn star(&self, direction: &Vec3) -> f32 {
+        let (sin, cos) = self.star_rotation.sin_cos();
+        let d = Vec3::new(cos * direction.x + sin * direction.y, -sin * direction.x + cos * direction.y, direction.z);
+
+        let abs = d.abs();
+        let (face, major, u, v) = if abs.x >= abs.y && abs.x >= abs.z {
+            (if d.x > 0.0 { 0 } else { 1 }, d.x, d.y, d.z)
+        } else if abs.y >= abs.z {
+            (if d.y > 0.0 { 2 } else { 3 }, d.y, d.x, d.z)
+        } else {
+            (if d.z > 0.0 { 4 } else { 5 }, d.z, d.x, d.y)
+        };
+        let u = (u / major.abs() * 0.5 + 0.5) * STAR_GRID;
+        let v = (v / major.abs() * 0.5 + 0.5) * STAR_GRID;
+        let (cell_u, cell_v) = (u.floor(), v.floor());
+
+        let seed = hash(face ^ hash(cell_u as u32 ^ hash(cell_v as u32)));
+        if unit(seed) > STAR_DENSITY {
+            return 0.0;
+        }
+        // Posición dentro de la celda, lejos del borde para que no se corte
+        let star_u = cell_u + 0.25 + 0.5 * unit(hash(seed));
+        let star_v = cell_v + 0.25 + 0.5 * unit(hash(seed ^ 0x9e37_79b9));
+        let to_cell = |w: f32| (w / STAR_GRID - 0.5) * 2.0 * major.abs();
+        let star_direction = match face {
+            0 | 1 => Vec3::new(major, to_cell(star_u), to_cell(star_v)),
+            2 | 3 => Vec3::new(to_cell(star_u), major, to_cell(star_v)),
+            _ => Vec3::new(to_cell(star_u), to_cell(star_v), major),
+        };
+
+        let brightness = unit(hash(seed ^ 0x85eb_ca6b));
+        disk(&d, &star_direction.normalize(), STAR_RADIUS) * STAR_RADIANCE * brightness * brightness
+    }
+}
+
+// Disco de radio angular `radius` alrededor de `center` con el borde suavizado
+fn disk(direction: &Vec3, center: &Vec3, radius: f32) -> f32 {
+    let angle = direction.dot(center).clamp(-1.0, 1.0).acos();
+    1.0 - smoothstep(radius * 0.8, radius, angle)
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// Hash entero de Wang: las estrellas quedan fijas entre cuadros y entre ejecuciones
+fn hash(mut x: u32) -> u32 {
+    x = (x ^ 61) ^ (x >> 16);
+    x = x.wrapping_mul(9);
+    x ^= x >> 4;
+    x = x.wrapping_mul(0x27d4_eb2d);
+    x ^ (x >> 15)
+}
+
+fn unit(x: u32) -> f32 {
+    x as f32 / u32::MAX as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day_sky_is_blue_and_brighter_near_the_sun() {
+        let sky = Sky::new(Vec3::new(0.3, 0.8, 0.2), 2.5, Radiance::black());
+        let zenith = sky.radiance(&Vec3::new(0.0, 1.0, 0.0));
+        assert!(zenith.b > zenith.r);
+        assert!(zenith.luminance() > 0.1 && zenith.luminance() < 2.0);
+
+        let near_sun = sky.radiance(&Vec3::new(0.35, 0.8, 0.2));
+        let away = sky.radiance(&Vec3::new(-0.6, 0.5, -0.2));
+        assert!(near_sun.luminance() > away.luminance());
+    }
+
+    #[test]
+    fn test_sun_disk_only_in_background() {
+        let sun = Vec3::new(0.0, 0.6, 0.8);
+        let sky = Sky::new(sun, 2.5, Radiance::black()).with_sun_disk(Radiance::new(1.0, 1.0, 1.0), 1.0);
+        assert!(sky.background(&sun).luminance() > 10.0);
+        assert!(sky.radiance(&sun).luminance() < 10.0);
+    }
+
+    #[test]
+    fn test_night_sky_has_stars_and_uniform_sky_does_not() {
+        let night = Sky::new(Vec3::new(0.0, -1.0, 0.0), 2.5, Radiance::new(0.01, 0.01, 0.02));
+        let uniform = Sky::uniform(Radiance::new(0.01, 0.01, 0.02));
+        let mut stars = 0;
+        for i in 0..20000 {
+            let a = i as f32 * 0.001;
+            let direction = Vec3::new(a.cos(), 0.5 + 0.5 * (a * 7.0).sin().abs(), a.sin());
+            assert_eq!(night.radiance(&direction), Radiance::new(0.01, 0.01, 0.02));
+            assert_eq!(uniform.background(&direction), Radiance::new(0.01, 0.01, 0.02));
+            if night.background(&direction).luminance() > 0.02 {
+                stars += 1;
+            }
+        }
+        assert!(stars > 0);
+    }
+}
@@ -1,6 +1,9 @@
 use crate::materials::Material;
 use nalgebra_glm::{Vec3, dot};
 use crate::ray_intersect::{RayIntersect, Intersect};
+use crate::aabb::Aabb;
+use crate::light::{Light, LightShape};
+use crate::sampling::orthonormal_basis;
 use std::f32::consts::PI;
 
 pub struct Sphere {
@@ -24,6 +27,17 @@ impl Sphere {
 
         (u, v)
     }
+
+    // u crece alrededor del eje y y v hacia el polo norte; en los polos la
+    // tangente no está definida y se usa una base cualquiera
+    fn get_tangents(&self, normal: &Vec3) -> (Vec3, Vec3) {
+        let around = Vec3::new(normal.z, 0.0, -normal.x);
+        if around.norm() < 1e-6 {
+            return orthonormal_basis(normal);
+        }
+        let tangent = around.normalize();
+        (tangent, normal.cross(&tangent))
+    }
 }
 
 impl RayIntersect for Sphere {
@@ -48,17 +62,38 @@ impl RayIntersect for Sphere {
         let t1 = (-b - discriminant.sqrt()) / (2.0 * a);
         let t2 = (-b + discriminant.sqrt()) / (2.0 * a);
         
-        // Tomar la intersección más cercana y positiva
-        let t = if t1 > 0.0 { t1 } else { t2 };
-        
-        if t > 0.0 {
-            // Calcular el punto de intersección y la normal
-            let intersection_point = ray_origin + ray_direction * t;
-            let normal = (intersection_point - self.center).normalize();
-            let (u, v) = self.get_uv(&intersection_point);
-            Intersect::new(intersection_point, normal, t, self.material, u, v)
-        } else {
-            Intersect::empty()
+        // Tomar la intersección más cercana y positiva que no caiga en un hueco del
+        // recorte alfa; la cara de atrás se ve por dentro y su normal mira hacia el rayo
+        for t in [t1, t2] {
+            if t > 0.0 {
+                // Calcular el punto de intersección y la normal
+                let intersection_point = ray_origin + ray_direction * t;
+                let normal = (intersection_point - self.center).normalize();
+                // u da la vuelta al ecuador y v va de polo a polo
+                let (u, v) = self.get_uv(&intersection_point);
+                let (u, v) = self.material.texture_uv(u, v, u * 2.0 * PI * self.radius, v * PI * self.radius);
+                if self.material.is_cut(u, v) {
+                    continue;
+                }
+                let (tangent, bitangent) = self.get_tangents(&normal);
+                let normal = if t == t2 && t1 > 0.0 { -normal } else { normal };
+                let uv_extent = self.material.texture_uv_extent((2.0 * PI * PI).sqrt() * self.radius);
+                return Intersect::new(intersection_point, normal, t, self.material.clone(), u, v)
+                    .with_tangents(tangent, bitangent)
+                    .with_uv_extent(uv_extent);
+            }
         }
+        Intersect::empty()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let radius = Vec3::repeat(self.radius);
+        Aabb::new(self.center - radius, self.center + radius)
+    }
+
+    fn emitters(&self) -> Vec<Light> {
+        Light::emitter(self.center, LightShape::Sphere { radius: self.radius }, &self.material)
+            .into_iter()
+            .collect()
     }
 }
\ No newline at end of file
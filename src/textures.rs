@@ -1,48 +1,389 @@
 extern crate image;
-use image::{ImageReader, Pixel, DynamicImage, GenericImageView};
+use image::{ImageReader, ImageResult, Pixel, DynamicImage, GenericImageView};
+use nalgebra_glm::Vec3;
+use std::f32::consts::PI;
 use std::fmt;
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use crate::color::Color;
+use crate::radiance::Radiance;
 
 #[derive(Clone)]
 pub struct Texture {
     image: DynamicImage,
     pub width: usize,
     pub height: usize,
-    color_array: Vec<Color>,
+    color_array: Vec<Radiance>, // Texels ya convertidos de sRGB a lineal
+    alpha_array: Vec<f32>,      // Opacidad de cada texel, 1 en las imágenes sin alfa
+    mips: OnceCell<Vec<MipLevel>>, // Copias de color_array a la mitad, armadas en la primera consulta trilineal
+}
+
+// Un nivel de la cadena de mipmaps: la imagen promediada a width x height
+#[derive(Clone)]
+struct MipLevel {
+    width: usize,
+    height: usize,
+    texels: Vec<Radiance>,
+}
+
+impl MipLevel {
+    // Promedio de cada bloque de 2x2, o de 2x1 cuando un lado ya tiene un solo texel
+    fn halve(&self) -> MipLevel {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut texels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = Radiance::black();
+                let mut count = 0.0;
+                for sy in (y * 2)..(y * 2 + 2).min(self.height) {
+                    for sx in (x * 2)..(x * 2 + 2).min(self.width) {
+                        sum += self.texels[sy * self.width + sx];
+                        count += 1.0;
+                    }
+                }
+                texels.push(sum / count);
+            }
+        }
+        MipLevel { width, height, texels }
+    }
+}
+
+// Cómo se combinan los texeles al muestrear una textura
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Nearest,   // El texel más cercano, que conserva el aspecto pixelado
+    Bilinear,  // Mezcla de los cuatro texeles más cercanos de la imagen completa
+    Trilinear, // Bilineal en los dos mipmaps más cercanos a la huella, mezclados
+}
+
+// Qué leen las coordenadas fuera de 0..1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WrapMode {
+    Repeat, // La imagen se repite
+    Clamp,  // Los texeles del borde se estiran
+    Mirror, // La imagen se repite, reflejada una vez sí y otra no
+}
+
+impl WrapMode {
+    // Índice del texel en 0..size para una coordenada entera de texel
+    fn apply(self, i: i64, size: usize) -> usize {
+        let size = size as i64;
+        let index = match self {
+            WrapMode::Repeat => i.rem_euclid(size),
+            WrapMode::Clamp => i.clamp(0, size - 1),
+            WrapMode::Mirror => {
+                let period = i.rem_euclid(2 * size);
+                if period < size { period } else { 2 * size - 1 - period }
+            }
+        };
+        index as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampler {
+    pub filter: Filter,
+    pub wrap: WrapMode,
+}
+
+// Las texturas de bloques son pixel art: el filtrado suave se pide en cada material
+impl Default for Sampler {
+    fn default() -> Self {
+        Sampler::new(Filter::Nearest, WrapMode::Repeat)
+    }
+}
+
+impl Sampler {
+    pub fn new(filter: Filter, wrap: WrapMode) -> Self {
+        Sampler { filter, wrap }
+    }
+
+    // Color en (u, v), con (0, 0) la esquina superior izquierda de la imagen.
+    // `footprint` es el ancho en unidades uv del área que representa la muestra, como
+    // un píxel visto de lejos; elige el mipmap del filtrado trilineal y 0 es la imagen
+    // completa
+    pub fn sample(&self, texture: &Texture, u: f32, v: f32, footprint: f32) -> Radiance {
+        match self.filter {
+            Filter::Nearest => {
+                let (x, y) = texture.nearest_texel(u, v, self.wrap);
+                texture.get_color(x, y)
+            }
+            Filter::Bilinear => self.bilinear(texture.width, texture.height, &texture.color_array, u, v),
+            Filter::Trilinear => {
+                let texels = footprint * texture.width.max(texture.height) as f32;
+                if texels <= 1.0 {
+                    return self.bilinear(texture.width, texture.height, &texture.color_array, u, v);
+                }
+                let mips = texture.mips();
+                let lod = texels.log2().min(mips.len() as f32);
+                let level = lod.floor() as usize;
+                let blend = lod - level as f32;
+                let lookup = |level: usize| match level {
+                    0 => self.bilinear(texture.width, texture.height, &texture.color_array, u, v),
+                    _ => {
+                        let mip = &mips[(level - 1).min(mips.len() - 1)];
+                        self.bilinear(mip.width, mip.height, &mip.texels, u, v)
+                    }
+                };
+                let lower = lookup(level);
+                if blend <= 0.0 {
+                    lower
+                } else {
+                    lower * (1.0 - blend) + lookup(level + 1) * blend
+                }
+            }
+        }
+    }
+
+    fn bilinear(&self, width: usize, height: usize, texels: &[Radiance], u: f32, v: f32) -> Radiance {
+        let x = u * width as f32 - 0.5;
+        let y = v * height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let (left, right) = (self.wrap.apply(x0, width), self.wrap.apply(x0 + 1, width));
+        let (top, bottom) = (self.wrap.apply(y0, height), self.wrap.apply(y0 + 1, height));
+
+        let texel = |x: usize, y: usize| texels[y * width + x];
+        let upper = texel(left, top) * (1.0 - tx) + texel(right, top) * tx;
+        let lower = texel(left, bottom) * (1.0 - tx) + texel(right, bottom) * tx;
+        upper * (1.0 - ty) + lower * ty
+    }
 }
 
 impl Texture {
-    pub fn new(file_path: &str) -> Texture {
-        let img = ImageReader::open(file_path).unwrap().decode().unwrap();
+    pub fn new(file_path: &str) -> ImageResult<Texture> {
+        let img = ImageReader::open(file_path)?.decode()?;
+        Ok(Texture::from_image(img))
+    }
+
+    // Carga una imagen que guarda datos en lugar de colores, como un mapa de normales:
+    // los valores de 8 bits pasan a 0..1 sin la conversión de sRGB a lineal
+    pub fn new_linear(file_path: &str) -> ImageResult<Texture> {
+        let img = ImageReader::open(file_path)?.decode()?;
+        Ok(Texture::load(img, false))
+    }
+
+    pub fn from_image(img: DynamicImage) -> Texture {
+        Texture::load(img, true)
+    }
+
+    // Como `new_linear`, para una imagen ya decodificada, como una incrustada en un modelo
+    pub fn from_image_linear(img: DynamicImage) -> Texture {
+        Texture::load(img, false)
+    }
+
+    fn load(img: DynamicImage, srgb: bool) -> Texture {
         let width = img.width() as usize;
         let height = img.height() as usize;
         let mut texture = Texture {
             image: img,
             width,
             height,
-            color_array: vec![Color::black(); width * height],
+            color_array: vec![Radiance::black(); width * height],
+            alpha_array: vec![1.0; width * height],
+            mips: OnceCell::new(),
         };
-        texture.load_color_array();
+        texture.load_color_array(srgb);
         texture
     }
 
-    fn load_color_array(&mut self) {
+    fn load_color_array(&mut self, srgb: bool) {
+        // Las imágenes de punto flotante (Radiance .hdr, OpenEXR) ya guardan radiancia lineal
+        if let DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) = self.image {
+            let pixels = self.image.to_rgba32f();
+            for (index, pixel) in pixels.pixels().enumerate() {
+                self.color_array[index] = Radiance::new(pixel[0], pixel[1], pixel[2]);
+                self.alpha_array[index] = pixel[3];
+            }
+            return;
+        }
         for x in 0..self.width {
             for y in 0..self.height {
-                let pixel = self.image.get_pixel(x as u32, y as u32).to_rgb();
-                let color = ((pixel[0] as u32) << 16) | ((pixel[1] as u32) << 8) | (pixel[2] as u32);
-                self.color_array[y * self.width + x] = Color::from_hex(color);
+                let pixel = self.image.get_pixel(x as u32, y as u32).to_rgba();
+                self.alpha_array[y * self.width + x] = pixel[3] as f32 / 255.0;
+                self.color_array[y * self.width + x] = if srgb {
+                    let color = ((pixel[0] as u32) << 16) | ((pixel[1] as u32) << 8) | (pixel[2] as u32);
+                    Radiance::from(Color::from_hex(color))
+                } else {
+                    Radiance::new(pixel[0] as f32 / 255.0, pixel[1] as f32 / 255.0, pixel[2] as f32 / 255.0)
+                };
             }
         }
     }
 
-    pub fn get_color(&self, x: usize, y: usize) -> Color {
+    pub fn get_color(&self, x: usize, y: usize) -> Radiance {
         if x >= self.width || y >= self.height {
-            Color::from_hex(0xFF00FF)
+            Radiance::from(Color::from_hex(0xFF00FF))
         } else {
             self.color_array[y * self.width + x]
         }
     }
+
+    pub fn get_alpha(&self, x: usize, y: usize) -> f32 {
+        if x >= self.width || y >= self.height {
+            1.0
+        } else {
+            self.alpha_array[y * self.width + x]
+        }
+    }
+
+    // Copia de un rectángulo de la imagen como textura propia, para que el filtro, la
+    // repetición y los mipmaps nunca lean las baldosas vecinas de un atlas. None si el
+    // rectángulo está vacío o no cabe en la imagen. Una baldosa girada está guardada
+    // 90° en sentido horario: se recorta con los lados cambiados y se endereza
+    pub fn tile(&self, rect: &AtlasRect) -> Option<Texture> {
+        let (width, height) = if rect.rotated { (rect.height, rect.width) } else { (rect.width, rect.height) };
+        let fits = rect.x.checked_add(width).is_some_and(|right| right as usize <= self.width)
+            && rect.y.checked_add(height).is_some_and(|bottom| bottom as usize <= self.height);
+        if !fits || width == 0 || height == 0 {
+            return None;
+        }
+        let image = self.image.crop_imm(rect.x, rect.y, width, height);
+        Some(Texture::from_image(if rect.rotated { image.rotate270() } else { image }))
+    }
+
+    // Texel más cercano a (u, v), con (0, 0) la esquina superior izquierda de la imagen
+    pub fn nearest_texel(&self, u: f32, v: f32, wrap: WrapMode) -> (usize, usize) {
+        let x = (u * self.width as f32).floor() as i64;
+        let y = (v * self.height as f32).floor() as i64;
+        (wrap.apply(x, self.width), wrap.apply(y, self.height))
+    }
+
+    // Mipmaps por debajo de la imagen completa, hasta un solo texel
+    fn mips(&self) -> &[MipLevel] {
+        self.mips.get_or_init(|| {
+            let mut level = MipLevel { width: self.width, height: self.height, texels: self.color_array.clone() };
+            let mut mips = Vec::new();
+            while level.width > 1 || level.height > 1 {
+                level = level.halve();
+                mips.push(level.clone());
+            }
+            mips
+        })
+    }
+}
+
+// Rectángulo de la imagen de un atlas en píxeles, desde la esquina superior izquierda.
+// Los campos siguen el JSON de los empaquetadores de atlas como TexturePacker, donde
+// el tamaño es el de la baldosa derecha aunque esté guardada girada
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct AtlasRect {
+    pub x: u32,
+    pub y: u32,
+    #[serde(rename = "w")]
+    pub width: u32,
+    #[serde(rename = "h")]
+    pub height: u32,
+    // Se lee del marco que rodea al rectángulo, ver `AtlasFrame`
+    #[serde(skip)]
+    pub rotated: bool,
+}
+
+#[derive(Deserialize)]
+struct AtlasLayout {
+    frames: AtlasFrames,
+}
+
+// Los empaquetadores escriben los marcos como un objeto por nombre o como una lista
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AtlasFrames {
+    Hash(BTreeMap<String, AtlasFrame>),
+    Array(Vec<NamedAtlasFrame>),
+}
+
+#[derive(Deserialize)]
+struct AtlasFrame {
+    frame: AtlasRect,
+    #[serde(default)]
+    rotated: bool,
+}
+
+impl AtlasFrame {
+    fn rect(self) -> AtlasRect {
+        AtlasRect { rotated: self.rotated, ..self.frame }
+    }
+}
+
+#[derive(Deserialize)]
+struct NamedAtlasFrame {
+    filename: String,
+    #[serde(flatten)]
+    frame: AtlasFrame,
+}
+
+// Baldosas con nombre de un atlas en JSON: {"frames": {"nombre": {"frame": {"x", "y", "w", "h"}}}}
+// o los mismos marcos como una lista con un "filename" cada uno
+pub fn parse_atlas_layout(json: &str) -> serde_json::Result<Vec<(String, AtlasRect)>> {
+    let layout: AtlasLayout = serde_json::from_str(json)?;
+    Ok(match layout.frames {
+        AtlasFrames::Hash(frames) => frames.into_iter().map(|(name, frame)| (name, frame.rect())).collect(),
+        AtlasFrames::Array(frames) => frames.into_iter().map(|frame| (frame.filename, frame.frame.rect())).collect(),
+    })
+}
+
+// Imagen de toda la esfera de direcciones alrededor de la escena
+#[derive(Debug, Clone)]
+pub enum EnvironmentMap {
+    // Imagen de latitud y longitud: u gira alrededor del eje y, v va de +y (arriba) a -y
+    Equirectangular(Arc<Texture>),
+    // Seis caras cuadradas en el orden +x, -x, +y, -y, +z, -z
+    Cubemap([Arc<Texture>; 6]),
+}
+
+// Las imágenes de entorno son fotografías suaves: bilineal, dando la vuelta al eje y
+// en la imagen de latitud y longitud y deteniéndose en los bordes de cada cara del cubo
+const EQUIRECTANGULAR_SAMPLER: Sampler = Sampler { filter: Filter::Bilinear, wrap: WrapMode::Repeat };
+const CUBEMAP_SAMPLER: Sampler = Sampler { filter: Filter::Bilinear, wrap: WrapMode::Clamp };
+
+impl EnvironmentMap {
+    pub fn lookup(&self, direction: &Vec3) -> Radiance {
+        match self {
+            EnvironmentMap::Equirectangular(texture) => {
+                let (u, v) = direction_to_equirectangular(direction);
+                // v se detiene en los polos en lugar de pasar al otro: se queda entre
+                // los centros de la primera y la última fila
+                let half_texel = 0.5 / texture.height as f32;
+                EQUIRECTANGULAR_SAMPLER.sample(texture, u, v.clamp(half_texel, 1.0 - half_texel), 0.0)
+            }
+            EnvironmentMap::Cubemap(faces) => {
+                let (face, u, v) = cubemap_face(direction);
+                CUBEMAP_SAMPLER.sample(&faces[face], u, v, 0.0)
+            }
+        }
+    }
+}
+
+// Coordenadas equirrectangulares de una dirección unitaria; el centro de la imagen mira hacia -z
+pub fn direction_to_equirectangular(direction: &Vec3) -> (f32, f32) {
+    let u = 0.5 + direction.x.atan2(-direction.z) / (2.0 * PI);
+    let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+    (u, v)
+}
+
+pub fn equirectangular_to_direction(u: f32, v: f32) -> Vec3 {
+    let phi = (u - 0.5) * 2.0 * PI;
+    let theta = v * PI;
+    Vec3::new(theta.sin() * phi.sin(), theta.cos(), -theta.sin() * phi.cos())
+}
+
+// Cara y coordenadas en la cara de una dirección, según la disposición de cubemaps de OpenGL
+fn cubemap_face(direction: &Vec3) -> (usize, f32, f32) {
+    let (x, y, z) = (direction.x, direction.y, direction.z);
+    let (face, major, s, t) = if x.abs() >= y.abs() && x.abs() >= z.abs() {
+        if x > 0.0 { (0, x, -z, -y) } else { (1, -x, z, -y) }
+    } else if y.abs() >= z.abs() {
+        if y > 0.0 { (2, y, x, z) } else { (3, -y, x, -z) }
+    } else if z > 0.0 {
+        (4, z, x, -y)
+    } else {
+        (5, -z, -x, -y)
+    };
+    (face, (s / major + 1.0) * 0.5, (t / major + 1.0) * 0.5)
 }
 
 impl fmt::Debug for Texture {
@@ -52,4 +393,109 @@ impl fmt::Debug for Texture {
             .field("height", &self.height)
             .finish()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb};
+
+    // Textura de 4x4 texeles con el valor x + 4y en cada canal
+    fn ramp() -> Texture {
+        let image = ImageBuffer::from_fn(4, 4, |x, y| {
+            let value = (x + 4 * y) as f32;
+            Rgb([value, value, value])
+        });
+        Texture::from_image(DynamicImage::ImageRgb32F(image))
+    }
+
+    #[test]
+    fn test_atlas_tiles_do_not_bleed() {
+        let layout = parse_atlas_layout(
+            r#"{"frames": [
+                {"filename": "left", "frame": {"x": 0, "y": 0, "w": 2, "h": 4}, "rotated": false},
+                {"filename": "right", "frame": {"x": 2, "y": 0, "w": 2, "h": 4}}
+            ], "meta": {"size": {"w": 4, "h": 4}}}"#,
+        )
+        .unwrap();
+        assert_eq!(layout[1], ("right".to_string(), AtlasRect { x: 2, y: 0, width: 2, height: 4, rotated: false }));
+
+        let texture = ramp();
+        let right = texture.tile(&layout[1].1).unwrap();
+        assert_eq!((right.width, right.height), (2, 4));
+        // Ni el filtro ni los mipmaps leen la columna 1, que es de la otra baldosa
+        for filter in [Filter::Bilinear, Filter::Trilinear] {
+            let value = Sampler::new(filter, WrapMode::Repeat).sample(&right, 0.0, 0.125, 1.0).r;
+            assert!(value >= 2.0, "{:?}: {}", filter, value);
+        }
+        assert!(texture.tile(&AtlasRect { x: 3, y: 0, width: 2, height: 1, rotated: false }).is_none());
+    }
+
+    #[test]
+    fn test_rotated_atlas_tile() {
+        // Baldosa de 1x3 guardada girada en la primera fila: 3 de ancho y 1 de alto
+        let layout = parse_atlas_layout(
+            r#"{"frames": {"column": {"frame": {"x": 0, "y": 0, "w": 1, "h": 3}, "rotated": true}}}"#,
+        )
+        .unwrap();
+        assert!(layout[0].1.rotated);
+
+        let tile = ramp().tile(&layout[0].1).unwrap();
+        assert_eq!((tile.width, tile.height), (1, 3));
+        // El giro horario dejó la parte de arriba de la baldosa en el extremo derecho de la fila
+        let nearest = Sampler::new(Filter::Nearest, WrapMode::Clamp);
+        let column: Vec<f32> = (0..3).map(|y| nearest.sample(&tile, 0.5, (y as f32 + 0.5) / 3.0, 0.0).r).collect();
+        assert_eq!(column, vec![2.0, 1.0, 0.0]);
+        assert!(ramp().tile(&AtlasRect { x: 0, y: 2, width: 1, height: 3, rotated: true }).is_some());
+        assert!(ramp().tile(&AtlasRect { x: 0, y: 2, width: 3, height: 1, rotated: true }).is_none());
+    }
+
+    #[test]
+    fn test_wrap_modes() {
+        let texture = ramp();
+        let nearest = |wrap, u| Sampler::new(Filter::Nearest, wrap).sample(&texture, u, 0.1, 0.0).r;
+        // Un texel a la derecha del borde
+        assert_eq!(nearest(WrapMode::Repeat, 1.1), 0.0);
+        assert_eq!(nearest(WrapMode::Clamp, 1.1), 3.0);
+        assert_eq!(nearest(WrapMode::Mirror, 1.1), 3.0);
+        assert_eq!(nearest(WrapMode::Mirror, 1.3), 2.0);
+        assert_eq!(nearest(WrapMode::Mirror, -0.1), 0.0);
+    }
+
+    #[test]
+    fn test_bilinear_blends_neighbours() {
+        let texture = ramp();
+        let sampler = Sampler::new(Filter::Bilinear, WrapMode::Clamp);
+        // Entre los centros de los texeles (1, 0) y (2, 0)
+        assert!((sampler.sample(&texture, 0.5, 0.125, 0.0).r - 1.5).abs() < 1e-5);
+        // En el centro de un texel no se mezcla nada
+        assert!((sampler.sample(&texture, 0.375, 0.625, 0.0).r - 9.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_equirectangular_lookup_wraps_around_y_only() {
+        // Fila de arriba 0..3 y de abajo 10..13
+        let image = ImageBuffer::from_fn(4, 2, |x, y| {
+            let value = (x + 10 * y) as f32;
+            Rgb([value, value, value])
+        });
+        let map = EnvironmentMap::Equirectangular(Arc::new(Texture::from_image(DynamicImage::ImageRgb32F(image))));
+        // En la costura de u = 0 se mezclan la última columna y la primera
+        let seam = map.lookup(&equirectangular_to_direction(0.0, 0.25)).r;
+        assert!((seam - 1.5).abs() < 1e-4, "{}", seam);
+        // En el polo no se mezcla la fila del otro polo
+        assert!(map.lookup(&Vec3::new(0.0, 1.0, 0.0)).r <= 3.0);
+        assert!(map.lookup(&Vec3::new(0.0, -1.0, 0.0)).r >= 10.0);
+    }
+
+    #[test]
+    fn test_trilinear_uses_mip_chain() {
+        let texture = ramp();
+        let sampler = Sampler::new(Filter::Trilinear, WrapMode::Repeat);
+        // Con una huella menor que un texel se ve la imagen completa
+        assert!((sampler.sample(&texture, 0.375, 0.625, 0.1).r - 9.0).abs() < 1e-5);
+        // Con una huella de toda la textura, el promedio de todos los texeles
+        assert!((sampler.sample(&texture, 0.3, 0.7, 1.0).r - 7.5).abs() < 1e-5);
+        // Con la de dos texeles, el promedio del bloque de 2x2 que contiene el punto
+        assert!((sampler.sample(&texture, 0.25, 0.25, 0.5).r - 2.5).abs() < 1e-5);
+    }
+}
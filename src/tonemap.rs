@@ -0,0 +1,159 @@
+use std::fmt;
+use crate::radiance::Radiance;
+
+// Operadores para llevar la radiancia HDR al rango 0..1 antes de cuantizar
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMapOperator {
+    Clamp,
+    Reinhard,
+    // Reinhard con punto blanco: la luminancia `white_point` se lleva exactamente a 1
+    ReinhardExtended { white_point: f32 },
+    AcesFilmic,
+}
+
+impl ToneMapOperator {
+    pub fn apply(&self, radiance: Radiance) -> Radiance {
+        match *self {
+            ToneMapOperator::Clamp => Radiance::new(
+                radiance.r.clamp(0.0, 1.0),
+                radiance.g.clamp(0.0, 1.0),
+                radiance.b.clamp(0.0, 1.0),
+            ),
+            ToneMapOperator::Reinhard => {
+                scale_luminance(radiance, |l| l / (1.0 + l))
+            }
+            ToneMapOperator::ReinhardExtended { white_point } => {
+                let white_sq = white_point * white_point;
+                scale_luminance(radiance, |l| l * (1.0 + l / white_sq) / (1.0 + l))
+            }
+            ToneMapOperator::AcesFilmic => Radiance::new(
+                aces_filmic(radiance.r),
+                aces_filmic(radiance.g),
+                aces_filmic(radiance.b),
+            ),
+        }
+    }
+
+    // Siguiente operador en el ciclo que se recorre con el teclado
+    pub fn next(&self) -> ToneMapOperator {
+        match self {
+            ToneMapOperator::Clamp => ToneMapOperator::Reinhard,
+            ToneMapOperator::Reinhard => ToneMapOperator::ReinhardExtended { white_point: 4.0 },
+            ToneMapOperator::ReinhardExtended { .. } => ToneMapOperator::AcesFilmic,
+            ToneMapOperator::AcesFilmic => ToneMapOperator::Clamp,
+        }
+    }
+}
+
+impl fmt::Display for ToneMapOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToneMapOperator::Clamp => write!(f, "Clamp"),
+            ToneMapOperator::Reinhard => write!(f, "Reinhard"),
+            ToneMapOperator::ReinhardExtended { white_point } => write!(f, "Reinhard extendido (blanco {})", white_point),
+            ToneMapOperator::AcesFilmic => write!(f, "ACES filmic"),
+        }
+    }
+}
+
+// Aplica la curva a la luminancia y escala los tres canales por igual para no alterar el tono
+fn scale_luminance(radiance: Radiance, curve: impl Fn(f32) -> f32) -> Radiance {
+    let luminance = radiance.luminance();
+    if luminance <= 0.0 {
+        return Radiance::black();
+    }
+    let mapped = radiance * (curve(luminance) / luminance);
+    Radiance::new(mapped.r.min(1.0), mapped.g.min(1.0), mapped.b.min(1.0))
+}
+
+// Aproximación de Narkowicz a la curva filmic de ACES
+fn aces_filmic(x: f32) -> f32 {
+    let x = x.max(0.0);
+    let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+    ((x * (a * x + b)) / (x * (c * x + d) + e)).clamp(0.0, 1.0)
+}
+
+// Etapa de tone mapping entre la salida de cast_ray y el framebuffer
+#[derive(Debug, Clone, Copy)]
+pub struct ToneMapper {
+    pub operator: ToneMapOperator,
+    pub exposure_ev: f32, // Exposición en pasos (EV): cada paso duplica la luz
+}
+
+impl ToneMapper {
+    pub fn new(operator: ToneMapOperator, exposure_ev: f32) -> Self {
+        ToneMapper { operator, exposure_ev }
+    }
+
+    pub fn adjust_exposure(&mut self, delta_ev: f32) {
+        self.exposure_ev = (self.exposure_ev + delta_ev).clamp(-10.0, 10.0);
+    }
+
+    pub fn apply(&self, radiance: Radiance) -> Radiance {
+        self.operator.apply(radiance * 2f32.powf(self.exposure_ev))
+    }
+}
+
+impl fmt::Display for ToneMapper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, exposición {:+.1} EV", self.operator, self.exposure_ev)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exposure_doubles_per_stop() {
+        let mapper = ToneMapper::new(ToneMapOperator::Clamp, 2.0);
+        let result = mapper.apply(Radiance::new(0.05, 0.1, 0.2));
+        assert!((result.r - 0.2).abs() < 1e-6);
+        assert!((result.g - 0.4).abs() < 1e-6);
+        assert!((result.b - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_operators_stay_in_range() {
+        let operators = [
+            ToneMapOperator::Clamp,
+            ToneMapOperator::Reinhard,
+            ToneMapOperator::ReinhardExtended { white_point: 4.0 },
+            ToneMapOperator::AcesFilmic,
+        ];
+        for operator in operators {
+            for value in [0.0, 0.01, 0.5, 1.0, 3.0, 100.0] {
+                let result = operator.apply(Radiance::new(value, value * 0.5, value * 2.0));
+                for c in [result.r, result.g, result.b] {
+                    assert!((0.0..=1.0).contains(&c), "{} fuera de rango con {}", c, operator);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_reinhard_compresses_without_clipping() {
+        let operator = ToneMapOperator::Reinhard;
+        let bright = operator.apply(Radiance::new(3.0, 3.0, 3.0));
+        let brighter = operator.apply(Radiance::new(6.0, 6.0, 6.0));
+        assert!(bright.r < 1.0);
+        assert!(brighter.r > bright.r);
+    }
+
+    #[test]
+    fn test_reinhard_extended_maps_white_point_to_one() {
+        let operator = ToneMapOperator::ReinhardExtended { white_point: 4.0 };
+        let result = operator.apply(Radiance::new(4.0, 4.0, 4.0));
+        assert!((result.r - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_aces_is_monotonic() {
+        let mut previous = 0.0;
+        for i in 1..100 {
+            let value = aces_filmic(i as f32 * 0.1);
+            assert!(value >= previous);
+            previous = value;
+        }
+    }
+}
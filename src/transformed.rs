@@ -0,0 +1,161 @@
+use nalgebra_glm::{self as glm, Mat3, Mat4, Vec3, Vec4};
+use crate::aabb::Aabb;
+use crate::light::{Light, LightShape};
+use crate::ray_intersect::{Intersect, RayIntersect};
+
+// Primitiva o grupo movido, girado y escalado con una matriz afín de 4x4. El rayo se
+// lleva al espacio del objeto y el golpe vuelve al mundo, así que la geometría no se
+// copia: con T = Arc<...> muchas instancias comparten el mismo objeto
+pub struct Transformed<T: RayIntersect> {
+    object: T,
+    to_world: Mat4,
+    to_object: Mat4,
+    linear: Mat3,        // Parte lineal de to_world, para los vectores tangentes
+    normal_matrix: Mat3, // Inversa traspuesta de la parte lineal, para las normales
+}
+
+impl<T: RayIntersect> Transformed<T> {
+    // None si la matriz no se puede invertir, como con una escala de cero
+    pub fn new(object: T, to_world: Mat4) -> Option<Self> {
+        let to_object = to_world.try_inverse()?;
+        let linear = glm::mat4_to_mat3(&to_world);
+        let normal_matrix = glm::mat4_to_mat3(&to_object).transpose();
+        Some(Transformed { object, to_world, to_object, linear, normal_matrix })
+    }
+
+    fn point_to_world(&self, point: &Vec3) -> Vec3 {
+        glm::vec4_to_vec3(&(self.to_world * Vec4::new(point.x, point.y, point.z, 1.0)))
+    }
+}
+
+// Matriz afín que escala, luego gira (en grados alrededor de x, luego y, luego z) y
+// por último traslada
+pub fn affine(position: Vec3, rotation_degrees: Vec3, scale: Vec3) -> Mat4 {
+    let rotation = glm::rotation(rotation_degrees.z.to_radians(), &Vec3::z())
+        * glm::rotation(rotation_degrees.y.to_radians(), &Vec3::y())
+        * glm::rotation(rotation_degrees.x.to_radians(), &Vec3::x());
+    glm::translation(&position) * rotation * glm::scaling(&scale)
+}
+
+impl<T: RayIntersect> RayIntersect for Transformed<T> {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
+        let origin = glm::vec4_to_vec3(&(self.to_object * Vec4::new(ray_origin.x, ray_origin.y, ray_origin.z, 1.0)));
+        let direction = glm::vec4_to_vec3(&(self.to_object * Vec4::new(ray_direction.x, ray_direction.y, ray_direction.z, 0.0)));
+        // Las primitivas esperan una dirección unitaria; con escala la distancia en el
+        // espacio del objeto se estira por el largo de la dirección transformada
+        let stretch = direction.norm();
+        let hit = self.object.ray_intersect(&origin, &(direction / stretch));
+        if !hit.is_intersecting {
+            return hit;
+        }
+        let distance = hit.distance / stretch;
+
+        // Los vectores tangentes se estiran con el objeto y una unidad de uv con ellos
+        let (tangent, bitangent) = hit.tangent_frame();
+        let tangent = self.linear * tangent;
+        let bitangent = self.linear * bitangent;
+        Intersect {
+            point: ray_origin + ray_direction * distance,
+            normal: (self.normal_matrix * hit.normal).normalize(),
+            smooth_normal: (self.normal_matrix * hit.smooth_normal).normalize(),
+            tangent: tangent.normalize(),
+            bitangent: bitangent.normalize(),
+            distance,
+            uv_extent: hit.uv_extent * (tangent.norm() * bitangent.norm()).sqrt(),
+            ..hit
+        }
+    }
+
+    // Caja que contiene las ocho esquinas de la caja del objeto ya transformadas
+    fn bounding_box(&self) -> Aabb {
+        let local = self.object.bounding_box();
+        if local.extent().iter().any(|side| *side < 0.0) {
+            return local;
+        }
+        (0..8).fold(Aabb::empty(), |bounds, corner| {
+            let pick = |axis: usize| if corner & (1 << axis) == 0 { local.min[axis] } else { local.max[axis] };
+            bounds.grow(&self.point_to_world(&Vec3::new(pick(0), pick(1), pick(2))))
+        })
+    }
+
+    // Las luces del objeto se mueven con él. Una caja girada ya no está alineada a los
+    // ejes, así que se cambia por la caja alineada que la contiene
+    fn emitters(&self) -> Vec<Light> {
+        let scale = self.linear.determinant().abs().cbrt();
+        let absolute = self.linear.abs();
+        self.object
+            .emitters()
+            .into_iter()
+            .map(|light| Light {
+                position: self.point_to_world(&light.position),
+                shape: match light.shape {
+                    LightShape::Point => LightShape::Point,
+                    LightShape::Rectangle { u, v } => LightShape::Rectangle { u: self.linear * u, v: self.linear * v },
+                    LightShape::Sphere { radius } => LightShape::Sphere { radius: radius * scale },
+                    LightShape::Box { size } => LightShape::Box { size: absolute * size },
+                    LightShape::Triangle { corners } => LightShape::Triangle { corners: corners.map(|corner| self.linear * corner) },
+                },
+                ..light
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Cube;
+    use crate::materials::Material;
+    use crate::radiance::Radiance;
+    use std::sync::Arc;
+
+    fn unit_cube(material: Material) -> Cube {
+        Cube::new(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5), material)
+    }
+
+    #[test]
+    fn test_rotated_and_scaled_cube() {
+        // Cubo unitario estirado al doble en x y girado 90° alrededor de y: queda de
+        // 1 de ancho en x y 2 en z, centrado en (5, 0, 0)
+        let shared = Arc::new(unit_cube(Material::black()));
+        let transform = affine(Vec3::new(5.0, 0.0, 0.0), Vec3::new(0.0, 90.0, 0.0), Vec3::new(2.0, 1.0, 1.0));
+        let instance = Transformed::new(Arc::clone(&shared), transform).unwrap();
+
+        let bounds = instance.bounding_box();
+        assert!((bounds.min - Vec3::new(4.5, -0.5, -1.0)).norm() < 1e-5);
+        assert!((bounds.max - Vec3::new(5.5, 0.5, 1.0)).norm() < 1e-5);
+
+        let hit = instance.ray_intersect(&Vec3::new(5.0, 0.0, 10.0), &Vec3::new(0.0, 0.0, -1.0));
+        assert!(hit.is_intersecting && (hit.distance - 9.0).abs() < 1e-5);
+        assert!((hit.point - Vec3::new(5.0, 0.0, 1.0)).norm() < 1e-5);
+        assert!((hit.normal - Vec3::new(0.0, 0.0, 1.0)).norm() < 1e-5);
+        assert!(hit.tangent.dot(&hit.normal).abs() < 1e-5);
+
+        let hit = instance.ray_intersect(&Vec3::new(10.0, 0.2, 0.7), &Vec3::new(-1.0, 0.0, 0.0));
+        assert!(hit.is_intersecting && (hit.distance - 4.5).abs() < 1e-5);
+        assert!((hit.normal - Vec3::new(1.0, 0.0, 0.0)).norm() < 1e-5);
+        assert!(!instance.ray_intersect(&Vec3::new(10.0, 0.2, 1.2), &Vec3::new(-1.0, 0.0, 0.0)).is_intersecting);
+
+        // Otra instancia del mismo cubo, sin copiarlo
+        let other = Transformed::new(Arc::clone(&shared), affine(Vec3::new(0.0, 3.0, 0.0), Vec3::zeros(), Vec3::repeat(1.0))).unwrap();
+        assert!(other.ray_intersect(&Vec3::new(0.0, 10.0, 0.0), &Vec3::new(0.0, -1.0, 0.0)).is_intersecting);
+        assert_eq!(Arc::strong_count(&shared), 3);
+        assert!(Transformed::new(unit_cube(Material::black()), glm::scaling(&Vec3::new(1.0, 0.0, 1.0))).is_none());
+    }
+
+    #[test]
+    fn test_emitters_follow_the_instance() {
+        let mut lava = Material::black();
+        lava.emissive_color = Some(Radiance::new(1.0, 0.4, 0.1));
+        lava.emissive_intensity = 2.0;
+        let transform = affine(Vec3::new(0.0, 2.0, 0.0), Vec3::new(0.0, 0.0, 90.0), Vec3::new(3.0, 1.0, 1.0));
+        let instance = Transformed::new(unit_cube(lava), transform).unwrap();
+        let lights = instance.emitters();
+        assert_eq!(lights.len(), 1);
+        assert!((lights[0].position - Vec3::new(0.0, 2.0, 0.0)).norm() < 1e-5);
+        match lights[0].shape {
+            LightShape::Box { size } => assert!((size - Vec3::new(1.0, 3.0, 1.0)).norm() < 1e-5),
+            _ => panic!("se esperaba una caja"),
+        }
+    }
+}
@@ -0,0 +1,580 @@
+use nalgebra_glm::Vec3;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use crate::color::Color;
+use crate::materials::Material;
+use crate::radiance::Radiance;
+use crate::voxel::{BlockId, VoxelGrid};
+
+// Lado máximo de la rejilla en vóxeles, el tamaño de la escena de MagicaVoxel. Los
+// archivos con modelos más separados se rechazan antes de reservar la rejilla
+const MAX_EXTENT: i64 = 2048;
+// Límites del recorrido de la escena y de los vóxeles colocados, que un archivo
+// pequeño puede multiplicar compartiendo nodos y modelos
+const MAX_NODE_VISITS: usize = 1 << 16;
+const MAX_INSTANCES: usize = 1 << 14;
+const MAX_PLACED_VOXELS: usize = 1 << 26;
+
+// Modelos de un archivo de MagicaVoxel colocados según su escena y juntados en una
+// rejilla de bloques, con un material por cada color de la paleta que se usa
+pub struct VoxModel {
+    pub grid: VoxelGrid,
+    pub material_count: usize,
+    pub warnings: Vec<String>, // Materiales que no se pudieron traducir del todo
+}
+
+#[derive(Debug)]
+pub struct VoxError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for VoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for VoxError {}
+
+// Lee un .vox. MagicaVoxel tiene z hacia arriba: su (x, y, z) pasa a (x, z, -y) de la
+// escena. El origen de la escena de MagicaVoxel queda en `position` y cada vóxel
+// mide `voxel_size`; los materiales reciben ids a partir de `first_id`
+pub fn load_vox(path: &str, position: Vec3, voxel_size: f32, first_id: usize) -> Result<VoxModel, VoxError> {
+    let error = |message: String| VoxError { path: path.to_string(), message };
+    let bytes = fs::read(path).map_err(|e| error(format!("no se pudo leer el modelo: {}", e)))?;
+    let file = parse_vox(&bytes).map_err(error)?;
+    let instances = file.instances().map_err(error)?;
+    if instances.is_empty() {
+        return Err(error("el modelo no tiene vóxeles visibles".to_string()));
+    }
+    let mut placed = 0usize;
+    for (model, _) in &instances {
+        let (_, voxels) = file.models.get(*model).ok_or_else(|| error(format!("el modelo {} no existe", model)))?;
+        placed += voxels.len();
+    }
+    if placed > MAX_PLACED_VOXELS {
+        return Err(error(format!("la escena coloca {} vóxeles, más de los {} que se admiten", placed, MAX_PLACED_VOXELS)));
+    }
+
+    // Celdas ocupadas en coordenadas de la escena. Se recorren dos veces sin guardarlas:
+    // primero para la caja que las contiene, que se controla a medida que crece, y
+    // después para llenar la rejilla
+    let cells = || {
+        instances.iter().flat_map(|(model, transform)| {
+            let (size, voxels) = &file.models[*model];
+            voxels.iter().map(move |&[x, y, z, color]| {
+                let [x, y, z] = transform.place([x, y, z], *size);
+                ([x, z, -y - 1], color)
+            })
+        })
+    };
+    let mut min = [i64::MAX; 3];
+    let mut max = [i64::MIN; 3];
+    for (cell, _) in cells() {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(cell[axis]);
+            max[axis] = max[axis].max(cell[axis]);
+        }
+        let extents = [0, 1, 2].map(|axis| max[axis] - min[axis] + 1);
+        if extents.iter().any(|&extent| extent > MAX_EXTENT) {
+            return Err(error(format!(
+                "los vóxeles ocupan más de {}x{}x{}, más de los {} por lado que admite la rejilla",
+                extents[0], extents[1], extents[2], MAX_EXTENT
+            )));
+        }
+    }
+    let dims = [0, 1, 2].map(|axis| (max[axis] - min[axis] + 1) as usize);
+    dims[0]
+        .checked_mul(dims[1])
+        .and_then(|area| area.checked_mul(dims[2]))
+        .ok_or_else(|| error(format!("la rejilla de {}x{}x{} vóxeles es demasiado grande", dims[0], dims[1], dims[2])))?;
+    let origin = position + Vec3::new(min[0] as f32, min[1] as f32, min[2] as f32) * voxel_size;
+
+    let mut grid = VoxelGrid::new(origin, voxel_size, dims);
+    let mut warnings = Vec::new();
+    let mut ids: HashMap<u8, BlockId> = HashMap::new();
+    for (cell, color) in cells() {
+        let id = match ids.get(&color) {
+            Some(id) => *id,
+            None => {
+                let mut material = palette_material(file.palette[color as usize], file.materials.get(&color), &mut warnings);
+                material.id = first_id + ids.len();
+                let id = grid.add_material(material);
+                ids.insert(color, id);
+                id
+            }
+        };
+        grid.set((cell[0] - min[0]) as usize, (cell[1] - min[1]) as usize, (cell[2] - min[2]) as usize, id);
+    }
+    Ok(VoxModel { grid, material_count: ids.len(), warnings })
+}
+
+// Material de un color de la paleta según su entrada MATL: el vidrio da
+// transparencia, el metal reflejos y la emisión una luz del color de la paleta
+fn palette_material(rgba: [u8; 4], matl: Option<&Dict>, warnings: &mut Vec<String>) -> Material {
+    let [r, g, b, _] = rgba;
+    let diffuse = Radiance::from(Color::new(r as i32, g as i32, b as i32));
+    let mut material = Material::new(diffuse, 50.0, [0.6, 0.3], 0.0, 0.0, 1.0);
+    let Some(matl) = matl else { return material };
+    let value = |key: &str| matl.get(key).and_then(|text| text.parse::<f32>().ok());
+
+    let kind = matl.get("_type").map_or("_diffuse", String::as_str);
+    match kind {
+        "_diffuse" => {}
+        "_metal" => {
+            let metal = value("_metal").or(value("_weight")).unwrap_or(1.0).clamp(0.0, 1.0);
+            let roughness = value("_rough").unwrap_or(0.1).clamp(0.0, 1.0);
+            material.reflectivity = metal * (1.0 - roughness);
+            material.specular = 2.0 / (roughness * roughness).max(0.01) - 2.0;
+            material.albedo = [1.0 - metal * 0.7, 0.3 + 0.6 * metal * (1.0 - roughness)];
+        }
+        // Blend mezcla vidrio y metal; aquí se queda en vidrio
+        "_glass" | "_blend" => {
+            material.transparency = value("_trans").or(value("_alpha")).unwrap_or(1.0).clamp(0.0, 1.0);
+            // Las versiones viejas guardan el índice de refracción menos uno
+            material.refraction_index = value("_ri").or(value("_ior").map(|ior| ior + 1.0)).unwrap_or(1.5);
+            material.reflectivity = 0.1;
+            material.specular = 125.0;
+        }
+        // La potencia (_flux, de 0 a 4) multiplica la emisión
+        "_emit" => {
+            material.emissive_color = Some(diffuse);
+            material.emissive_intensity = value("_emit").unwrap_or(1.0) * (1.0 + value("_flux").unwrap_or(0.0));
+        }
+        other => {
+            let warning = format!("los materiales de tipo {} se dibujan como difusos", other);
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+        }
+    }
+    material
+}
+
+type Dict = HashMap<String, String>;
+
+// Tamaño y vóxeles (x, y, z, color) de cada modelo
+type Model = ([i64; 3], Vec<[u8; 4]>);
+
+enum Node {
+    Transform { child: i32, layer: i32, hidden: bool, transform: Transform },
+    Group { children: Vec<i32> },
+    Shape { models: Vec<i32> },
+}
+
+struct VoxFile {
+    models: Vec<Model>,
+    palette: [[u8; 4]; 256], // Por índice de color; el 0 no se usa
+    materials: HashMap<u8, Dict>,
+    nodes: HashMap<i32, Node>,
+    hidden_layers: HashSet<i32>,
+}
+
+impl VoxFile {
+    // Modelos a dibujar con su transformación acumulada. Los archivos sin escena
+    // (anteriores a la versión 0.99) tienen sus modelos en el origen. Varios nodos
+    // pueden compartir un hijo, así que un archivo dañado puede pedir un recorrido
+    // exponencial aunque no tenga ciclos: las visitas y las instancias se limitan
+    fn instances(&self) -> Result<Vec<(usize, Transform)>, String> {
+        enum Step {
+            Enter(i32, Transform),
+            Leave(i32),
+        }
+        if self.nodes.is_empty() {
+            return Ok((0..self.models.len()).map(|model| (model, Transform::IDENTITY)).collect());
+        }
+        let mut instances = Vec::new();
+        // Nodos del camino desde la raíz hasta el actual, para rechazar los ciclos
+        let mut path = HashSet::new();
+        let mut visits = 0;
+        let mut stack = vec![Step::Enter(0, Transform::IDENTITY)];
+        while let Some(step) = stack.pop() {
+            let (id, parent) = match step {
+                Step::Enter(id, parent) => (id, parent),
+                Step::Leave(id) => {
+                    path.remove(&id);
+                    continue;
+                }
+            };
+            visits += 1;
+            if visits > MAX_NODE_VISITS {
+                return Err(format!("la escena visita más de {} nodos", MAX_NODE_VISITS));
+            }
+            if !path.insert(id) {
+                return Err(format!("el nodo {} se contiene a sí mismo", id));
+            }
+            stack.push(Step::Leave(id));
+            match self.nodes.get(&id) {
+                Some(Node::Transform { child, layer, hidden, transform }) if !hidden && !self.hidden_layers.contains(layer) => {
+                    stack.push(Step::Enter(*child, parent.then(transform)))
+                }
+                Some(Node::Group { children }) => stack.extend(children.iter().rev().map(|&child| Step::Enter(child, parent))),
+                Some(Node::Shape { models }) => {
+                    let models = models.iter().filter(|&&model| model >= 0);
+                    if instances.len() + models.clone().count() > MAX_INSTANCES {
+                        return Err(format!("la escena tiene más de {} instancias de modelos", MAX_INSTANCES));
+                    }
+                    instances.extend(models.map(|&model| (model as usize, parent)))
+                }
+                // Nodos ocultos o de capas ocultas, y los que no existen
+                _ => {}
+            }
+        }
+        Ok(instances)
+    }
+}
+
+// Rotación (una permutación de ejes con signos) y traslación en vóxeles
+#[derive(Clone, Copy)]
+struct Transform {
+    rotation: [[i64; 3]; 3],
+    translation: [i64; 3],
+}
+
+impl Transform {
+    const IDENTITY: Transform = Transform { rotation: [[1, 0, 0], [0, 1, 0], [0, 0, 1]], translation: [0; 3] };
+
+    fn rotate(&self, v: [i64; 3]) -> [i64; 3] {
+        self.rotation.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+    }
+
+    // El hijo se aplica primero
+    fn then(&self, child: &Transform) -> Transform {
+        let mut rotation = [[0; 3]; 3];
+        for (i, row) in rotation.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| self.rotation[i][k] * child.rotation[k][j]).sum();
+            }
+        }
+        let moved = self.rotate(child.translation);
+        Transform { rotation, translation: [0, 1, 2].map(|axis| moved[axis] + self.translation[axis]) }
+    }
+
+    // Celda de un vóxel del modelo. El modelo gira alrededor de su centro, así que se
+    // trabaja con coordenadas dobles para que los centros de vóxel sean enteros
+    fn place(&self, voxel: [u8; 3], size: [i64; 3]) -> [i64; 3] {
+        let doubled = [0, 1, 2].map(|axis| 2 * voxel[axis] as i64 + 1 - size[axis]);
+        let rotated = self.rotate(doubled);
+        [0, 1, 2].map(|axis| (rotated[axis] + 2 * self.translation[axis]).div_euclid(2))
+    }
+
+    // Atributos _r y _t de un cuadro de nTRN. _r guarda en sus bits la columna del
+    // uno de las dos primeras filas y el signo de cada fila
+    fn from_frame(frame: &Dict) -> Result<Transform, String> {
+        let mut transform = Transform::IDENTITY;
+        if let Some(text) = frame.get("_r") {
+            let bits: u8 = text.parse().map_err(|_| format!("rotación inválida '{}'", text))?;
+            let first = (bits & 3) as usize;
+            let second = ((bits >> 2) & 3) as usize;
+            if first > 2 || second > 2 || first == second {
+                return Err(format!("rotación inválida '{}'", text));
+            }
+            let columns = [first, second, 3 - first - second];
+            transform.rotation = [[0; 3]; 3];
+            for (row, &column) in columns.iter().enumerate() {
+                transform.rotation[row][column] = if bits & (16 << row) != 0 { -1 } else { 1 };
+            }
+        }
+        if let Some(text) = frame.get("_t") {
+            let values: Vec<i64> = text.split_whitespace().map(|value| value.parse().map_err(|_| format!("traslación inválida '{}'", text))).collect::<Result<_, _>>()?;
+            if values.len() != 3 {
+                return Err(format!("traslación inválida '{}'", text));
+            }
+            transform.translation = [values[0], values[1], values[2]];
+        }
+        Ok(transform)
+    }
+}
+
+// Lector de los enteros, cadenas y diccionarios de los chunks, en little endian
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let end = self.position.checked_add(count).ok_or("el archivo está cortado")?;
+        let bytes = self.bytes.get(self.position..end).ok_or("el archivo está cortado")?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn int(&mut self) -> Result<i32, String> {
+        let b = self.take(4)?;
+        Ok(i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn count(&mut self) -> Result<usize, String> {
+        usize::try_from(self.int()?).map_err(|_| "cantidad negativa".to_string())
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let length = self.count()?;
+        Ok(String::from_utf8_lossy(self.take(length)?).into_owned())
+    }
+
+    fn dict(&mut self) -> Result<Dict, String> {
+        let count = self.count()?;
+        (0..count).map(|_| Ok((self.string()?, self.string()?))).collect()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len().saturating_sub(self.position)
+    }
+}
+
+fn parse_vox(bytes: &[u8]) -> Result<VoxFile, String> {
+    let mut reader = Reader { bytes, position: 0 };
+    if reader.take(4)? != b"VOX " {
+        return Err("no es un archivo .vox".to_string());
+    }
+    reader.int()?; // Versión
+    if reader.take(4)? != b"MAIN" {
+        return Err("falta el chunk MAIN".to_string());
+    }
+    let content = reader.count()?;
+    let children = reader.count()?;
+    reader.take(content)?;
+    let mut chunks = Reader { bytes: reader.take(children)?, position: 0 };
+
+    let mut file = VoxFile {
+        models: Vec::new(),
+        palette: default_palette(),
+        materials: HashMap::new(),
+        nodes: HashMap::new(),
+        hidden_layers: HashSet::new(),
+    };
+    let mut size = None;
+    while !chunks.is_empty() {
+        let id = chunks.take(4)?;
+        let content = chunks.count()?;
+        let children = chunks.count()?;
+        let mut chunk = Reader { bytes: chunks.take(content)?, position: 0 };
+        chunks.take(children)?;
+        match id {
+            b"SIZE" => size = Some([chunk.int()?, chunk.int()?, chunk.int()?].map(|side| side as i64)),
+            b"XYZI" => {
+                let size = size.take().ok_or("XYZI sin SIZE antes")?;
+                let count = chunk.count()?;
+                let voxels = chunk.take(count.checked_mul(4).ok_or("el archivo está cortado")?)?.chunks_exact(4).map(|v| [v[0], v[1], v[2], v[3]]).collect();
+                file.models.push((size, voxels));
+            }
+            // La entrada i del chunk es el color de índice i + 1
+            b"RGBA" => {
+                for index in 1..256 {
+                    let c = chunk.take(4)?;
+                    file.palette[index] = [c[0], c[1], c[2], c[3]];
+                }
+            }
+            b"MATL" => {
+                let id = chunk.int()?;
+                let properties = chunk.dict()?;
+                if (1..256).contains(&id) {
+                    file.materials.insert(id as u8, properties);
+                }
+            }
+            b"nTRN" => {
+                let id = chunk.int()?;
+                let attributes = chunk.dict()?;
+                let child = chunk.int()?;
+                chunk.int()?; // Reservado
+                let layer = chunk.int()?;
+                // En las animaciones se usa el primer cuadro
+                let frames = chunk.count()?;
+                let transform = if frames > 0 { Transform::from_frame(&chunk.dict()?)? } else { Transform::IDENTITY };
+                let hidden = attributes.get("_hidden").is_some_and(|value| value == "1");
+                file.nodes.insert(id, Node::Transform { child, layer, hidden, transform });
+            }
+            b"nGRP" => {
+                let id = chunk.int()?;
+                chunk.dict()?;
+                let count = chunk.count()?;
+                let children = (0..count).map(|_| chunk.int()).collect::<Result<_, _>>()?;
+                file.nodes.insert(id, Node::Group { children });
+            }
+            b"nSHP" => {
+                let id = chunk.int()?;
+                chunk.dict()?;
+                let count = chunk.count()?;
+                // Cada modelo ocupa al menos su id y un diccionario vacío
+                if count > chunk.remaining() / 8 {
+                    return Err(format!("nSHP dice tener {} modelos, que no caben en el chunk", count));
+                }
+                let mut models = Vec::with_capacity(count);
+                for _ in 0..count {
+                    models.push(chunk.int()?);
+                    chunk.dict()?;
+                }
+                file.nodes.insert(id, Node::Shape { models });
+            }
+            b"LAYR" => {
+                let id = chunk.int()?;
+                if chunk.dict()?.get("_hidden").is_some_and(|value| value == "1") {
+                    file.hidden_layers.insert(id);
+                }
+            }
+            // PACK, cámaras, ajustes del render y notas no cambian la geometría
+            _ => {}
+        }
+    }
+    Ok(file)
+}
+
+// Paleta de MagicaVoxel para los archivos sin chunk RGBA: el cubo de 6x6x6 colores
+// sin el negro, seguido de degradados de rojo, verde, azul y gris
+fn default_palette() -> [[u8; 4]; 256] {
+    let mut palette = [[0; 4]; 256];
+    let steps = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    let mut index = 1;
+    for r in steps {
+        for g in steps {
+            for b in steps {
+                if index < 216 {
+                    palette[index] = [r, g, b, 0xff];
+                    index += 1;
+                }
+            }
+        }
+    }
+    let ramp: Vec<u8> = (1..15u8).rev().filter(|step| step % 3 != 0).map(|step| step * 0x11).collect();
+    for channel in 0..4 {
+        for &value in &ramp {
+            palette[index] = match channel {
+                3 => [value, value, value, 0xff],
+                _ => {
+                    let mut color = [0, 0, 0, 0xff];
+                    color[channel] = value;
+                    color
+                }
+            };
+            index += 1;
+        }
+    }
+    palette
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray_intersect::RayIntersect;
+
+    fn chunk(id: &[u8; 4], content: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend_from_slice(&(content.len() as i32).to_le_bytes());
+        bytes.extend_from_slice(&0i32.to_le_bytes());
+        bytes.extend_from_slice(content);
+        bytes
+    }
+
+    fn ints(values: &[i32]) -> Vec<u8> {
+        values.iter().flat_map(|value| value.to_le_bytes()).collect()
+    }
+
+    fn dict(pairs: &[(&str, &str)]) -> Vec<u8> {
+        let mut bytes = ints(&[pairs.len() as i32]);
+        for (key, value) in pairs {
+            for text in [key, value] {
+                bytes.extend(ints(&[text.len() as i32]));
+                bytes.extend_from_slice(text.as_bytes());
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_load_vox_scene_graph() {
+        // Un modelo de 2x1x1 con un vóxel de vidrio y uno emisivo, girado 90° alrededor
+        // de z y movido 10 vóxeles en x
+        let mut children = chunk(b"SIZE", &ints(&[2, 1, 1]));
+        children.extend(chunk(b"XYZI", &[ints(&[2]), vec![0, 0, 0, 1, 1, 0, 0, 2]].concat()));
+        children.extend(chunk(b"RGBA", &[[200, 220, 255, 255], [255, 180, 60, 255]].concat().repeat(128)));
+        children.extend(chunk(b"MATL", &[ints(&[1]), dict(&[("_type", "_glass"), ("_trans", "0.8"), ("_ior", "0.3")])].concat()));
+        children.extend(chunk(b"MATL", &[ints(&[2]), dict(&[("_type", "_emit"), ("_emit", "0.5"), ("_flux", "2")])].concat()));
+        children.extend(chunk(b"nTRN", &[ints(&[0]), dict(&[]), ints(&[1, -1, -1, 1]), dict(&[])].concat()));
+        children.extend(chunk(b"nGRP", &[ints(&[1]), dict(&[]), ints(&[2, 2, 4])].concat()));
+        children.extend(chunk(b"nTRN", &[ints(&[2]), dict(&[]), ints(&[3, -1, 0, 1]), dict(&[("_r", "17"), ("_t", "10 0 0")])].concat()));
+        children.extend(chunk(b"nSHP", &[ints(&[3]), dict(&[]), ints(&[1, 0]), dict(&[])].concat()));
+        // Oculto: no debe aparecer
+        children.extend(chunk(b"nTRN", &[ints(&[4]), dict(&[("_hidden", "1")]), ints(&[3, -1, 0, 1]), dict(&[])].concat()));
+
+        let path = std::env::temp_dir().join("modelo_prueba.vox");
+        fs::write(&path, vox_file(children)).unwrap();
+
+        let model = load_vox(&path.display().to_string(), Vec3::new(0.0, 1.0, 0.0), 0.5, 4).unwrap();
+        assert_eq!((model.grid.block_count(), model.material_count), (2, 2));
+        // La x del modelo pasa a ser su y, que en la escena es -z
+        assert_eq!(model.grid.dims, [1, 1, 2]);
+        assert_eq!(model.grid.origin, Vec3::new(5.0, 1.0, -0.5));
+
+        let hit = model.grid.ray_intersect(&Vec3::new(5.25, 5.0, 0.25), &Vec3::new(0.0, -1.0, 0.0));
+        assert!(hit.is_intersecting && (hit.distance - 3.5).abs() < 1e-4);
+        assert!((hit.material.transparency - 0.8).abs() < 1e-6 && (hit.material.refraction_index - 1.3).abs() < 1e-6);
+        let hit = model.grid.ray_intersect(&Vec3::new(5.25, 5.0, -0.25), &Vec3::new(0.0, -1.0, 0.0));
+        assert!(hit.is_intersecting && hit.material.emissive_intensity == 1.5);
+        assert_eq!(hit.material.id, 5);
+    }
+
+    fn vox_file(children: Vec<u8>) -> Vec<u8> {
+        let mut bytes = b"VOX ".to_vec();
+        bytes.extend(ints(&[200]));
+        bytes.extend_from_slice(b"MAIN");
+        bytes.extend(ints(&[0, children.len() as i32]));
+        bytes.extend(children);
+        bytes
+    }
+
+    #[test]
+    fn test_reject_oversized_files() {
+        // Un nSHP que anuncia más modelos de los que caben en sus bytes
+        let shape = chunk(b"nSHP", &[ints(&[0]), dict(&[]), ints(&[i32::MAX])].concat());
+        assert!(parse_vox(&vox_file(shape)).err().unwrap().contains("no caben en el chunk"));
+
+        // Dos copias de un vóxel a 100000 vóxeles de distancia
+        let mut children = chunk(b"SIZE", &ints(&[1, 1, 1]));
+        children.extend(chunk(b"XYZI", &[ints(&[1]), vec![0, 0, 0, 1]].concat()));
+        children.extend(chunk(b"nTRN", &[ints(&[0]), dict(&[]), ints(&[1, -1, -1, 1]), dict(&[])].concat()));
+        children.extend(chunk(b"nGRP", &[ints(&[1]), dict(&[]), ints(&[2, 2, 4])].concat()));
+        children.extend(chunk(b"nTRN", &[ints(&[2]), dict(&[]), ints(&[5, -1, 0, 1]), dict(&[])].concat()));
+        children.extend(chunk(b"nTRN", &[ints(&[4]), dict(&[]), ints(&[5, -1, 0, 1]), dict(&[("_t", "100000 0 0")])].concat()));
+        children.extend(chunk(b"nSHP", &[ints(&[5]), dict(&[]), ints(&[1, 0]), dict(&[])].concat()));
+        let path = std::env::temp_dir().join("modelo_enorme.vox");
+        fs::write(&path, vox_file(children)).unwrap();
+        let error = load_vox(&path.display().to_string(), Vec3::zeros(), 1.0, 0).err().unwrap();
+        assert!(error.message.contains("más de los 2048 por lado"), "{}", error);
+    }
+
+    #[test]
+    fn test_reject_cyclic_scenes() {
+        // Un grupo que se contiene a sí mismo dos veces
+        let group = chunk(b"nGRP", &[ints(&[0]), dict(&[]), ints(&[2, 0, 0])].concat());
+        let error = parse_vox(&vox_file(group)).unwrap().instances().err().unwrap();
+        assert!(error.contains("se contiene a sí mismo"), "{}", error);
+
+        // Sesenta grupos que repiten a su hijo: sin ciclos, pero 2^60 caminos
+        let mut children = chunk(b"SIZE", &ints(&[1, 1, 1]));
+        children.extend(chunk(b"XYZI", &[ints(&[1]), vec![0, 0, 0, 1]].concat()));
+        for id in 0..60 {
+            children.extend(chunk(b"nGRP", &[ints(&[id]), dict(&[]), ints(&[2, id + 1, id + 1])].concat()));
+        }
+        children.extend(chunk(b"nSHP", &[ints(&[60]), dict(&[]), ints(&[1, 0]), dict(&[])].concat()));
+        let error = parse_vox(&vox_file(children)).unwrap().instances().err().unwrap();
+        assert!(error.contains("más de"), "{}", error);
+    }
+
+    #[test]
+    fn test_default_palette() {
+        let palette = default_palette();
+        assert_eq!(palette[1], [0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(palette[2], [0xff, 0xff, 0xcc, 0xff]);
+        assert_eq!(palette[215], [0x00, 0x00, 0x33, 0xff]);
+        assert_eq!(palette[216], [0xee, 0x00, 0x00, 0xff]);
+        assert_eq!(palette[255], [0x11, 0x11, 0x11, 0xff]);
+    }
+}
@@ -0,0 +1,404 @@
+use nalgebra_glm::Vec3;
+use crate::materials::Material;
+use crate::ray_intersect::{RayIntersect, Intersect};
+use crate::aabb::{Aabb, inverse_direction};
+use crate::cube::face_tangents;
+use crate::light::{Light, LightShape};
+
+// Lado de un chunk en bloques; los chunks vacíos no ocupan memoria
+const CHUNK_SIZE: usize = 16;
+const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+// Identificador de bloque; el 0 siempre es aire
+pub type BlockId = u16;
+pub const AIR: BlockId = 0;
+
+type Chunk = [BlockId; CHUNK_VOLUME];
+
+// Mundo de bloques en una rejilla uniforme, recorrido con el DDA de Amanatides–Woo
+pub struct VoxelGrid {
+    pub origin: Vec3,     // Esquina mínima de la rejilla
+    pub voxel_size: f32,  // Lado de cada bloque
+    pub dims: [usize; 3], // Número de bloques por eje
+    chunk_dims: [usize; 3],
+    chunks: Vec<Option<Box<Chunk>>>,
+    palette: Vec<Material>, // Material por id de bloque (el índice 0 es el aire)
+    block_count: usize,
+}
+
+impl VoxelGrid {
+    pub fn new(origin: Vec3, voxel_size: f32, dims: [usize; 3]) -> Self {
+        let chunk_dims = [
+            dims[0].div_ceil(CHUNK_SIZE),
+            dims[1].div_ceil(CHUNK_SIZE),
+            dims[2].div_ceil(CHUNK_SIZE),
+        ];
+        let chunk_count = chunk_dims[0] * chunk_dims[1] * chunk_dims[2];
+        VoxelGrid {
+            origin,
+            voxel_size,
+            dims,
+            chunk_dims,
+            chunks: (0..chunk_count).map(|_| None).collect(),
+            palette: vec![Material::black()],
+            block_count: 0,
+        }
+    }
+
+    // Registra un material en la paleta y devuelve su id de bloque
+    pub fn add_material(&mut self, material: Material) -> BlockId {
+        self.palette.push(material);
+        (self.palette.len() - 1) as BlockId
+    }
+
+    #[allow(dead_code)]
+    pub fn block_count(&self) -> usize {
+        self.block_count
+    }
+
+    fn chunk_location(&self, x: usize, y: usize, z: usize) -> (usize, usize) {
+        let chunk = ((z / CHUNK_SIZE) * self.chunk_dims[1] + y / CHUNK_SIZE) * self.chunk_dims[0] + x / CHUNK_SIZE;
+        let local = ((z % CHUNK_SIZE) * CHUNK_SIZE + y % CHUNK_SIZE) * CHUNK_SIZE + x % CHUNK_SIZE;
+        (chunk, local)
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> BlockId {
+        if x >= self.dims[0] || y >= self.dims[1] || z >= self.dims[2] {
+            return AIR;
+        }
+        let (chunk, local) = self.chunk_location(x, y, z);
+        match &self.chunks[chunk] {
+            Some(blocks) => blocks[local],
+            None => AIR,
+        }
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, id: BlockId) {
+        if x >= self.dims[0] || y >= self.dims[1] || z >= self.dims[2] {
+            return;
+        }
+        assert!((id as usize) < self.palette.len(), "id de bloque {} sin material en la paleta", id);
+
+        let (chunk, local) = self.chunk_location(x, y, z);
+        if self.chunks[chunk].is_none() {
+            if id == AIR {
+                return;
+            }
+            self.chunks[chunk] = Some(Box::new([AIR; CHUNK_VOLUME]));
+        }
+
+        let blocks = self.chunks[chunk].as_mut().unwrap();
+        let previous = blocks[local];
+        blocks[local] = id;
+        match (previous == AIR, id == AIR) {
+            (true, false) => self.block_count += 1,
+            (false, true) => self.block_count -= 1,
+            _ => {}
+        }
+    }
+
+    fn bounds(&self) -> Aabb {
+        let size = Vec3::new(self.dims[0] as f32, self.dims[1] as f32, self.dims[2] as f32) * self.voxel_size;
+        Aabb::new(self.origin, self.origin + size)
+    }
+
+    fn block_at(&self, cell: &[i64; 3]) -> BlockId {
+        if cell.iter().any(|&c| c < 0) {
+            return AIR;
+        }
+        self.get(cell[0] as usize, cell[1] as usize, cell[2] as usize)
+    }
+
+    // Bloque rodeado de bloques por sus seis caras
+    fn is_enclosed(&self, x: usize, y: usize, z: usize) -> bool {
+        let cell = [x as i64, y as i64, z as i64];
+        (0..3).all(|axis| {
+            [-1, 1].iter().all(|step| {
+                let mut neighbor = cell;
+                neighbor[axis] += step;
+                self.block_at(&neighbor) != AIR
+            })
+        })
+    }
+
+    // Mismo mapeo de UV que Cube::get_uv, dentro del bloque golpeado, y las mismas
+    // direcciones en unidades del mundo
+    fn face_uv(&self, point: &Vec3, cell: &[i64; 3], axis: usize) -> ((f32, f32), (f32, f32)) {
+        let block_min = self.origin + Vec3::new(cell[0] as f32, cell[1] as f32, cell[2] as f32) * self.voxel_size;
+        let local = (point - block_min) / self.voxel_size;
+        let local = local.map(|c| c.clamp(0.0, 1.0));
+        let (u_axis, v_axis) = match axis {
+            0 => (2, 1), // Caras izquierda y derecha
+            1 => (0, 2), // Caras inferior y superior
+            _ => (0, 1), // Caras trasera y delantera
+        };
+        ((local[u_axis], local[v_axis]), (point[u_axis], point[v_axis]))
+    }
+
+    // La normal apunta contra el sentido en que avanzaba el rayo sobre `axis`
+    fn make_intersect(&self, point: Vec3, t: f32, cell: &[i64; 3], axis: usize, step: i64, id: BlockId) -> Intersect {
+        let mut normal = Vec3::zeros();
+        normal[axis] = -step as f32;
+        let material = &self.palette[id as usize];
+        let ((u, v), (world_u, world_v)) = self.face_uv(&point, cell, axis);
+        let (u, v) = material.texture_uv(u, v, world_u, world_v);
+        let (tangent, bitangent) = face_tangents(&normal);
+        Intersect::new(point, normal, t, material.clone(), u, v)
+            .with_tangents(tangent, bitangent)
+            .with_uv_extent(material.texture_uv_extent(self.voxel_size))
+    }
+
+    // Golpe al entrar en el bloque `id` de `cell`. Si la textura tiene un hueco en
+    // ese punto, el rayo cruza el bloque y puede golpear por dentro la cara de
+    // salida; `t_max` son ya las distancias a los bordes de salida de la celda
+    #[allow(clippy::too_many_arguments)]
+    fn block_hit(&self, ray_origin: &Vec3, ray_direction: &Vec3, t: f32, cell: &[i64; 3], axis: usize, step: &[i64; 3], t_max: &[f32; 3], id: BlockId) -> Option<Intersect> {
+        let point = ray_origin + ray_direction * t;
+        let entry = self.make_intersect(point, t, cell, axis, step[axis], id);
+        if !entry.material.is_cut(entry.u, entry.v) {
+            return Some(entry);
+        }
+        let exit_axis = next_axis(t_max);
+        let t_out = t_max[exit_axis];
+        let point = ray_origin + ray_direction * t_out;
+        let exit = self.make_intersect(point, t_out, cell, exit_axis, step[exit_axis], id);
+        if exit.material.is_cut(exit.u, exit.v) {
+            None
+        } else {
+            Some(exit)
+        }
+    }
+}
+
+// Eje del borde de celda más cercano sobre el rayo
+fn next_axis(t_max: &[f32; 3]) -> usize {
+    if t_max[0] < t_max[1] && t_max[0] < t_max[2] {
+        0
+    } else if t_max[1] < t_max[2] {
+        1
+    } else {
+        2
+    }
+}
+
+impl RayIntersect for VoxelGrid {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
+        let inv_dir = inverse_direction(ray_direction);
+        let bounds = self.bounds();
+
+        // Recortamos el rayo contra la caja de la rejilla
+        let t0 = (bounds.min - ray_origin).component_mul(&inv_dir);
+        let t1 = (bounds.max - ray_origin).component_mul(&inv_dir);
+        let mut t_enter = 0.0;
+        let mut enter_axis = None;
+        let mut t_exit = f32::INFINITY;
+        for axis in 0..3 {
+            let (near, far) = if t0[axis] < t1[axis] { (t0[axis], t1[axis]) } else { (t1[axis], t0[axis]) };
+            if near > t_enter {
+                t_enter = near;
+                enter_axis = Some(axis);
+            }
+            t_exit = t_exit.min(far);
+        }
+        if t_enter > t_exit || t_exit < 0.0 {
+            return Intersect::empty();
+        }
+
+        let step = [
+            if ray_direction.x > 0.0 { 1 } else { -1 },
+            if ray_direction.y > 0.0 { 1 } else { -1 },
+            if ray_direction.z > 0.0 { 1 } else { -1 },
+        ];
+
+        // Celda inicial, ya dentro de la rejilla
+        let start = ray_origin + ray_direction * t_enter;
+        let mut cell = [0i64; 3];
+        for axis in 0..3 {
+            let c = ((start[axis] - self.origin[axis]) / self.voxel_size).floor() as i64;
+            cell[axis] = c.clamp(0, self.dims[axis] as i64 - 1);
+        }
+
+        let mut t_max = [f32::INFINITY; 3];
+        let mut t_delta = [f32::INFINITY; 3];
+        for axis in 0..3 {
+            if ray_direction[axis] != 0.0 {
+                let boundary = self.origin[axis] + (cell[axis] + if step[axis] > 0 { 1 } else { 0 }) as f32 * self.voxel_size;
+                t_max[axis] = (boundary - ray_origin[axis]) * inv_dir[axis];
+                t_delta[axis] = self.voxel_size * inv_dir[axis].abs();
+            }
+        }
+
+        // Bloque en el que arranca el rayo: como en Cube, un rayo que nace dentro de un
+        // bloque no lo golpea, y las caras entre bloques iguales no son superficies
+        // Un bloque con recorte alfa que el rayo atraviesa por sus huecos cuenta como
+        // aire, para que las caras del siguiente bloque igual se vean
+        let mut current = self.block_at(&cell);
+        if let Some(axis) = enter_axis {
+            if current != AIR {
+                match self.block_hit(ray_origin, ray_direction, t_enter, &cell, axis, &step, &t_max, current) {
+                    Some(hit) => return hit,
+                    None => current = AIR,
+                }
+            }
+        }
+
+        loop {
+            let axis = next_axis(&t_max);
+
+            let t = t_max[axis];
+            if t > t_exit {
+                return Intersect::empty();
+            }
+
+            cell[axis] += step[axis];
+            if cell[axis] < 0 || cell[axis] >= self.dims[axis] as i64 {
+                return Intersect::empty();
+            }
+            t_max[axis] += t_delta[axis];
+
+            let id = self.block_at(&cell);
+            if id != AIR && id != current {
+                if let Some(hit) = self.block_hit(ray_origin, ray_direction, t, &cell, axis, &step, &t_max, id) {
+                    return hit;
+                }
+                current = AIR;
+                continue;
+            }
+            current = id;
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds()
+    }
+
+    // Un emisor por bloque emisivo; los bloques tapados por completo no iluminan nada
+    fn emitters(&self) -> Vec<Light> {
+        let mut emitters = Vec::new();
+        if !self.palette.iter().any(|material| material.is_emissive()) {
+            return emitters;
+        }
+        let size = Vec3::repeat(self.voxel_size);
+        for z in 0..self.dims[2] {
+            for y in 0..self.dims[1] {
+                for x in 0..self.dims[0] {
+                    let id = self.get(x, y, z);
+                    if id == AIR || self.is_enclosed(x, y, z) {
+                        continue;
+                    }
+                    let center = self.origin + (Vec3::new(x as f32, y as f32, z as f32) + Vec3::repeat(0.5)) * self.voxel_size;
+                    emitters.extend(Light::emitter(center, LightShape::Box { size }, &self.palette[id as usize]));
+                }
+            }
+        }
+        emitters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Cube;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    fn random_ray(rng: &mut StdRng, center: Vec3) -> (Vec3, Vec3) {
+        let origin = center + Vec3::new(
+            rng.gen_range(-20.0..20.0),
+            rng.gen_range(-20.0..20.0),
+            rng.gen_range(-20.0..20.0),
+        );
+        let target = center + Vec3::new(
+            rng.gen_range(-4.0..4.0),
+            rng.gen_range(-4.0..4.0),
+            rng.gen_range(-4.0..4.0),
+        );
+        (origin, (target - origin).normalize())
+    }
+
+    #[test]
+    fn test_single_block_matches_cube() {
+        let mut grid = VoxelGrid::new(Vec3::new(-1.0, -1.0, -1.0), 2.0, [1, 1, 1]);
+        let id = grid.add_material(Material::black());
+        grid.set(0, 0, 0, id);
+        let cube = Cube::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0), Material::black());
+
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..500 {
+            let (origin, direction) = random_ray(&mut rng, Vec3::zeros());
+            let expected = cube.ray_intersect(&origin, &direction);
+            let hit = grid.ray_intersect(&origin, &direction);
+            assert_eq!(hit.is_intersecting, expected.is_intersecting);
+            if expected.is_intersecting {
+                assert!((hit.distance - expected.distance).abs() < 1e-4);
+                assert!((hit.normal - expected.normal).norm() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn test_top_face_uv() {
+        let mut grid = VoxelGrid::new(Vec3::zeros(), 1.0, [4, 1, 4]);
+        let id = grid.add_material(Material::black());
+        grid.set(2, 0, 1, id);
+
+        let hit = grid.ray_intersect(&Vec3::new(2.25, 5.0, 1.75), &Vec3::new(0.0, -1.0, 0.0));
+        assert!(hit.is_intersecting);
+        assert!((hit.distance - 4.0).abs() < 1e-5);
+        assert_eq!(hit.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert!((hit.u - 0.25).abs() < 1e-5);
+        assert!((hit.v - 0.75).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_dda_matches_linear_cubes() {
+        let mut rng = StdRng::seed_from_u64(9);
+        let origin = Vec3::new(-4.0, -4.0, -4.0);
+        let mut grid = VoxelGrid::new(origin, 0.5, [16, 16, 16]);
+        let id = grid.add_material(Material::black());
+        let mut cubes = Vec::new();
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    if rng.gen_bool(0.05) {
+                        grid.set(x, y, z, id);
+                        let min = origin + Vec3::new(x as f32, y as f32, z as f32) * 0.5;
+                        cubes.push(Cube::new(min, min + Vec3::repeat(0.5), Material::black()));
+                    }
+                }
+            }
+        }
+        assert_eq!(grid.block_count(), cubes.len());
+
+        for _ in 0..500 {
+            let (ray_origin, direction) = random_ray(&mut rng, Vec3::zeros());
+            let expected = cubes
+                .iter()
+                .map(|cube| cube.ray_intersect(&ray_origin, &direction))
+                .filter(|hit| hit.is_intersecting)
+                .map(|hit| hit.distance)
+                .fold(f32::INFINITY, f32::min);
+
+            let hit = grid.ray_intersect(&ray_origin, &direction);
+            if expected.is_finite() {
+                assert!(hit.is_intersecting);
+                assert!((hit.distance - expected).abs() < 1e-3);
+            } else {
+                assert!(!hit.is_intersecting);
+            }
+        }
+    }
+
+    #[test]
+    fn test_empty_chunks_are_not_allocated() {
+        let mut grid = VoxelGrid::new(Vec3::zeros(), 1.0, [64, 64, 64]);
+        let id = grid.add_material(Material::black());
+        grid.set(3, 3, 3, id);
+        grid.set(70, 0, 0, id);
+        assert_eq!(grid.block_count(), 1);
+        assert_eq!(grid.chunks.iter().filter(|chunk| chunk.is_some()).count(), 1);
+        assert_eq!(grid.get(3, 3, 3), id);
+        grid.set(3, 3, 3, AIR);
+        assert_eq!(grid.block_count(), 0);
+    }
+}
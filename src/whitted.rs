@@ -0,0 +1,166 @@
+use nalgebra_glm::Vec3;
+use rand::{Rng, RngCore};
+use crate::bvh::Bvh;
+use crate::integrator::{reflect, refract, Integrator, RenderContext};
+use crate::light::Light;
+use crate::radiance::Radiance;
+use crate::ray_intersect::{Intersect, Object};
+
+// Profundidad máxima de reflexiones y refracciones
+const MAX_DEPTH: u32 = 3;
+pub const SHADOW_EPSILON: f32 = 1e-3;
+
+// Trazador Whitted: luz directa de cada fuente atenuada por la fracción visible
+// de la luz, más reflexión y refracción recursivas
+pub struct WhittedIntegrator {
+    max_depth: u32,
+}
+
+impl WhittedIntegrator {
+    pub fn new() -> Self {
+        WhittedIntegrator { max_depth: MAX_DEPTH }
+    }
+
+    // `travelled` es la distancia que el rayo ya recorrió desde la cámara
+    fn cast_ray(&self, ray_origin: &Vec3, ray_direction: &Vec3, context: &RenderContext, depth: u32, travelled: f32, rng: &mut dyn RngCore) -> Radiance {
+        if depth > self.max_depth {
+            return context.background(ray_direction); // Fondo si excedemos la profundidad máxima
+        }
+
+        // El objeto más cercano golpeado por el rayo, consultando la BVH
+        let intersect: Intersect = context.objects.closest_hit(ray_origin, ray_direction);
+
+        if !intersect.is_intersecting {
+            return context.background(ray_direction); // Cielo de fondo
+        }
+
+        let material = &intersect.material;
+
+        // Si el material es emisivo, sumamos su emisión
+        let mut final_color = if material.is_emissive() {
+            material.get_emission() // Obtener la emisión del material
+        } else {
+            Radiance::black()
+        };
+
+        let view_dir = (ray_origin - intersect.point).normalize();
+        // La luz usa la normal del mapa de normales; los rayos secundarios se desplazan
+        // con la geométrica para no chocar con la propia superficie
+        let shading_normal = intersect.shading_normal(context.texture_manager);
+        let travelled = travelled + intersect.distance;
+        let footprint = intersect.texture_footprint(ray_direction, travelled * context.pixel_spread);
+        let diffuse_color = intersect.material.get_diffuse_color(intersect.u, intersect.v, footprint, context.texture_manager);
+
+        // Luz ambiente del cielo, para que las zonas en sombra no queden negras
+        final_color += diffuse_color * context.ambient * intersect.material.albedo[0];
+
+        // Aporte difuso y especular de una luz según lo que se ve de ella, escalado por `scale`
+        let mut shade = |light: &Light, visible: VisibleLight, scale: f32| {
+            if visible.visibility <= 0.0 {
+                return;
+            }
+            let light_dir = visible.direction.normalize();
+            let reflect_dir = reflect(&-light_dir, &shading_normal);
+            let light_intensity = light.intensity * scale;
+
+            // Componente difusa, con el N·L de cada punto muestreado de la luz
+            let diffuse = diffuse_color * light.color * intersect.material.albedo[0] * visible.cosine * light_intensity;
+
+            // Componente especular, hacia la dirección media de la parte visible
+            let specular_intensity = view_dir.dot(&reflect_dir).max(0.0).powf(intersect.material.specular);
+            let specular = light.color * intersect.material.albedo[1] * specular_intensity * visible.visibility * light_intensity;
+
+            // Sumar luz difusa y especular de esta luz al color final
+            final_color += diffuse + specular;
+        };
+
+        // Iteramos sobre todas las luces
+        for light in context.lights.lights() {
+            shade(light, cast_shadow(&intersect, &shading_normal, light, light.sample_count(), context.objects, rng), 1.0);
+        }
+        // Y sobre unos pocos emisores al azar, compensando la probabilidad de elegirlos
+        let picks = context.lights.emitter_picks();
+        for _ in 0..picks {
+            if let Some((light, probability)) = context.lights.pick_emitter(rng.gen()) {
+                let visible = cast_shadow(&intersect, &shading_normal, light, 1, context.objects, rng);
+                shade(light, visible, 1.0 / (probability * picks as f32));
+            }
+        }
+
+        // Cálculo de reflexión
+        let mut reflect_color = Radiance::black();
+        let reflectivity = intersect.material.reflectivity;
+        let epsilon = 1e-3; // Pequeño desplazamiento para evitar "acné"
+
+        if reflectivity > 0.0 {
+            let reflect_dir = reflect(&-ray_direction, &shading_normal).normalize();
+            let reflect_origin = intersect.point + intersect.normal * epsilon;
+            reflect_color = self.cast_ray(&reflect_origin, &reflect_dir, context, depth + 1, travelled, rng);
+        }
+
+        // Cálculo de refracción
+        let mut refract_color = Radiance::black();
+        let transparency = intersect.material.transparency;
+
+        if transparency > 0.0 {
+            let refract_dir = refract(ray_direction, &shading_normal, intersect.material.refraction_index);
+            let refract_origin = intersect.point - intersect.normal * epsilon;
+            refract_color = self.cast_ray(&refract_origin, &refract_dir, context, depth + 1, travelled, rng);
+        }
+
+        // Combinar resultados: color difuso + especular + reflexión + refracción.
+        // El peso local no baja de cero aunque reflexión y transparencia sumen más de uno
+        (final_color * (1.0 - reflectivity - transparency).max(0.0)) + (reflect_color * reflectivity) + (refract_color * transparency)
+    }
+}
+
+impl Integrator for WhittedIntegrator {
+    fn radiance(&self, origin: &Vec3, direction: &Vec3, context: &RenderContext, rng: &mut dyn RngCore) -> Radiance {
+        self.cast_ray(origin, direction, context, 0, 0.0, rng)
+    }
+}
+
+// Lo que se ve de una luz desde un punto, promediado entre sus muestras
+struct VisibleLight {
+    // Fracción visible, ya atenuada según el tipo de luz: 1 sin obstáculos ni
+    // atenuación, 0 en sombra total
+    visibility: f32,
+    // N·L de las muestras visibles, con la misma atenuación
+    cosine: f32,
+    // Suma de las direcciones visibles, sin normalizar
+    direction: Vec3,
+}
+
+// Muestrea la luz en `samples` puntos desde el punto de la intersección, lo que da
+// la penumbra de las luces de área. Los rayos hacia una luz direccional no tienen
+// límite; los demás se detienen en la luz
+fn cast_shadow(
+    intersect: &Intersect,
+    normal: &Vec3,
+    light: &Light,
+    samples: u32,
+    objects: &Bvh<Object>,
+    rng: &mut dyn RngCore,
+) -> VisibleLight {
+    // Ajusta el origen del rayo de sombra para evitar la autointersección
+    let offset = intersect.normal * 1e-4; // Pequeño valor para evitar estar dentro del cubo
+    let shadow_ray_origin = intersect.point + offset;
+
+    let mut visible = VisibleLight { visibility: 0.0, cosine: 0.0, direction: Vec3::zeros() };
+    for _ in 0..samples {
+        let sample = light.sample(&shadow_ray_origin, rng);
+        if sample.weight <= 0.0 {
+            continue;
+        }
+        // El rayo se detiene antes de la luz para no chocar con el propio emisor
+        if objects.any_hit(&shadow_ray_origin, &sample.direction, sample.distance - SHADOW_EPSILON) {
+            continue;
+        }
+        visible.visibility += sample.weight;
+        visible.cosine += normal.dot(&sample.direction).clamp(0.0, 1.0) * sample.weight;
+        visible.direction += sample.direction * sample.weight;
+    }
+    visible.visibility /= samples as f32;
+    visible.cosine /= samples as f32;
+    visible
+}